@@ -0,0 +1,11 @@
+//! Command-line entrypoint for the headless renderer
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    if let Err(e) = brot3_render::main() {
+        eprintln!("{e:#}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,583 @@
+//! Command line argument definitions
+// (c) 2025 Ross Younger
+
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, ensure, Context as _};
+use const_default::ConstDefault as _;
+use glam::DVec2;
+use shader_common::{
+    enums::{
+        Algorithm, Antialias, Bailout, ColourStyle, Colourer, Interior, Interpolation, Mapping,
+        Modifier, OrbitTrap,
+    },
+    gradient::{sort_stops, GradientStop, GRADIENT_SIZE},
+    hybrid::{HybridStep, HYBRID_SIZE},
+    FragmentConstants, Palette, PushExponent,
+};
+use util::{BigComplex, BigVec2};
+
+use crate::render::View;
+
+#[derive(Debug, clap::Parser, Clone)]
+#[command(
+    version,
+    about = "Renders a fractal to an image file, without using the GPU"
+)]
+pub(crate) struct Args {
+    /// The file to write (PNG format)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// Width of the image in pixels
+    #[arg(long, default_value_t = FragmentConstants::DEFAULT_SIZE.x)]
+    pub width: u32,
+
+    /// Height of the image in pixels
+    #[arg(long, default_value_t = FragmentConstants::DEFAULT_SIZE.y)]
+    pub height: u32,
+
+    /// Supersamples each pixel, for anti-aliasing: on a 2x2 or 3x3 grid, or `jittered`
+    /// (at a random point in each cell of a 3x3 grid)
+    #[arg(long, value_name = "PATTERN", default_value = "off")]
+    pub antialias: Antialias,
+
+    /// Only supersamples the pixels on an edge, as the distance estimate or the iteration
+    /// counts of the neighbouring pixels show it (with `--antialias`)
+    #[arg(long)]
+    pub adaptive_antialias: bool,
+
+    /// Real part of the centre of the image.
+    ///
+    /// This may be given to any number of decimal places.
+    #[arg(long, value_name = "X", default_value = "-1", allow_hyphen_values = true,
+        value_parser = parse_coordinate)]
+    pub re: String,
+
+    /// Imaginary part of the centre of the image.
+    ///
+    /// This may be given to any number of decimal places.
+    #[arg(long, value_name = "Y", default_value = "0", allow_hyphen_values = true,
+        value_parser = parse_coordinate)]
+    pub im: String,
+
+    /// Zoom level (as shown in the GUI)
+    #[arg(short, long, default_value_t = 1.0)]
+    pub zoom: f64,
+
+    /// Selects the fractal algorithm to use
+    #[arg(
+        short = 'F',
+        long,
+        alias = "fractal",
+        value_name = "NAME",
+        default_value = "mandelbrot"
+    )]
+    pub fractal: Algorithm,
+
+    /// The exponent: an integer, a real number, or a complex number written as `RE,IM`
+    #[arg(short, long, default_value = "2", allow_hyphen_values = true)]
+    pub exponent: PushExponent,
+
+    /// Maximum number of iterations per point
+    #[arg(short, long, default_value_t = FragmentConstants::DEFAULT_MAX_ITER,
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(FragmentConstants::MAX_ITER_LIMIT)))]
+    pub max_iter: u32,
+
+    /// Renders the Julia set for the given parameter, written as `RE,IM`
+    #[arg(short, long, value_name = "RE,IM", allow_hyphen_values = true, value_parser = parse_dvec2)]
+    pub julia: Option<DVec2>,
+
+    /// A root of the polynomial for the `newton-roots` fractal, written as `RE,IM`.
+    ///
+    /// Give this three times, or not at all.
+    #[arg(long = "root", value_name = "RE,IM", allow_hyphen_values = true, value_parser = parse_dvec2)]
+    pub roots: Vec<DVec2>,
+
+    /// Weight of the previous point, for the `phoenix` fractal, written as `RE,IM`
+    #[arg(long, value_name = "RE,IM", allow_hyphen_values = true, value_parser = parse_dvec2,
+        default_value = "-0.5,0")]
+    pub phoenix: DVec2,
+
+    /// The iteration formula for the `formula` fractal, in terms of `z`, `c` and `n`
+    #[arg(long, value_name = "EXPR", default_value = "z^2 + c")]
+    pub formula: String,
+
+    /// A step of the sequence for the `hybrid` fractal, written as `ALGORITHM[:REPEAT[:EXPONENT]]`.
+    ///
+    /// Give this once for each step, in order. The default is `burning-ship`, then `mandelbrot`.
+    #[arg(long = "step", value_name = "STEP", allow_hyphen_values = true)]
+    pub steps: Vec<HybridStep>,
+
+    /// Orbits escape when they leave the region of this radius (larger radii give smoother colouring)
+    #[arg(long, value_name = "R", default_value_t = FragmentConstants::DEFAULT_ESCAPE_RADIUS)]
+    pub escape_radius: f32,
+
+    /// Selects the shape of the escape region
+    #[arg(long, value_name = "SHAPE", default_value = "circle")]
+    pub bailout: Bailout,
+
+    /// Selects the orbit trap, for the `trap` colourer and modifier
+    #[arg(long, value_name = "SHAPE", default_value = "point")]
+    pub trap: OrbitTrap,
+
+    /// Position of the `user-point` orbit trap, written as `RE,IM`
+    #[arg(long, value_name = "RE,IM", allow_hyphen_values = true, value_parser = parse_dvec2,
+        default_value = "-0.5,0.5")]
+    pub trap_point: DVec2,
+
+    /// Radius of the `circle` orbit trap
+    #[arg(long, value_name = "R", default_value_t = FragmentConstants::DEFAULT_TRAP_RADIUS,
+        allow_negative_numbers = true)]
+    pub trap_radius: f32,
+
+    /// Selects the colouring algorithm to use
+    #[arg(
+        short = 'C',
+        long,
+        alias = "colorer",
+        value_name = "NAME",
+        default_value = "log-rainbow"
+    )]
+    pub colourer: Colourer,
+
+    /// A stop of the `gradient` colourer, written as `POSITION:#RRGGBB` (the position is from 0 to 1).
+    ///
+    /// Give this once for each stop.
+    #[arg(long = "stop", value_name = "STOP", conflicts_with = "gradient_file")]
+    pub stops: Vec<GradientStop>,
+
+    /// Loads the stops of the `gradient` colourer from a palette file:
+    /// a Fractint map (`.map`), a GIMP gradient (`.ggr`), or a list of CSS hex colours
+    #[arg(long, value_name = "FILE")]
+    pub gradient_file: Option<PathBuf>,
+
+    /// Selects the colour space in which the `gradient` colourer blends between its stops
+    #[arg(long, value_name = "SPACE", default_value = "rgb")]
+    pub interpolation: Interpolation,
+
+    /// How the colourers which run through their palette once (`monochrome`, `lch-gradient` and the
+    /// colour maps) scale the iteration counts to it: relative to the maximum iteration count,
+    /// from the lowest to the highest count in the image, or spread evenly over the image
+    #[arg(long, value_name = "MAPPING", default_value = "fixed")]
+    pub mapping: Mapping,
+
+    /// Selects the colour style
+    #[arg(long, value_name = "STYLE", default_value = "continuous")]
+    pub colour_style: ColourStyle,
+
+    /// Selects the brightness modifier
+    #[arg(long, value_name = "STYLE", default_value = "standard")]
+    pub brightness_style: Modifier,
+
+    /// Selects the saturation modifier
+    #[arg(long, value_name = "STYLE", default_value = "standard")]
+    pub saturation_style: Modifier,
+
+    /// Selects the colouring of the inside of the set
+    #[arg(long, value_name = "STYLE", default_value = "black")]
+    pub interior: Interior,
+
+    /// Palette gradient
+    #[arg(long, default_value_t = Palette::DEFAULT.gradient)]
+    pub gradient: f32,
+
+    /// Palette offset
+    #[arg(long, default_value_t = Palette::DEFAULT.offset, allow_negative_numbers = true)]
+    pub offset: f32,
+
+    /// Palette saturation (not used by all colourers)
+    #[arg(long, default_value_t = Palette::DEFAULT.saturation)]
+    pub saturation: f32,
+
+    /// Palette lightness (not used by all colourers)
+    #[arg(long, default_value_t = Palette::DEFAULT.lightness)]
+    pub lightness: f32,
+
+    /// Palette gamma (not used by all colourers)
+    #[arg(long, default_value_t = Palette::DEFAULT.gamma)]
+    pub gamma: f32,
+
+    /// Direction the light comes from, in degrees anticlockwise (for the `lighting` modifier)
+    #[arg(long, value_name = "DEGREES", default_value_t = Palette::DEFAULT.light_azimuth)]
+    pub light_azimuth: f32,
+
+    /// Height of the light above the horizon, in degrees (for the `lighting` modifier)
+    #[arg(long, value_name = "DEGREES", default_value_t = Palette::DEFAULT.light_elevation)]
+    pub light_elevation: f32,
+
+    /// Proportion of the light which reaches every point, 0 to 1 (for the `lighting` modifier)
+    #[arg(long, default_value_t = Palette::DEFAULT.ambient)]
+    pub ambient: f32,
+
+    /// Strength of the highlights, 0 to 1 (for the `lighting` modifier)
+    #[arg(long, default_value_t = Palette::DEFAULT.specular)]
+    pub specular: f32,
+}
+
+impl Args {
+    /// Checks the arguments and converts them into a [`View`] ready to render
+    pub(crate) fn view(&self) -> anyhow::Result<View> {
+        ensure!(
+            self.width > 0 && self.height > 0,
+            "image size must not be zero"
+        );
+        ensure!(
+            self.zoom.is_finite() && self.zoom > 0.,
+            "zoom must be a positive number"
+        );
+        ensure!(
+            (FragmentConstants::MIN_ESCAPE_RADIUS..=FragmentConstants::MAX_ESCAPE_RADIUS)
+                .contains(&self.escape_radius),
+            "escape radius must be between {} and {}",
+            FragmentConstants::MIN_ESCAPE_RADIUS,
+            FragmentConstants::MAX_ESCAPE_RADIUS
+        );
+        ensure!(
+            self.trap_radius.is_finite() && self.trap_radius >= 0.,
+            "trap radius must not be negative"
+        );
+        ensure!(
+            self.steps.len() <= HYBRID_SIZE as usize,
+            "--step may be given at most {HYBRID_SIZE} times"
+        );
+        let hybrid = if self.steps.is_empty() {
+            HybridStep::default_sequence().to_vec()
+        } else {
+            self.steps.clone()
+        };
+        let newton_roots = match self.roots.as_slice() {
+            [] => FragmentConstants::DEFAULT_NEWTON_ROOTS,
+            [a, b, c] => [a.as_vec2(), b.as_vec2(), c.as_vec2()],
+            _ => anyhow::bail!("--root must be given exactly three times"),
+        };
+        let coordinate = |s: &str| {
+            util::parse_decimal(s, 0).map_err(|e| anyhow!("invalid co-ordinate {s}: {e}"))
+        };
+        Ok(View {
+            size: glam::uvec2(self.width, self.height),
+            centre: BigComplex::from(BigVec2::new(coordinate(&self.re)?, coordinate(&self.im)?)),
+            zoom: self.zoom / f64::from(FragmentConstants::UI_ZOOM_FACTOR),
+            algorithm: self.fractal,
+            exponent: self.exponent,
+            max_iter: self.max_iter,
+            julia: self.julia,
+            palette: self.palette()?,
+            newton_roots,
+            phoenix_parameter: self.phoenix.as_vec2(),
+            escape_radius: self.escape_radius,
+            bailout: self.bailout,
+            trap: self.trap,
+            trap_point: self.trap_point.as_vec2(),
+            trap_radius: self.trap_radius,
+            formula: util::compile_formula(&self.formula)
+                .map_err(|e| anyhow!("invalid formula: {e}"))?,
+            hybrid,
+            gradient: self.gradient()?,
+            antialias: self.antialias,
+            adaptive_antialias: self.adaptive_antialias,
+        })
+    }
+
+    /// The stops of the gradient colourer, in order
+    fn gradient(&self) -> anyhow::Result<Vec<GradientStop>> {
+        let mut stops = if let Some(path) = &self.gradient_file {
+            let text =
+                std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+            // Palette files are often older than UTF-8; any text in them is only comments
+            util::import_palette(
+                &String::from_utf8_lossy(&text),
+                util::PaletteFormat::from_path(path),
+            )
+            .with_context(|| format!("importing {}", path.display()))?
+        } else if self.stops.is_empty() {
+            GradientStop::default_gradient().to_vec()
+        } else {
+            ensure!(
+                self.stops.len() <= GRADIENT_SIZE as usize,
+                "--stop may be given at most {GRADIENT_SIZE} times"
+            );
+            self.stops.clone()
+        };
+        sort_stops(&mut stops);
+        Ok(stops)
+    }
+
+    fn palette(&self) -> anyhow::Result<Palette> {
+        let palette = Palette {
+            colourer: self.colourer,
+            colour_style: self.colour_style,
+            brightness_style: self.brightness_style,
+            saturation_style: self.saturation_style,
+            interior: self.interior,
+            interpolation: self.interpolation,
+            mapping: self.mapping,
+            gradient: self.gradient,
+            offset: self.offset,
+            saturation: self.saturation,
+            lightness: self.lightness,
+            gamma: self.gamma,
+            light_azimuth: self.light_azimuth,
+            light_elevation: self.light_elevation,
+            ambient: self.ambient,
+            specular: self.specular,
+        };
+        palette.check_ranges().map_err(anyhow::Error::msg)?;
+        Ok(palette)
+    }
+}
+
+/// Parses a complex number written as `RE,IM`
+fn parse_pair<T>(s: &str) -> anyhow::Result<[T; 2]>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let (re, im) = s
+        .split_once(',')
+        .ok_or_else(|| anyhow!("expected a complex number written as RE,IM"))?;
+    Ok([re.trim().parse()?, im.trim().parse()?])
+}
+
+fn parse_dvec2(s: &str) -> anyhow::Result<DVec2> {
+    parse_pair(s).map(DVec2::from)
+}
+
+/// Checks that a co-ordinate is a valid decimal number
+fn parse_coordinate(s: &str) -> anyhow::Result<String> {
+    util::parse_decimal(s, 0)
+        .map(|_| s.trim().to_string())
+        .map_err(|e| anyhow!("expected a decimal number ({e})"))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::Args;
+    use clap::Parser as _;
+    use float_eq::assert_float_eq;
+    use pretty_assertions::assert_eq;
+    use shader_common::{
+        enums::{
+            Algorithm, Antialias, Bailout, Colourer, Interior, Interpolation, Mapping, OrbitTrap,
+        },
+        gradient::GradientStop,
+        hybrid::HybridStep,
+        FragmentConstants, NumericType,
+    };
+
+    fn args(extra: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["brot3-render", "-o", "out.png"].iter().chain(extra))
+    }
+
+    #[test]
+    fn defaults() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.size, glam::uvec2(800, 600));
+        assert_eq!(view.algorithm, Algorithm::Mandelbrot);
+        assert_float_eq!(view.zoom, 0.25, ulps <= 1);
+        assert_eq!(view.julia, None);
+        assert_eq!(
+            view.phoenix_parameter,
+            FragmentConstants::DEFAULT_PHOENIX_PARAMETER
+        );
+    }
+
+    #[test]
+    fn centre() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.centre, util::BigComplex::try_new(-1., 0.).unwrap());
+        // The centre is kept to as many places as it was given, far beyond f64
+        let re = "-1.90000000000000000000000000000271";
+        let view = args(&["--re", re, "--im", "-0.5"]).unwrap().view().unwrap();
+        assert_eq!(view.centre.x, util::parse_decimal(re, 0).unwrap());
+        assert_ne!(view.centre, util::BigComplex::try_new(-1.9, -0.5).unwrap());
+        assert!(args(&["--re", "1.2.3"]).is_err());
+    }
+
+    #[test]
+    fn exponent() {
+        let view = args(&["-e", "-1.5,2"]).unwrap().view().unwrap();
+        assert_eq!(view.exponent.typ, NumericType::Complex);
+        assert!(args(&["-e", "two"]).is_err());
+    }
+
+    #[test]
+    fn julia() {
+        let view = args(&["--julia", "-0.8,0.156", "-F", "burning-ship"])
+            .unwrap()
+            .view()
+            .unwrap();
+        assert_eq!(view.algorithm, Algorithm::BurningShip);
+        let c = view.julia.unwrap();
+        assert!((c.x + 0.8).abs() < 1e-6 && (c.y - 0.156).abs() < 1e-6);
+    }
+
+    #[test]
+    fn roots() {
+        let view = args(&["-F", "newton-roots", "--root", "1,0", "--root", "-1,0.5"])
+            .unwrap()
+            .view();
+        assert!(view.is_err());
+        let view = args(&[
+            "-F",
+            "newton-roots",
+            "--root",
+            "1,0",
+            "--root",
+            "-1,0.5",
+            "--root",
+            "0,-2",
+        ])
+        .unwrap()
+        .view()
+        .unwrap();
+        assert_eq!(view.newton_roots[2], glam::vec2(0., -2.));
+    }
+
+    #[test]
+    fn formula() {
+        let view = args(&["-F", "formula", "--formula", "z^3 + c"])
+            .unwrap()
+            .view()
+            .unwrap();
+        assert_eq!(view.algorithm, Algorithm::Formula);
+        assert_eq!(view.formula, util::compile_formula("z^3 + c").unwrap());
+        assert!(args(&["--formula", "z^"]).unwrap().view().is_err());
+    }
+
+    #[test]
+    fn hybrid() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.hybrid, HybridStep::default_sequence());
+        let view = args(&[
+            "-F",
+            "hybrid",
+            "--step",
+            "celtic:2",
+            "--step",
+            "mandelbar:1:-1.5,1",
+        ])
+        .unwrap()
+        .view()
+        .unwrap();
+        assert_eq!(view.algorithm, Algorithm::Hybrid);
+        assert_eq!(view.hybrid.len(), 2);
+        assert_eq!(view.hybrid[1].algorithm, Algorithm::Mandelbar);
+        assert!(args(&["--step", "newton"]).is_err());
+        let too_many = ["--step", "celtic"].repeat(17);
+        assert!(args(&too_many).unwrap().view().is_err());
+    }
+
+    #[test]
+    fn bailout() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.bailout, Bailout::Circle);
+        let view = args(&["--escape-radius", "1000", "--bailout", "manhattan"])
+            .unwrap()
+            .view()
+            .unwrap();
+        assert_eq!(view.bailout, Bailout::Manhattan);
+        assert_float_eq!(view.escape_radius, 1000., ulps <= 0);
+        assert!(args(&["--escape-radius", "1"]).unwrap().view().is_err());
+        assert!(args(&["--bailout", "triangle"]).is_err());
+    }
+
+    #[test]
+    fn trap() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.trap, OrbitTrap::Point);
+        assert_eq!(view.trap_point, FragmentConstants::DEFAULT_TRAP_POINT);
+        let view = args(&["--trap", "user-point", "--trap-point", "0.25,-1"])
+            .unwrap()
+            .view()
+            .unwrap();
+        assert_eq!(view.trap, OrbitTrap::UserPoint);
+        assert_eq!(view.trap_point, glam::vec2(0.25, -1.));
+        assert!(args(&["--trap-radius", "-1"]).unwrap().view().is_err());
+    }
+
+    #[test]
+    fn interior() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.palette.interior, Interior::Black);
+        let view = args(&["--interior", "multiplier"]).unwrap().view().unwrap();
+        assert_eq!(view.palette.interior, Interior::Multiplier);
+        assert!(args(&["--interior", "pink"]).is_err());
+    }
+
+    #[test]
+    fn mapping() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.palette.mapping, Mapping::Fixed);
+        let view = args(&["--mapping", "equalised"]).unwrap().view().unwrap();
+        assert_eq!(view.palette.mapping, Mapping::Equalised);
+        assert!(args(&["--mapping", "sideways"]).is_err());
+    }
+
+    #[test]
+    fn antialias() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.antialias, Antialias::Off);
+        assert!(!view.adaptive_antialias);
+        let view = args(&["--antialias", "grid3x3", "--adaptive-antialias"])
+            .unwrap()
+            .view()
+            .unwrap();
+        assert_eq!(view.antialias, Antialias::Grid3x3);
+        assert!(view.adaptive_antialias);
+        assert!(args(&["--antialias", "4x4"]).is_err());
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert!(args(&["--gamma", "5"]).unwrap().view().is_err());
+        assert!(args(&["--offset", "-11"]).unwrap().view().is_err());
+        assert!(args(&["--width", "0"]).unwrap().view().is_err());
+        assert!(args(&["--max-iter", "0"]).is_err());
+        assert!(args(&["--zoom", "0"]).unwrap().view().is_err());
+    }
+
+    #[test]
+    fn gradient() {
+        let view = args(&[]).unwrap().view().unwrap();
+        assert_eq!(view.gradient, GradientStop::default_gradient());
+        assert_eq!(view.palette.interpolation, Interpolation::Rgb);
+        let view = args(&[
+            "-C",
+            "gradient",
+            "--interpolation",
+            "lch",
+            "--stop",
+            "1:#ffffff",
+            "--stop",
+            "0:#000000",
+        ])
+        .unwrap()
+        .view()
+        .unwrap();
+        assert_eq!(view.palette.colourer, Colourer::Gradient);
+        assert_eq!(view.palette.interpolation, Interpolation::Lch);
+        // The stops are put in order
+        assert_eq!(
+            view.gradient,
+            ["0:#000000".parse().unwrap(), "1:#ffffff".parse().unwrap()]
+        );
+        assert!(args(&["--stop", "2:#ffffff"]).is_err());
+
+        let path = std::env::temp_dir().join(format!("brot3-test-{}.map", std::process::id()));
+        std::fs::write(&path, "0 0 0\n255 0 0 red\n0 0 255 blue\n").unwrap();
+        let view = args(&["--gradient-file", path.to_str().unwrap()])
+            .unwrap()
+            .view();
+        std::fs::remove_file(&path).unwrap();
+        let view = view.unwrap();
+        assert_eq!(view.gradient.len(), 3);
+        assert_eq!(view.gradient[1].colour(), glam::vec3(1., 0., 0.));
+        assert!(args(&["--gradient-file", "x.map", "--stop", "0:#000"]).is_err());
+        assert!(args(&["--gradient-file", "/nonexistent/x.map"])
+            .unwrap()
+            .view()
+            .is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Headless fractal renderer.
+//!
+//! This renders on the CPU, using the same code as the GPU shader, and writes the result
+//! to an image file. No window or graphics hardware is required.
+// (c) 2025 Ross Younger
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::Context as _;
+use clap::Parser as _;
+use glam::UVec2;
+
+mod cli;
+mod render;
+
+/// Entrypoint for the `brot3-render` binary
+pub fn main() -> anyhow::Result<()> {
+    let args = cli::Args::parse();
+    let view = args.view()?;
+    let pixels = render::render(&view);
+    write_png(&args.output, view.size, &pixels)
+        .with_context(|| format!("writing {}", args.output.display()))
+}
+
+/// Writes 8-bit sRGB pixel data to a PNG file
+fn write_png(path: &Path, size: UVec2, pixels: &[u8]) -> anyhow::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, size.x, size.y);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,605 @@
+//! Rendering on the CPU
+// (c) 2025 Ross Younger
+
+use glam::{uvec2, vec2, DVec2, IVec2, UVec2, Vec2, Vec3};
+use rayon::prelude::*;
+use shader::fractal;
+use shader_common::{
+    data::{PointResult, Supersamples},
+    enums::{Algorithm, Antialias, Bailout, OrbitTrap},
+    flag_if,
+    gradient::GradientStop,
+    histogram::{IterationStats, ReductionParams},
+    hybrid::HybridStep,
+    Flags, FragmentConstants, Palette, PushExponent,
+};
+use util::BigComplex;
+
+/// Above this zoom level, we render by perturbation if the fractal supports it
+const PERTURBATION_MIN_ZOOM: f64 = 1000.;
+/// Minimum precision of the reference orbit, in bits
+const MIN_PRECISION: usize = 128;
+/// Extra bits of precision we carry beyond the size of a pixel
+const GUARD_BITS: usize = 32;
+
+/// Everything we need to know to render an image
+#[derive(Clone, Debug)]
+pub(crate) struct View {
+    /// Image size in pixels
+    pub size: UVec2,
+    /// The point at the centre of the image, exactly as given
+    pub centre: BigComplex,
+    /// Zoom level, in the units of [`FragmentConstants::viewport_zoom`]
+    pub zoom: f64,
+    pub algorithm: Algorithm,
+    pub exponent: PushExponent,
+    pub max_iter: u32,
+    /// If present, renders the Julia set for this parameter
+    pub julia: Option<DVec2>,
+    pub palette: Palette,
+    /// Roots of the polynomial, for [`Algorithm::NewtonRoots`]
+    pub newton_roots: [Vec2; 3],
+    /// For [`Algorithm::Phoenix`]
+    pub phoenix_parameter: Vec2,
+    /// The bailout test: orbits escape when they leave the region of this radius and shape
+    pub escape_radius: f32,
+    pub bailout: Bailout,
+    /// The orbit trap, for colourers and modifiers which use it
+    pub trap: OrbitTrap,
+    /// Position of [`OrbitTrap::UserPoint`]
+    pub trap_point: Vec2,
+    /// Radius of [`OrbitTrap::Circle`]
+    pub trap_radius: f32,
+    /// Compiled bytecode, for [`Algorithm::Formula`]
+    pub formula: Vec<u32>,
+    /// The sequence, for [`Algorithm::Hybrid`]
+    pub hybrid: Vec<HybridStep>,
+    /// The stops of the gradient colourer, in order
+    pub gradient: Vec<GradientStop>,
+    /// Supersampling pattern, for anti-aliasing
+    pub antialias: Antialias,
+    /// If set, only the pixels on an edge are supersampled
+    pub adaptive_antialias: bool,
+}
+
+impl View {
+    /// Do we need to render by perturbation?
+    fn perturbed(&self) -> bool {
+        self.zoom > PERTURBATION_MIN_ZOOM
+            && self.julia.is_none()
+            && fractal::supports_perturbation(self.algorithm, &self.exponent)
+    }
+
+    /// Computes the reference orbit for the centre of the image
+    fn reference_orbit(&self) -> Vec<Vec2> {
+        #![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let pixel_bits = -FragmentConstants::pixel_spacing_f64(self.size.y, self.zoom)
+            .log2()
+            .ceil();
+        let precision = (pixel_bits as usize)
+            .saturating_add(GUARD_BITS)
+            .max(MIN_PRECISION);
+        let centre = self.centre.clone().with_precision(precision);
+        util::reference_orbit(&centre, self.max_iter, fractal::REFERENCE_ESCAPE_SQ)
+    }
+
+    /// The user-defined algorithms, as passed to the shader
+    fn custom(&self) -> fractal::Custom<'_> {
+        fractal::Custom {
+            formula: &self.formula,
+            hybrid: &self.hybrid,
+        }
+    }
+
+    fn fragment_constants(&self, perturbed: bool, reference_orbit_len: usize) -> FragmentConstants {
+        #![allow(clippy::cast_possible_truncation)]
+        FragmentConstants {
+            flags: Flags::NEEDS_REITERATE
+                | flag_if(perturbed, Flags::PERTURBED)
+                | flag_if(self.julia.is_some(), Flags::JULIA)
+                | flag_if(self.adaptive_antialias, Flags::ADAPTIVE_ANTIALIAS),
+            viewport_translate: self.centre.as_vec2(),
+            viewport_zoom: self.zoom as f32,
+            size: self.size.into(),
+            algorithm: self.algorithm,
+            max_iter: self.max_iter,
+            exponent: self.exponent,
+            palette: self.palette,
+            inspector_point_pixel_address: Vec2::ZERO,
+            julia_parameter: self.julia.unwrap_or_default().as_vec2(),
+            reference_orbit_len: reference_orbit_len as u32,
+            newton_roots: self.newton_roots,
+            phoenix_parameter: self.phoenix_parameter,
+            escape_radius: self.escape_radius,
+            bailout: self.bailout,
+            trap: self.trap,
+            trap_point: self.trap_point,
+            trap_radius: self.trap_radius,
+            // We don't keep a cache between images
+            grid_size: self.size,
+            grid_origin: UVec2::ZERO,
+            pan: IVec2::ZERO,
+            preview_offset: Vec2::ZERO,
+            preview_scale: 1.0,
+            slice_start: 0,
+            slice_end: 0,
+            sample_slice: 0,
+            antialias: self.antialias,
+        }
+    }
+}
+
+/// Renders an image, using all available CPU cores.
+///
+/// Returns the pixels as 8-bit sRGB triplets, in rows from the top left.
+pub(crate) fn render(view: &View) -> Vec<u8> {
+    #![allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    let perturbed = view.perturbed();
+    let orbit = if perturbed {
+        view.reference_orbit()
+    } else {
+        Vec::new()
+    };
+    let constants = view.fragment_constants(perturbed, orbit.len());
+    let custom = view.custom();
+    let pixel_spacing = constants.pixel_spacing();
+    let half_size = 0.5 * view.size.as_vec2();
+    let width = view.size.x as usize;
+    let row_bytes = width * 3;
+    // This is the same calculation as the fragment shader, which works with pixel centres
+    let offset_of =
+        |x: usize, y: usize| (vec2(x as f32 + 0.5, y as f32 + 0.5) - half_size) * pixel_spacing;
+    let point_at =
+        |x: usize, y: usize| fractal::render_point(&constants, offset_of(x, y), &orbit, custom);
+
+    let samples = view.antialias.samples();
+    let adaptive = samples > 1 && view.adaptive_antialias;
+    // If the palette adapts to the image, or only the edges are to be supersampled,
+    // we need all the points before we can colour any of them
+    let points = (view.palette.adapts_to_view() || adaptive).then(|| {
+        let mut points = vec![PointResult::default(); width * view.size.y as usize];
+        points
+            .par_chunks_exact_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, point) in row.iter_mut().enumerate() {
+                    *point = point_at(x, y);
+                }
+            });
+        points
+    });
+    let stats = points.as_ref().map_or(IterationStats::EMPTY, |points| {
+        let params = ReductionParams::new(view.size.into(), view.palette.colour_style);
+        let grid_a = points.iter().map(PointResult::a).collect::<Vec<_>>();
+        shader::histogram::reduce(&params, &grid_a)
+    });
+    let lookups = shader::colour::Lookups {
+        gradient: &view.gradient,
+        stats: &stats,
+    };
+    let colour_of = |data| shader::colour::colour_data(data, &constants, pixel_spacing, lookups);
+    let height = view.size.y as usize;
+    // As the fragment shader does, look at the distance estimate and the neighbouring pixels
+    let on_edge = |points: &[PointResult], x: usize, y: usize| {
+        let point = points[y * width + x];
+        let neighbour = |dx: isize, dy: isize| {
+            let (x, y) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+            (x < width && y < height).then(|| points[y * width + x])
+        };
+        shader::antialias::near_edge(&point, pixel_spacing)
+            || [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .into_iter()
+                .filter_map(|(dx, dy)| neighbour(dx, dy))
+                .any(|other| shader::antialias::across_edge(point.a(), other.a()))
+    };
+    let supersample = |x: usize, y: usize| {
+        let pixel = uvec2(x as u32, y as u32);
+        // Add up the samples in the same order as the fragment shader, so the mean comes out the same
+        (0..samples)
+            .fold(Supersamples::default(), |sum, index| {
+                let offset = shader::antialias::sample_offset(view.antialias, pixel, index);
+                let offset = offset_of(x, y) + offset * pixel_spacing;
+                sum.with_sample(colour_of(fractal::render_point(
+                    &constants, offset, &orbit, custom,
+                )))
+            })
+            .mean()
+    };
+
+    let mut pixels = vec![0; row_bytes * height];
+    pixels
+        .par_chunks_exact_mut(row_bytes)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, rgb) in row.chunks_exact_mut(3).enumerate() {
+                let supersampled = samples > 1
+                    && match &points {
+                        Some(points) if adaptive => on_edge(points, x, y),
+                        _ => true,
+                    };
+                let colour = if supersampled {
+                    supersample(x, y)
+                } else {
+                    colour_of(match &points {
+                        Some(points) => points[y * width + x],
+                        None => point_at(x, y),
+                    })
+                };
+                rgb.copy_from_slice(&to_srgb8(colour));
+            }
+        });
+    pixels
+}
+
+/// Converts a linear RGB colour, as output by the shader, to 8-bit sRGB.
+///
+/// (On the GPU, this conversion is performed by the sRGB surface.)
+fn to_srgb8(colour: Vec3) -> [u8; 3] {
+    #![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    colour.to_array().map(|c| {
+        let c = c.clamp(0., 1.);
+        let s = if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1. / 2.4) - 0.055
+        };
+        // NaN saturates to 0
+        (s * 255.).round() as u8
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{render, to_srgb8, View};
+    use glam::{dvec2, uvec2, vec2, vec3, vec4, Vec4};
+    use pretty_assertions::assert_eq;
+    use shader_common::{
+        data::{PointResultA, PointResultB, PointResultC, Supersamples},
+        enums::{Algorithm, Antialias, Bailout, Colourer, Interpolation, Mapping, OrbitTrap},
+        gradient::GradientStop,
+        histogram::{IterationStats, ReductionParams},
+        Flags, FragmentConstants, Palette, PushExponent,
+    };
+    use util::{BigComplex, BigVec2};
+
+    fn centre(re: f64, im: f64) -> BigComplex {
+        BigComplex::try_new(re, im).unwrap()
+    }
+
+    fn test_view() -> View {
+        View {
+            size: uvec2(16, 12),
+            centre: centre(-1., 0.),
+            zoom: 0.25,
+            algorithm: Algorithm::Mandelbrot,
+            exponent: PushExponent::default(),
+            max_iter: 100,
+            julia: None,
+            palette: Palette::default(),
+            newton_roots: FragmentConstants::DEFAULT_NEWTON_ROOTS,
+            phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+            escape_radius: FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+            bailout: Bailout::Circle,
+            trap: OrbitTrap::Point,
+            trap_point: FragmentConstants::DEFAULT_TRAP_POINT,
+            trap_radius: FragmentConstants::DEFAULT_TRAP_RADIUS,
+            formula: Vec::new(),
+            hybrid: Vec::new(),
+            gradient: GradientStop::default_gradient().to_vec(),
+            antialias: Antialias::Off,
+            adaptive_antialias: false,
+        }
+    }
+
+    #[test]
+    fn srgb() {
+        assert_eq!(to_srgb8(vec3(0., 1., 0.5)), [0, 255, 188]);
+        assert_eq!(to_srgb8(vec3(-1., 2., f32::NAN)), [0, 255, 0]);
+    }
+
+    #[test]
+    fn matches_shader() {
+        check_matches_shader(&test_view());
+    }
+
+    #[test]
+    fn formula_matches_shader() {
+        check_matches_shader(&View {
+            algorithm: Algorithm::Formula,
+            formula: util::compile_formula("z^3 + c * sin(z) + 0.5i").unwrap(),
+            ..test_view()
+        });
+    }
+
+    #[test]
+    fn hybrid_matches_shader() {
+        check_matches_shader(&View {
+            algorithm: Algorithm::Hybrid,
+            hybrid: vec![
+                "burning-ship:2:3".parse().unwrap(),
+                "celtic:1:2.5".parse().unwrap(),
+            ],
+            ..test_view()
+        });
+    }
+
+    #[test]
+    fn gradient_matches_shader() {
+        for interpolation in [Interpolation::Rgb, Interpolation::Hsl, Interpolation::Lch] {
+            let mut palette = Palette::default().with_colourer(Colourer::Gradient);
+            palette.interpolation = interpolation;
+            check_matches_shader(&View {
+                palette,
+                gradient: vec![
+                    "0:#000080".parse().unwrap(),
+                    "0.3:#ffff00".parse().unwrap(),
+                    "0.7:#00ff80".parse().unwrap(),
+                ],
+                ..test_view()
+            });
+        }
+    }
+
+    #[test]
+    fn formula_matches_builtin() {
+        // The formula for the Mandelbrot set must give the same picture as the built-in algorithm.
+        // (The built-in algorithm short-cuts the main cardioid, so we look at a view without it.)
+        let view = View {
+            centre: centre(-0.75, 0.2),
+            zoom: 8.,
+            ..test_view()
+        };
+        let formula = View {
+            algorithm: Algorithm::Formula,
+            formula: util::compile_formula("z^2 + c").unwrap(),
+            ..view.clone()
+        };
+        assert_eq!(render(&view), render(&formula));
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn check_matches_shader(view: &View) {
+        // Every pixel must be the same colour as the fragment shader would draw
+        let pixels = render(view);
+        assert_eq!(pixels.len(), 16 * 12 * 3);
+
+        let mut constants = view.fragment_constants(false, 0);
+        let n = (view.size.x * view.size.y) as usize;
+        let mut grids = (
+            vec![PointResultA::default(); n],
+            vec![PointResultB::default(); n],
+            vec![PointResultC::default(); n],
+            vec![Supersamples::default(); n],
+        );
+        let mut outputs = run_shader(view, &constants, &IterationStats::EMPTY, &mut grids);
+        // As the UI does: reduce the cached points, then colour them again
+        let stats = if view.palette.adapts_to_view() {
+            let params = ReductionParams::new(view.size.into(), view.palette.colour_style);
+            shader::histogram::reduce(&params, &grids.0)
+        } else {
+            IterationStats::EMPTY
+        };
+        // Each frame from the cache adds a supersample
+        constants.flags.remove(Flags::NEEDS_REITERATE);
+        for _ in 0..view.antialias.samples() {
+            outputs = run_shader(view, &constants, &stats, &mut grids);
+        }
+        for (i, (rgb, output)) in pixels.chunks_exact(3).zip(outputs).enumerate() {
+            let (x, y) = (i % 16, i / 16);
+            assert_eq!(rgb, to_srgb8(output.truncate()), "pixel {x},{y}");
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn run_shader(
+        view: &View,
+        constants: &FragmentConstants,
+        stats: &IterationStats,
+        grids: &mut (
+            Vec<PointResultA>,
+            Vec<PointResultB>,
+            Vec<PointResultC>,
+            Vec<Supersamples>,
+        ),
+    ) -> Vec<Vec4> {
+        (0..16 * 12)
+            .map(|i| {
+                let mut output = Vec4::ZERO;
+                shader::main_fs(
+                    vec4((i % 16) as f32 + 0.5, (i / 16) as f32 + 0.5, 0., 0.),
+                    &constants.push(),
+                    &mut grids.0,
+                    &mut grids.1,
+                    &[],
+                    &view.formula,
+                    &view.hybrid,
+                    &mut grids.2,
+                    &view.gradient,
+                    stats,
+                    &mut [],
+                    &mut grids.3,
+                    &constants.view(),
+                    &mut output,
+                );
+                output
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mappings_match_shader() {
+        for mapping in [Mapping::AutoRange, Mapping::Equalised] {
+            for colourer in [Colourer::Monochrome, Colourer::Viridis] {
+                let mut palette = Palette::default().with_colourer(colourer);
+                palette.mapping = mapping;
+                check_matches_shader(&View {
+                    palette,
+                    ..test_view()
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn antialias_matches_shader() {
+        for antialias in [Antialias::Grid2x2, Antialias::Grid3x3, Antialias::Jittered] {
+            for adaptive_antialias in [false, true] {
+                check_matches_shader(&View {
+                    antialias,
+                    adaptive_antialias,
+                    ..test_view()
+                });
+            }
+        }
+        // Supersampling with a palette which adapts to the view
+        let mut palette = Palette::default().with_colourer(Colourer::Viridis);
+        palette.mapping = Mapping::Equalised;
+        check_matches_shader(&View {
+            palette,
+            antialias: Antialias::Grid2x2,
+            adaptive_antialias: true,
+            ..test_view()
+        });
+    }
+
+    #[test]
+    fn adaptive_antialias_samples_edges() {
+        // Every pixel is either supersampled or not, and both kinds are found in this view
+        let view = View {
+            centre: centre(-0.75, 0.2),
+            zoom: 8.,
+            ..test_view()
+        };
+        let plain = render(&view);
+        let full = render(&View {
+            antialias: Antialias::Grid3x3,
+            ..view.clone()
+        });
+        let adaptive = render(&View {
+            antialias: Antialias::Grid3x3,
+            adaptive_antialias: true,
+            ..view.clone()
+        });
+        let (mut edges, mut smooth) = (0, 0);
+        for ((plain, full), adaptive) in plain
+            .chunks_exact(3)
+            .zip(full.chunks_exact(3))
+            .zip(adaptive.chunks_exact(3))
+        {
+            if plain == full {
+                continue;
+            }
+            if adaptive == full {
+                edges += 1;
+            } else {
+                assert_eq!(adaptive, plain);
+                smooth += 1;
+            }
+        }
+        assert!(edges > 0 && smooth > 0, "{edges} edges, {smooth} smooth");
+    }
+
+    #[test]
+    fn equalised_uses_whole_palette() {
+        // Whatever the view, equalising spreads the escaped points over the whole palette
+        let mut palette = Palette::default().with_colourer(Colourer::Monochrome);
+        palette.mapping = Mapping::Equalised;
+        let view = View {
+            centre: centre(-0.75, 0.2),
+            zoom: 8.,
+            palette,
+            ..test_view()
+        };
+        let pixels = render(&view);
+        let darkest = pixels.iter().min().unwrap();
+        let brightest = pixels.iter().max().unwrap();
+        assert!(*darkest < 32, "darkest {darkest}");
+        assert!(*brightest > 224, "brightest {brightest}");
+    }
+
+    /// Compares a view rendered by perturbation against iterating each point at high precision
+    #[allow(clippy::cast_possible_truncation)]
+    fn check_perturbation(view: &View) {
+        assert!(view.perturbed());
+        let orbit = view.reference_orbit();
+        let constants = view.fragment_constants(true, orbit.len());
+        let spacing = constants.pixel_spacing();
+        let (mut checked, mut mismatches, mut escaped) = (0, 0, 0);
+        for y in 0..6u8 {
+            for x in 0..8u8 {
+                let offset = (vec2(f32::from(x), f32::from(y)) - vec2(3.5, 2.5)) * spacing;
+                let result =
+                    shader::fractal::render_point(&constants, offset, &orbit, view.custom());
+                let point = view.centre.clone()
+                    + BigComplex::from(BigVec2::try_from(offset.as_dvec2()).unwrap());
+                let expected =
+                    util::reference_orbit(&point, view.max_iter, view.escape_radius.powi(2)).len()
+                        as u32
+                        - 1;
+                // Points which are a tiny fraction of a pixel from the boundary are chaotic,
+                // and cannot be expected to agree; they are indistinguishable at this resolution.
+                if result.distance() < spacing * 1e-4 {
+                    continue;
+                }
+                checked += 1;
+                if !result.inside() {
+                    escaped += 1;
+                }
+                if result.iters_whole().abs_diff(expected) > expected / 100 {
+                    eprintln!("{x},{y}: {result:?}, expected {expected}");
+                    mismatches += 1;
+                }
+            }
+        }
+        assert!(checked > 24, "too few points checked");
+        assert!(escaped > 0, "test points should not all be inside the set");
+        assert!(mismatches <= 1, "{mismatches} points differ");
+    }
+
+    #[test]
+    fn perturbation() {
+        // Far beyond the precision of f32
+        check_perturbation(&View {
+            size: uvec2(8, 6),
+            centre: centre(-0.743_643_887_037_158_7, 0.131_825_904_205_301_97),
+            zoom: 1e9,
+            max_iter: 2000,
+            ..test_view()
+        });
+    }
+
+    #[test]
+    fn perturbation_beyond_f64() {
+        // A point on the real axis, where the set is a filament; the nearest f64
+        // to it is billions of pixels away.
+        let (re, im) = ("-1.90000000000000000000000000000271", "0");
+        let centre = BigComplex::from(BigVec2::new(
+            util::parse_decimal(re, 0).unwrap(),
+            util::parse_decimal(im, 0).unwrap(),
+        ));
+        let nearest = centre.as_dvec2();
+        assert_ne!(
+            BigComplex::try_new(nearest.x, nearest.y).unwrap(),
+            centre,
+            "the centre must not be representable in f64"
+        );
+        check_perturbation(&View {
+            size: uvec2(8, 6),
+            centre,
+            zoom: 1e28,
+            max_iter: 2000,
+            ..test_view()
+        });
+    }
+
+    #[test]
+    fn julia_not_perturbed() {
+        let view = View {
+            zoom: 1e6,
+            julia: Some(dvec2(-0.8, 0.156)),
+            ..test_view()
+        };
+        assert!(!view.perturbed());
+    }
+}
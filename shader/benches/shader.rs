@@ -5,9 +5,13 @@ fn main() {
 
 use divan::black_box;
 use shader::exponentiation::{Exp2, ExpFloat, ExpIntN, Exponentiator as _};
-use shader_common::enums::{Algorithm, Colourer};
-use shader_common::{data::PointResult, Flags, FragmentConstants, Palette, PushExponent};
-use shader_util::{vec2, Size, Vec2, Vec3};
+use shader_common::enums::{Algorithm, Antialias, Bailout, Colourer, OrbitTrap};
+use shader_common::{
+    data::{PointResult, DEFAULT_GRID_SIZE},
+    gradient::GradientStop,
+    Flags, FragmentConstants, Palette, PushExponent,
+};
+use shader_util::{vec2, Size, UVec2, Vec2, Vec3};
 use strum::VariantArray as _;
 
 use shader_common::Complex;
@@ -31,6 +35,24 @@ fn fractal(alg: Algorithm) -> PointResult {
         exponent: PushExponent::from(2),
         palette: Palette::default(),
         inspector_point_pixel_address: Vec2::default(),
+        julia_parameter: Vec2::default(),
+        reference_orbit_len: 0,
+        newton_roots: FragmentConstants::DEFAULT_NEWTON_ROOTS,
+        phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+        escape_radius: FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+        bailout: Bailout::Circle,
+        trap: OrbitTrap::Point,
+        trap_point: FragmentConstants::DEFAULT_TRAP_POINT,
+        trap_radius: FragmentConstants::DEFAULT_TRAP_RADIUS,
+        grid_size: DEFAULT_GRID_SIZE,
+        grid_origin: UVec2::default(),
+        pan: Default::default(),
+        preview_offset: Vec2::default(),
+        preview_scale: 1.0,
+        slice_start: 0,
+        slice_end: 0,
+        sample_slice: 0,
+        antialias: Antialias::Off,
     };
     shader::fractal::render(&consts, black_box(vec2(0.5, 0.5)))
 }
@@ -47,9 +69,35 @@ fn colour(col: Colourer) -> Vec3 {
         exponent: PushExponent::from(2),
         palette: Palette::default().with_colourer(col),
         inspector_point_pixel_address: Vec2::default(),
+        julia_parameter: Vec2::default(),
+        reference_orbit_len: 0,
+        newton_roots: FragmentConstants::DEFAULT_NEWTON_ROOTS,
+        phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+        escape_radius: FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+        bailout: Bailout::Circle,
+        trap: OrbitTrap::Point,
+        trap_point: FragmentConstants::DEFAULT_TRAP_POINT,
+        trap_radius: FragmentConstants::DEFAULT_TRAP_RADIUS,
+        grid_size: DEFAULT_GRID_SIZE,
+        grid_origin: UVec2::default(),
+        pan: Default::default(),
+        preview_offset: Vec2::default(),
+        preview_scale: 1.0,
+        slice_start: 0,
+        slice_end: 0,
+        sample_slice: 0,
+        antialias: Antialias::Off,
     };
     let data = PointResult::new_outside(3, 5.423, 0.123, 1., 2.);
-    shader::colour::colour_data(black_box(data), &consts, 0.0)
+    shader::colour::colour_data(
+        black_box(data),
+        &consts,
+        0.0,
+        shader::colour::Lookups {
+            gradient: &GradientStop::default_gradient(),
+            ..Default::default()
+        },
+    )
 }
 
 #[divan::bench(args = [0, 1, 2])]
@@ -0,0 +1,136 @@
+//! Supersampling, for anti-aliasing: see [`FragmentConstants::antialias`](shader_common::FragmentConstants::antialias).
+//!
+//! The fragment shader adds one sample to each pixel in each frame, until the pattern is complete.
+//! The CPU renderer takes them all at once. Both take the samples where [`sample_offset`] puts them,
+//! and use [`near_edge`] and [`across_edge`] to find the pixels which adaptive anti-aliasing samples.
+
+use spirv_std::glam::{uvec2, vec2, UVec2, Vec2};
+
+use shader_common::{
+    data::{PointResult, PointResultA},
+    enums::{Antialias, ColourStyle},
+};
+
+/// Adaptive anti-aliasing samples the points which the distance estimate puts within this many pixels of the set
+const EDGE_DISTANCE: f32 = 1.0;
+
+/// Adaptive anti-aliasing samples the pixels whose iteration count differs from a neighbour's by more than this
+const EDGE_ITERATIONS: f32 = 1.0;
+
+/// Where a sample of a pixel is taken, relative to the centre of the pixel, in pixels.
+///
+/// The regular grids start with the middle row, so the first sample of [`Antialias::Grid3x3`]
+/// is the centre itself. [`Antialias::Jittered`] takes a random point in each cell of a 3x3 grid,
+/// which trades the moiré of a regular pattern for noise; the points depend only on the pixel,
+/// so they are the same in every frame.
+pub fn sample_offset(pattern: Antialias, pixel: UVec2, index: u32) -> Vec2 {
+    match pattern {
+        Antialias::Grid2x2 => grid_point(2, index),
+        Antialias::Grid3x3 => grid_point(3, index),
+        Antialias::Jittered => grid_point(3, index) + (jitter(pixel, index) - 0.5) / 3.0,
+        Antialias::Off | _ => Vec2::ZERO,
+    }
+}
+
+/// The centre of cell `index` of an `n` by `n` grid over the pixel
+fn grid_point(n: u32, index: u32) -> Vec2 {
+    let i = (index + n * n / 2) % (n * n);
+    (uvec2(i % n, i / n).as_vec2() + 0.5) / n as f32 - 0.5
+}
+
+/// A pseudo-random point in the unit square, for a sample of a pixel
+fn jitter(pixel: UVec2, index: u32) -> Vec2 {
+    let h = hash(pixel.x ^ hash(pixel.y ^ hash(index)));
+    // 24 bits of each half of the hash is plenty
+    let unit = |bits: u32| (bits >> 8) as f32 / (1 << 24) as f32;
+    vec2(unit(h), unit(hash(h)))
+}
+
+/// An integer hash with good avalanche behaviour (Chris Wellons' `lowbias32`)
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+/// Does the distance estimate put the point within [`EDGE_DISTANCE`] pixels of the set?
+pub fn near_edge(point: &PointResult, pixel_spacing: f32) -> bool {
+    !point.inside() && point.distance() < EDGE_DISTANCE * pixel_spacing
+}
+
+/// Is there an edge between two neighbouring points?
+/// That is, is one inside the set and the other not, or are their iteration counts far apart?
+pub fn across_edge(a: PointResultA, b: PointResultA) -> bool {
+    a.inside() != b.inside()
+        || (a.iters(ColourStyle::Continuous) - b.iters(ColourStyle::Continuous)).abs()
+            > EDGE_ITERATIONS
+}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{across_edge, near_edge, sample_offset};
+    use shader_common::{data::PointResult, enums::Antialias};
+    use spirv_std::glam::{uvec2, Vec2};
+
+    fn offsets(pattern: Antialias, pixel: u32) -> Vec<Vec2> {
+        (0..pattern.samples())
+            .map(|i| sample_offset(pattern, uvec2(pixel, 7), i))
+            .collect()
+    }
+
+    #[test]
+    fn grids() {
+        for pattern in [Antialias::Grid2x2, Antialias::Grid3x3] {
+            let offsets = offsets(pattern, 0);
+            // The samples are spread evenly over the pixel, and balance around its centre
+            let sum: Vec2 = offsets.iter().sum();
+            assert!(sum.abs_diff_eq(Vec2::ZERO, 1e-6), "{pattern:?}: {sum}");
+            for (i, a) in offsets.iter().enumerate() {
+                assert!(a.abs().cmplt(Vec2::splat(0.5)).all(), "{pattern:?}: {a}");
+                assert!(!offsets[..i].contains(a), "{pattern:?}: {a} twice");
+            }
+        }
+        assert_eq!(offsets(Antialias::Grid2x2, 0)[0].abs(), Vec2::splat(0.25));
+        assert_eq!(offsets(Antialias::Grid3x3, 0)[0], Vec2::ZERO);
+        assert_eq!(offsets(Antialias::Off, 0), [Vec2::ZERO]);
+    }
+
+    #[test]
+    fn jittered() {
+        let grid = offsets(Antialias::Grid3x3, 0);
+        let a = offsets(Antialias::Jittered, 12);
+        // One sample in each cell of the grid, at a different place in each pixel
+        for (sample, cell) in a.iter().zip(&grid) {
+            assert!(
+                (*sample - *cell).abs().cmple(Vec2::splat(1. / 6.)).all(),
+                "{sample} is outside the cell at {cell}"
+            );
+        }
+        assert_eq!(a, offsets(Antialias::Jittered, 12));
+        assert_ne!(a, offsets(Antialias::Jittered, 13));
+        assert_ne!(a, grid);
+    }
+
+    #[test]
+    fn edges() {
+        let inside = PointResult::new_inside(0., 0., 0., 0);
+        let near = PointResult::new_outside(10, 0.2, 0.5, 0., 0.);
+        let far = PointResult::new_outside(11, 0.1, 2., 0., 0.);
+        let convergent = PointResult::new_outside(5, 0.5, f32::INFINITY, 0., 0.);
+        assert!(near_edge(&near, 1.));
+        assert!(!near_edge(&far, 1.));
+        assert!(!near_edge(&inside, 1.));
+        assert!(!near_edge(&convergent, 1.));
+
+        assert!(across_edge(inside.a(), near.a()));
+        assert!(!across_edge(inside.a(), inside.a()));
+        // 10.2 and 11.1 are close enough; 10.2 and 11.5 are not
+        assert!(!across_edge(near.a(), far.a()));
+        let further = PointResult::new_outside(11, 0.5, 2., 0., 0.);
+        assert!(across_edge(further.a(), near.a()));
+    }
+}
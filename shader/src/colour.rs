@@ -16,16 +16,44 @@ macro_rules! deprintln {
 use spirv_std::num_traits::real::Real;
 
 use core::f32::consts::TAU;
-use shader_common::enums::{ColourStyle, Colourer as CS, Modifier};
-use shader_util::colourspace::{Hsl, Lch, Rgb, Vec3Rgb};
+use shader_common::enums::{
+    ColourStyle, Colourer as CS, Interior, Interpolation, Mapping, Modifier,
+};
+use shader_common::gradient::GradientStop;
+use shader_common::histogram::IterationStats;
+use shader_util::colourspace::{srgb_to_linear, Hsl, Lch, Oklab, Oklch, Rgb, Vec3Rgb};
 
-use super::{vec3, FragmentConstants, PointResult};
+use super::{vec3, Complex, FragmentConstants, PointResult, Vec3};
+
+/// Palette data which is too large for the push constants and travels in storage buffers
+#[derive(Clone, Copy)]
+pub struct Lookups<'a> {
+    /// The stops of [`CS::Gradient`], in order
+    pub gradient: &'a [GradientStop],
+    /// The iteration counts in view, for [`Mapping::AutoRange`] and [`Mapping::Equalised`]
+    pub stats: &'a IterationStats,
+}
+
+#[cfg(not(target_arch = "spirv"))]
+impl Default for Lookups<'_> {
+    fn default() -> Self {
+        Self {
+            gradient: &[],
+            stats: &IterationStats::EMPTY,
+        }
+    }
+}
 
 pub fn colour_data(
     data: PointResult,
     constants: &FragmentConstants,
     pixel_spacing: f32,
+    lookups: Lookups,
 ) -> Vec3Rgb {
+    // The inside of the set has a palette of its own, which the modifiers don't apply to
+    if data.inside() && constants.palette.uses_interior() {
+        return interior(constants, pixel_spacing, &data).into();
+    }
     let iters = data.iters(constants.palette.colour_style);
     let mut hsl = if constants.palette.colour_style == ColourStyle::None {
         Hsl::WHITE
@@ -36,8 +64,15 @@ pub fn colour_data(
             CS::WhiteFade => white_fade(constants, iters, &data),
             CS::BlackFade => black_fade(constants, iters, &data),
             CS::OneLoneCoder => one_lone_coder(constants, iters, &data),
-            CS::LchGradient => lch_gradient(constants, iters, &data),
-            CS::Monochrome => monochrome(constants, iters, &data),
+            CS::LchGradient => lch_gradient(constants, iters, &data, lookups.stats),
+            CS::Monochrome => monochrome(constants, iters, &data, lookups.stats),
+            CS::Basin => basin(constants, iters, &data),
+            CS::Trap => trap(constants, iters, &data),
+            CS::Gradient => gradient(constants, iters, &data, lookups.gradient),
+            CS::Viridis => colour_map(constants, iters, &data, lookups.stats, &VIRIDIS),
+            CS::Magma => colour_map(constants, iters, &data, lookups.stats, &MAGMA),
+            CS::Cividis => colour_map(constants, iters, &data, lookups.stats, &CIVIDIS),
+            CS::OklchRainbow => oklch_rainbow(constants, iters, &data),
             _ => todo!(),
         }
     };
@@ -46,19 +81,27 @@ pub fn colour_data(
     hsl.l = factor_for(
         hsl.l,
         constants.palette.brightness_style,
+        constants,
         pixel_spacing,
         &data,
     );
     hsl.s = factor_for(
         hsl.s,
         constants.palette.saturation_style,
+        constants,
         pixel_spacing,
         &data,
     );
     hsl.into()
 }
 
-fn factor_for(input: f32, style: Modifier, pixel_spacing: f32, data: &PointResult) -> f32 {
+fn factor_for(
+    input: f32,
+    style: Modifier,
+    constants: &FragmentConstants,
+    pixel_spacing: f32,
+    data: &PointResult,
+) -> f32 {
     let factor = match style {
         shader_common::enums::Modifier::Filaments1 => {
             if data.inside() {
@@ -74,13 +117,58 @@ fn factor_for(input: f32, style: Modifier, pixel_spacing: f32, data: &PointResul
         }
         shader_common::enums::Modifier::FinalAngle => data.angle() / TAU + 0.5,
         shader_common::enums::Modifier::FinalRadius => {
-            let factor = data.radius_sqr() / crate::fractal::ESCAPE_THRESHOLD_SQ;
+            // How far the last point before escape was towards the edge of the escape region,
+            // measured in the shape of the bailout test (0..1)
+            let angle = data.angle();
+            let last = Complex::new(angle.cos(), angle.sin()) * data.radius_sqr().sqrt();
+            let factor = crate::fractal::bailout_norm_sq(last, constants.bailout)
+                / (constants.escape_radius * constants.escape_radius);
             deprintln!("rsqr {}, factor {factor}", data.radius_sqr());
             factor
         }
+        shader_common::enums::Modifier::Trap => trap_closeness(data.trap_distance()),
+        // The averages only describe escaping orbits, so leave the inside of the set alone
+        shader_common::enums::Modifier::StripeAverage if !data.inside() => data.stripe_average(),
+        shader_common::enums::Modifier::TriangleInequality if !data.inside() => {
+            data.triangle_average()
+        }
+        shader_common::enums::Modifier::CurvatureAverage if !data.inside() => {
+            data.curvature_average()
+        }
+        // Lighting needs a distance estimate, which points inside the set (and convergent algorithms) don't have
+        shader_common::enums::Modifier::Lighting
+            if !data.inside() && data.distance().is_finite() =>
+        {
+            lighting(constants, data.normal())
+        }
         _ => 1.0,
     };
-    factor * input
+    (factor * input).min(100.0)
+}
+
+/// Shades a point as if the distance estimate were a height field lit from the direction set in the palette.
+///
+/// We only know which way the surface faces, not how steep it is, so take the slope to be 45°.
+/// Returns the brightness, in 0..2: highlights can be brighter than the unlit colour.
+fn lighting(constants: &FragmentConstants, normal: f32) -> f32 {
+    /// Controls the size of the highlights
+    const SHININESS: f32 = 20.0;
+    let palette = &constants.palette;
+    let normal = vec3(normal.cos(), normal.sin(), 1.0).normalize();
+    let (azimuth, elevation) = (
+        palette.light_azimuth.to_radians(),
+        palette.light_elevation.to_radians(),
+    );
+    let light = vec3(
+        elevation.cos() * azimuth.cos(),
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+    );
+    let diffuse = normal.dot(light).max(0.0);
+    // Blinn-Phong, looking straight down
+    let halfway = (light + vec3(0.0, 0.0, 1.0)).normalize();
+    let highlight = normal.dot(halfway).max(0.0).powf(SHININESS);
+    palette.ambient + (1.0 - palette.ambient) * diffuse + palette.specular * highlight
 }
 
 fn dist_value(distance: f32, pixel_spacing: f32) -> f32 {
@@ -183,12 +271,27 @@ fn one_lone_coder(constants: &FragmentConstants, iters: f32, pixel: &PointResult
     .into()
 }
 
-fn monochrome(constants: &FragmentConstants, iters: f32, pixel: &PointResult) -> Hsl {
+/// Where an iteration count falls in the palette (0..1), for the colourers which run through it once
+fn palette_fraction(constants: &FragmentConstants, iters: f32, stats: &IterationStats) -> f32 {
+    match constants.palette.mapping {
+        Mapping::AutoRange => stats.auto_range(iters),
+        Mapping::Equalised => stats.equalised(iters),
+        // Mapping::Fixed
+        _ => iters.ln() / (constants.max_iter as f32).ln(),
+    }
+}
+
+fn monochrome(
+    constants: &FragmentConstants,
+    iters: f32,
+    pixel: &PointResult,
+    stats: &IterationStats,
+) -> Hsl {
     if pixel.inside() {
         return Hsl::BLACK;
     }
-    // Compute an input from 0..1, relative to max_iter
-    let input = iters.ln() / (constants.max_iter as f32).ln();
+    // Compute an input from 0..1, relative to max_iter (or to the view)
+    let input = palette_fraction(constants, iters, stats);
     // Scale the offset down to -2..2
     let offset = constants.palette.offset / 5.;
     // This palette has a gamma transfer function
@@ -197,14 +300,23 @@ fn monochrome(constants: &FragmentConstants, iters: f32, pixel: &PointResult) ->
 }
 
 /// LCH Gradient function from <https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set#LCH_coloring>
-fn lch_gradient(constants: &FragmentConstants, iters: f32, pixel: &PointResult) -> Hsl {
+fn lch_gradient(
+    constants: &FragmentConstants,
+    iters: f32,
+    pixel: &PointResult,
+    stats: &IterationStats,
+) -> Hsl {
     if pixel.inside() {
         return Hsl::BLACK;
     }
     // Input offset range is 0..10. As we're operating with a hue angle, scale it so that 0.0 === 360.
     let offset = constants.palette.offset * 36.;
 
-    let s: f32 = iters / constants.max_iter as f32;
+    let s: f32 = if constants.palette.mapping == Mapping::Fixed {
+        iters / constants.max_iter as f32
+    } else {
+        palette_fraction(constants, iters, stats)
+    };
     let v1 = (core::f32::consts::PI * s).cos();
     let lightness = 75.0 * v1 * v1;
     let hue = (s * 360.0 * constants.palette.gradient).powf(1.5) + offset;
@@ -212,10 +324,277 @@ fn lch_gradient(constants: &FragmentConstants, iters: f32, pixel: &PointResult)
     lch.into()
 }
 
+/// Successive multiples of the golden angle are well spread around the colour wheel
+const GOLDEN_ANGLE: f32 = 137.507_77; // DEGREES
+
+/// For algorithms which converge to a root: tints by the root reached, and shades by how long it took
+fn basin(constants: &FragmentConstants, iters: f32, pixel: &PointResult) -> Hsl {
+    if pixel.inside() {
+        return Hsl::BLACK;
+    }
+    // Input offset range is 0..10. As we're operating with a hue angle, scale it so that 0.0 === 360.
+    let offset = constants.palette.offset * 36.;
+    let hue = pixel.basin() as f32 * GOLDEN_ANGLE + offset;
+    // Points which converge quickly are brightest
+    let shade = 1.0 / (1.0 + 0.1 * constants.palette.gradient * iters);
+    let lightness = (2.0 * constants.palette.lightness * shade).min(100.0);
+    Hsl::new(hue, constants.palette.saturation, lightness)
+}
+
+/// How close the orbit came to the trap: 1 if it went through it, tending to 0 far away
+fn trap_closeness(distance: f32) -> f32 {
+    /// Controls how quickly the closeness falls away
+    const TRAP_FALLOFF: f32 = 8.0;
+    1.0 / (1.0 + TRAP_FALLOFF * distance)
+}
+
+/// Orbit trap colouring: hue by the closest approach of the orbit to the trap,
+/// brightest where the orbit passed through it
+fn trap(constants: &FragmentConstants, _iters: f32, pixel: &PointResult) -> Hsl {
+    // Points inside the set are coloured too; their orbits make the most interesting shapes.
+    let distance = pixel.trap_distance();
+    // Input offset range is 0..10. As we're operating with a hue angle, scale it so that 0.0 === 360.
+    let offset = constants.palette.offset * 36.;
+    let hue = -distance.max(f32::MIN_POSITIVE).ln() * constants.palette.gradient * 60. + offset;
+    let lightness = (2.0 * constants.palette.lightness * trap_closeness(distance)).min(100.0);
+    Hsl::new(hue, constants.palette.saturation, lightness)
+}
+
+/// Colours by the user-defined gradient, which repeats as the iteration count rises
+fn gradient(
+    constants: &FragmentConstants,
+    iters: f32,
+    pixel: &PointResult,
+    stops: &[GradientStop],
+) -> Hsl {
+    if pixel.inside() {
+        return Hsl::BLACK;
+    }
+    // Scaled to match the rainbow colourers: one trip round the gradient per trip round the colour wheel.
+    // Input offset range is 0..10, which goes once round the gradient.
+    let position =
+        iters.ln() * constants.palette.gradient * (100. / 360.) + constants.palette.offset * 0.1;
+    gradient_colour(
+        stops,
+        position - position.floor(),
+        constants.palette.interpolation,
+    )
+}
+
+/// Looks up a position (0..1) in a gradient, blending between the stops on either side of it
+pub fn gradient_colour(stops: &[GradientStop], position: f32, interpolation: Interpolation) -> Hsl {
+    if stops.is_empty() {
+        return Hsl::BLACK;
+    }
+    // Find the first stop after the position
+    let (mut lo, mut hi) = (0, stops.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if stops[mid].position <= position {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    // Before the first stop, or after the last, the colour is constant
+    if lo == 0 {
+        return Rgb::from(srgb_to_linear(stops[0].colour())).into();
+    }
+    let before = stops[lo - 1];
+    if lo == stops.len() || stops[lo].position.is_infinite() {
+        return Rgb::from(srgb_to_linear(before.colour())).into();
+    }
+    let after = stops[lo];
+    let fraction = (position - before.position) / (after.position - before.position);
+    // The stops are sRGB, and palettes are designed to be blended as such;
+    // but the lightness of Lch is perceptual, so it starts from linear RGB.
+    let (a, b) = (before.colour(), after.colour());
+    let srgb = match interpolation {
+        Interpolation::Hsl => {
+            let (a, b): (Hsl, Hsl) = (Rgb::from(a).into(), Rgb::from(b).into());
+            let (ha, hb) = grey_hues(a.h, a.s, b.h, b.s);
+            Hsl::new(
+                lerp_hue(ha, hb, fraction),
+                a.s + (b.s - a.s) * fraction,
+                a.l + (b.l - a.l) * fraction,
+            )
+            .into()
+        }
+        Interpolation::Lch => {
+            let (a, b): (Lch, Lch) = (srgb_to_linear(a).into(), srgb_to_linear(b).into());
+            let (ha, hb) = grey_hues(a.h, a.c, b.h, b.c);
+            return Lch::new(
+                a.l + (b.l - a.l) * fraction,
+                a.c + (b.c - a.c) * fraction,
+                lerp_hue(ha, hb, fraction),
+            )
+            .into();
+        }
+        // Interpolation::Rgb
+        _ => a.lerp(b, fraction),
+    };
+    Rgb::from(srgb_to_linear(srgb)).into()
+}
+
+/// Greys have no hue of their own; blending them with a colour should only change its saturation.
+fn grey_hues(hue_a: f32, chroma_a: f32, hue_b: f32, chroma_b: f32) -> (f32, f32) {
+    /// Colours less saturated than this count as grey
+    const GREY: f32 = 0.5;
+    match (chroma_a < GREY, chroma_b < GREY) {
+        (true, false) => (hue_b, hue_b),
+        (false, true) => (hue_a, hue_a),
+        _ => (hue_a, hue_b),
+    }
+}
+
+/// Blends between two hues (in degrees) the short way round the colour wheel
+fn lerp_hue(a: f32, b: f32, fraction: f32) -> f32 {
+    let difference = b - a;
+    let difference = difference - 360. * ((difference + 180.) / 360.).floor();
+    a + difference * fraction
+}
+
+/// Unpacks a colour written as `0xRRGGBB` (sRGB) into the range 0..1
+const fn hex(rgb: u32) -> Vec3 {
+    Vec3::new(
+        ((rgb >> 16) & 0xff) as f32 / 255.,
+        ((rgb >> 8) & 0xff) as f32 / 255.,
+        (rgb & 0xff) as f32 / 255.,
+    )
+}
+
+// Evenly spaced samples of the perceptually uniform colour maps of matplotlib
+// <https://bids.github.io/colormap/> and of Nuñez, Anderton & Renslow (2018)
+// <https://doi.org/10.1371/journal.pone.0199239>.
+// Blending between them in Oklab stays close to the originals.
+const VIRIDIS: [Vec3; 9] = [
+    hex(0x440154),
+    hex(0x472d7b),
+    hex(0x3b528b),
+    hex(0x2c728e),
+    hex(0x21908c),
+    hex(0x27ad81),
+    hex(0x5dc863),
+    hex(0xaadc32),
+    hex(0xfde725),
+];
+const MAGMA: [Vec3; 9] = [
+    hex(0x000004),
+    hex(0x1d1147),
+    hex(0x51127c),
+    hex(0x822681),
+    hex(0xb63679),
+    hex(0xe65164),
+    hex(0xfb8861),
+    hex(0xfec287),
+    hex(0xfcfdbf),
+];
+// Cividis is designed to be seen alike by those with and without red-green colour blindness
+const CIVIDIS: [Vec3; 5] = [
+    hex(0x00204d),
+    hex(0x414d6b),
+    hex(0x7c7b78),
+    hex(0xbcaf6f),
+    hex(0xffea46),
+];
+
+/// Colours by one of the perceptually uniform colour maps, whose lightness rises steadily from end to end.
+///
+/// The map runs forwards then backwards as the iteration count rises, so there are no seams;
+/// or, if the palette adapts to the view, once from end to end.
+fn colour_map<const N: usize>(
+    constants: &FragmentConstants,
+    iters: f32,
+    pixel: &PointResult,
+    stats: &IterationStats,
+    map: &[Vec3; N],
+) -> Hsl {
+    if pixel.inside() {
+        return Hsl::BLACK;
+    }
+    if constants.palette.mapping != Mapping::Fixed {
+        let position = palette_fraction(constants, iters, stats);
+        return Rgb::from(map_colour(map, position)).into();
+    }
+    // Scaled like the gradient colourer. Input offset range is 0..10, which goes once there and back.
+    let position =
+        iters.ln() * constants.palette.gradient * (100. / 360.) + constants.palette.offset * 0.1;
+    let position = 1. - (2. * (position - position.floor()) - 1.).abs();
+    Rgb::from(map_colour(map, position)).into()
+}
+
+/// Looks up a position (0..1) in a colour map, blending between its samples in Oklab.
+///
+/// Returns linear RGB.
+fn map_colour<const N: usize>(map: &[Vec3; N], position: f32) -> Vec3Rgb {
+    #![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let scaled = position.clamp(0., 1.) * (N - 1) as f32;
+    let i = (scaled as usize).min(N - 2);
+    let fraction = scaled - i as f32;
+    let a: Oklab = srgb_to_linear(map[i]).into();
+    let b: Oklab = srgb_to_linear(map[i + 1]).into();
+    Oklab::new(
+        a.l + (b.l - a.l) * fraction,
+        a.a + (b.a - a.a) * fraction,
+        a.b + (b.b - a.b) * fraction,
+    )
+    .into()
+}
+
+/// Oklch lightness of the rainbow at the default palette lightness.
+/// Around here, the most colourful rainbow of constant lightness fits inside sRGB.
+const OKLCH_RAINBOW_LIGHTNESS: f32 = 0.72;
+/// Oklch chroma of the rainbow at full saturation
+const OKLCH_RAINBOW_CHROMA: f32 = 0.12;
+
+/// A rainbow of constant lightness, unlike the HSL rainbows in which yellow and cyan stand out as bright bands
+fn oklch_rainbow(constants: &FragmentConstants, iters: f32, pixel: &PointResult) -> Hsl {
+    if pixel.inside() {
+        return Hsl::BLACK;
+    }
+    let palette = &constants.palette;
+    // Input offset range is 0..10. As we're operating with a hue angle, scale it so that 0.0 === 360.
+    let hue = iters.ln() * palette.gradient * 100. + palette.offset * 36.;
+    // Palette lightness and saturation are 0..100; 50 is the usual lightness
+    let lightness = (palette.lightness * (OKLCH_RAINBOW_LIGHTNESS / 50.)).min(1.0);
+    let chroma = palette.saturation * (OKLCH_RAINBOW_CHROMA / 100.);
+    Oklch::new(lightness, chroma, hue).into()
+}
+
+/// Colours a point inside the set by the cycle which attracted its orbit.
+///
+/// Each period has its own hue. Points where no cycle was found are black.
+fn interior(constants: &FragmentConstants, pixel_spacing: f32, pixel: &PointResult) -> Hsl {
+    let period = pixel.period();
+    if period == 0 {
+        return Hsl::BLACK;
+    }
+    // Input offset range is 0..10. As we're operating with a hue angle, scale it so that 0.0 === 360.
+    let offset = constants.palette.offset * 36.;
+    let hue = period as f32 * GOLDEN_ANGLE + offset;
+    let palette = &constants.palette;
+    match palette.interior {
+        Interior::Period => Hsl::new(hue, palette.saturation, palette.lightness),
+        Interior::Multiplier => {
+            // The multiplier is 0 at the centre of each component and has magnitude 1 at its edge;
+            // its angle winds once round the component.
+            let hue = hue + pixel.multiplier_arg().to_degrees() * palette.gradient;
+            let shade = 1.0 - pixel.multiplier_abs();
+            let lightness = (2.0 * palette.lightness * shade).min(100.0);
+            Hsl::new(hue, palette.saturation, lightness)
+        }
+        Interior::Distance => {
+            let shade = dist_value(pixel.interior_distance(), pixel_spacing);
+            Hsl::new(hue, palette.saturation, palette.lightness * shade)
+        }
+        _ => Hsl::BLACK,
+    }
+}
+
 #[cfg(all(test, not(target_arch = "spirv")))]
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
-    use super::{PointResult, Vec3Rgb};
+    use super::{Lookups, PointResult, Vec3Rgb};
     use crate::Vec3;
     use float_eq::float_eq;
     use shader_common::enums::{Algorithm, ColourStyle, Colourer, Modifier};
@@ -239,7 +618,10 @@ mod tests {
         let consts = FragmentConstants::default();
         let data = PointResult::new_outside(100, 0.0, 1.0, 0., 0.);
         let expected = Vec3Rgb::from([0.3247156, 1., 0.]);
-        assert_vec3_eq!(expected, super::colour_data(data, &consts, 0.0));
+        assert_vec3_eq!(
+            expected,
+            super::colour_data(data, &consts, 0.0, Lookups::default())
+        );
     }
 
     #[test]
@@ -254,7 +636,7 @@ mod tests {
         assert_eq!(consts.algorithm, Algorithm::Mandelbrot);
         let data = PointResult::new_outside(5, 0.31876, 1.0, 0., 0.);
         let expected = Vec3Rgb::from([1., 0.7824273, 0.]);
-        let result = super::colour_data(data, &consts, 0.0);
+        let result = super::colour_data(data, &consts, 0.0, Lookups::default());
         assert_vec3_eq!(result, expected);
     }
 
@@ -268,7 +650,7 @@ mod tests {
         assert_eq!(consts.algorithm, Algorithm::Mandelbrot);
         let data = PointResult::new_outside(10, 0.31876, 1.0, 0., 0.);
         let expected = Vec3Rgb::from([0.47777647, 0.03193772, 0.1543931]);
-        let result = super::colour_data(data, &consts, 0.0);
+        let result = super::colour_data(data, &consts, 0.0, Lookups::default());
         assert_vec3_eq!(result, expected);
     }
 
@@ -292,7 +674,7 @@ mod tests {
         consts.viewport_translate = pt;
         let data = crate::fractal::render(&consts, pt);
         eprintln!("data: {data:?}");
-        let result = super::colour_data(data, &consts, pixel_size);
+        let result = super::colour_data(data, &consts, pixel_size, Lookups::default());
         eprintln!("result: {result:?}");
         assert_eq!(result, Vec3Rgb::new(0.0, 0., 0.));
     }
@@ -317,8 +699,396 @@ mod tests {
         consts.viewport_translate = pt;
         let data = crate::fractal::render(&consts, pt);
         eprintln!("data: {data:?}");
-        let result = super::colour_data(data, &consts, pixel_size);
+        let result = super::colour_data(data, &consts, pixel_size, Lookups::default());
         eprintln!("result: {result:?}");
         assert_eq!(result, Vec3::splat(0.3254935));
     }
+
+    #[test]
+    fn basin() {
+        let consts = FragmentConstants {
+            palette: Palette::default().with_colourer(Colourer::Basin),
+            ..Default::default()
+        };
+        let colour = |iters, basin| {
+            let data =
+                PointResult::new_outside(iters, 0.0, f32::INFINITY, 0., 1.).with_basin(basin);
+            super::colour_data(data, &consts, 0.0, Lookups::default())
+        };
+        // Different basins have different hues
+        assert_ne!(colour(5, 1), colour(5, 2));
+        assert_ne!(colour(5, 2), colour(5, 3));
+        // Slower convergence is darker
+        assert!(colour(20, 1).length() < colour(5, 1).length());
+        let inside = super::colour_data(
+            PointResult::new_inside(0., 0., 0., 0),
+            &consts,
+            0.0,
+            Lookups::default(),
+        );
+        assert_eq!(inside, Vec3Rgb::ZERO);
+    }
+
+    #[test]
+    fn trap() {
+        use shader_common::enums::OrbitTrap;
+        use spirv_std::glam::vec2;
+        let plain = FragmentConstants {
+            max_iter: 200,
+            trap: OrbitTrap::Line,
+            ..Default::default()
+        };
+        let consts = FragmentConstants {
+            palette: Palette {
+                colourer: Colourer::Trap,
+                ..Palette::default()
+            },
+            ..plain
+        };
+        // The orbit of a real point runs along the line trap, that of an off-axis point doesn't
+        let on = crate::fractal::render(&consts, vec2(-1.8, 0.));
+        let off = crate::fractal::render(&consts, vec2(0.3, 0.6));
+        assert_eq!(
+            super::factor_for(1.0, Modifier::Trap, &consts, 0.0, &on),
+            1.0
+        );
+        let factor = super::factor_for(1.0, Modifier::Trap, &consts, 0.0, &off);
+        assert!((0.0..1.0).contains(&factor), "{factor}");
+        // Brighter where the orbit came closer; inside points are coloured too
+        let inside = crate::fractal::render(&consts, vec2(-0.1, 0.1));
+        assert!(inside.inside());
+        let luma = |data| {
+            let rgb = super::colour_data(data, &consts, 0.0, Lookups::default());
+            rgb.x + rgb.y + rgb.z
+        };
+        assert!(luma(on) > luma(off));
+        assert!(luma(inside) > 0.0);
+    }
+
+    #[test]
+    fn average_modifiers() {
+        use spirv_std::glam::vec2;
+        for (style, get) in [
+            (
+                Modifier::StripeAverage,
+                PointResult::stripe_average as fn(&_) -> f32,
+            ),
+            (Modifier::TriangleInequality, PointResult::triangle_average),
+            (Modifier::CurvatureAverage, PointResult::curvature_average),
+        ] {
+            let consts = FragmentConstants {
+                max_iter: 200,
+                palette: Palette::default().with_brightness(style),
+                ..Default::default()
+            };
+            let outside = crate::fractal::render(&consts, vec2(0.3, 0.6));
+            let factor = super::factor_for(1.0, style, &consts, 0.0, &outside);
+            assert_eq!(factor, get(&outside), "{style:?}");
+            assert!(factor > 0.0 && factor < 1.0, "{style:?}: {factor}");
+            // The inside of the set is left alone
+            let inside = crate::fractal::render(&consts, vec2(-0.1, 0.1));
+            assert_eq!(super::factor_for(0.7, style, &consts, 0.0, &inside), 0.7);
+        }
+    }
+
+    #[test]
+    fn interior() {
+        use shader_common::enums::Interior;
+        use spirv_std::glam::vec2;
+        let with = |interior| FragmentConstants {
+            max_iter: 1000,
+            palette: Palette {
+                interior,
+                ..Palette::default()
+            },
+            ..Default::default()
+        };
+        let luma = |consts: &FragmentConstants, c| {
+            let data = crate::fractal::render(consts, c);
+            assert!(data.inside());
+            let rgb = super::colour_data(data, consts, 0.05, Lookups::default());
+            rgb.x + rgb.y + rgb.z
+        };
+        let centre = vec2(0., 0.);
+        let near_edge = vec2(0.24, 0.);
+        assert_eq!(luma(&with(Interior::Black), centre), 0.0);
+        for interior in [Interior::Period, Interior::Multiplier, Interior::Distance] {
+            assert!(luma(&with(interior), centre) > 0.0, "{interior:?}");
+        }
+        // Shaded darker towards the edge of the component
+        for interior in [Interior::Multiplier, Interior::Distance] {
+            let consts = with(interior);
+            assert!(
+                luma(&consts, centre) > luma(&consts, near_edge),
+                "{interior:?}"
+            );
+        }
+        // Components of different period have different colours
+        let consts = with(Interior::Period);
+        let a = super::colour_data(
+            crate::fractal::render(&consts, centre),
+            &consts,
+            0.001,
+            Lookups::default(),
+        );
+        let b = super::colour_data(
+            crate::fractal::render(&consts, vec2(-1., 0.)),
+            &consts,
+            0.001,
+            Lookups::default(),
+        );
+        assert!(a.distance(b) > 0.1, "{a} vs {b}");
+    }
+
+    #[test]
+    fn lighting() {
+        use spirv_std::glam::vec2;
+        let consts = FragmentConstants {
+            max_iter: 100,
+            palette: Palette {
+                light_azimuth: 0.,
+                ..Palette::default().with_brightness(Modifier::Lighting)
+            },
+            ..Default::default()
+        };
+        let lit = |c| {
+            let data = crate::fractal::render(&consts, c);
+            super::factor_for(50.0, Modifier::Lighting, &consts, 0.0, &data)
+        };
+        // Lit from the east, so the eastern side of the set is brighter than the western
+        assert!(lit(vec2(0.5, 0.)) > lit(vec2(-2.5, 0.)));
+        // The inside of the set has no normal, so is left alone
+        assert_eq!(lit(vec2(0., 0.)), 50.0);
+        // All ambient, no highlights: there is no shading
+        let flat = Palette {
+            ambient: 1.0,
+            specular: 0.0,
+            ..consts.palette
+        };
+        let flat = FragmentConstants {
+            palette: flat,
+            ..consts
+        };
+        for normal in [-3.0, -1.0, 0.0, 1.0, 3.0] {
+            assert!((super::lighting(&flat, normal) - 1.0).abs() < 1e-6);
+        }
+        // Facing the light head on, with the highlight
+        let facing = Palette {
+            light_azimuth: 0.,
+            light_elevation: 45.,
+            ..consts.palette
+        };
+        let facing = FragmentConstants {
+            palette: facing,
+            ..consts
+        };
+        assert!(super::lighting(&facing, 0.0) > 1.0);
+        assert!(super::lighting(&facing, 0.0) > super::lighting(&facing, 2.0));
+    }
+
+    #[test]
+    fn radius_bailout_shapes() {
+        use shader_common::enums::Bailout;
+        use spirv_std::glam::vec2;
+        use strum::VariantArray as _;
+        // The final radius factor measures the last point against the escape region it was in
+        for shape in Bailout::VARIANTS {
+            for escape_radius in [2.0, 10.0, 1000.0] {
+                let consts = FragmentConstants {
+                    max_iter: 200,
+                    escape_radius,
+                    bailout: *shape,
+                    ..Default::default()
+                };
+                for pt in [vec2(0.3, 0.6), vec2(-1.8, 0.05), vec2(0.17388, 0.80085)] {
+                    let data = crate::fractal::render(&consts, pt);
+                    let factor = super::factor_for(1.0, Modifier::FinalRadius, &consts, 0.0, &data);
+                    assert!(
+                        (0.0..1.0001).contains(&factor),
+                        "{shape:?} {escape_radius} {pt}: {factor}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gradient() {
+        use shader_common::{enums::Interpolation, gradient::GradientStop};
+        use shader_util::colourspace::Hsl;
+        let stops = [
+            GradientStop::new(0.25, Vec3::new(1., 0., 0.)),
+            GradientStop::new(0.75, Vec3::new(0., 0., 1.)),
+            GradientStop::END,
+            GradientStop::END,
+        ];
+        let colour = |position, interpolation| -> Vec3Rgb {
+            super::gradient_colour(&stops, position, interpolation).into()
+        };
+        // The ends of the gradient are the colours of the outermost stops
+        assert_vec3_eq!(colour(0.0, Interpolation::Rgb), Vec3::new(1., 0., 0.));
+        assert_vec3_eq!(colour(0.25, Interpolation::Rgb), Vec3::new(1., 0., 0.));
+        assert_vec3_eq!(colour(0.9, Interpolation::Rgb), Vec3::new(0., 0., 1.));
+        // The stops are blended as sRGB, but the output is linear
+        assert_vec3_eq!(
+            colour(0.5, Interpolation::Rgb),
+            Vec3::new(0.214_041, 0., 0.214_041)
+        );
+        // Red to blue the short way round the colour wheel is through magenta
+        assert_vec3_eq!(colour(0.5, Interpolation::Hsl), Vec3::new(1., 0., 1.));
+        for position in [0.25, 0.75] {
+            assert_vec3_eq!(
+                colour(position, Interpolation::Lch),
+                colour(position, Interpolation::Rgb)
+            );
+        }
+        assert_ne!(
+            colour(0.5, Interpolation::Lch),
+            colour(0.5, Interpolation::Rgb)
+        );
+        // Blending with grey doesn't change the hue
+        let grey = [
+            GradientStop::new(0., Vec3::splat(0.5)),
+            GradientStop::new(1., Vec3::new(0., 1., 0.)),
+        ];
+        let hsl = super::gradient_colour(&grey, 0.2, Interpolation::Hsl);
+        assert!((hsl.h - 120.).abs() < 0.01, "{hsl:?}");
+        assert_eq!(
+            super::gradient_colour(&[], 0.5, Interpolation::Rgb),
+            Hsl::BLACK
+        );
+
+        // The colourer repeats the gradient
+        let consts = FragmentConstants {
+            palette: Palette::default().with_colourer(Colourer::Gradient),
+            ..Default::default()
+        };
+        let default_gradient = GradientStop::default_gradient();
+        let lookups = Lookups {
+            gradient: &default_gradient,
+            ..Lookups::default()
+        };
+        let data = PointResult::new_outside(3, 5.423, 0.123, 1., 2.);
+        let rgb = super::colour_data(data, &consts, 0.0, lookups);
+        assert_ne!(rgb, Vec3Rgb::ZERO);
+        let inside = PointResult::new_inside(0., 0., 0., 0);
+        assert_eq!(
+            super::colour_data(inside, &consts, 0.0, lookups),
+            Vec3Rgb::ZERO
+        );
+    }
+
+    #[test]
+    fn colour_maps() {
+        fn check<const N: usize>(map: &[Vec3; N]) {
+            use shader_util::colourspace::{srgb_to_linear, Oklab};
+            // The ends are the ends of the map
+            assert_vec3_eq!(super::map_colour(map, 0.0), srgb_to_linear(map[0]));
+            assert_vec3_eq!(super::map_colour(map, 1.0), srgb_to_linear(map[N - 1]));
+            // Lightness rises steadily
+            let lightness = (0..=20)
+                .map(|i| Oklab::from(super::map_colour(map, i as f32 / 20.)).l)
+                .collect::<Vec<_>>();
+            assert!(lightness.windows(2).all(|w| w[1] > w[0]), "{lightness:?}");
+        }
+        check(&super::VIRIDIS);
+        check(&super::MAGMA);
+        check(&super::CIVIDIS);
+
+        // The colourer runs there and back, without a seam
+        let consts = FragmentConstants {
+            palette: Palette::default().with_colourer(Colourer::Viridis),
+            ..Default::default()
+        };
+        let colour = |iters| {
+            super::colour_data(
+                PointResult::new_outside(iters, 0.0, 1.0, 0., 0.),
+                &consts,
+                0.0,
+                Lookups::default(),
+            )
+        };
+        assert_ne!(colour(3), colour(4));
+        let inside = PointResult::new_inside(0., 0., 0., 0);
+        assert_eq!(
+            super::colour_data(inside, &consts, 0.0, Lookups::default()),
+            Vec3Rgb::ZERO
+        );
+    }
+
+    #[test]
+    fn oklch_rainbow() {
+        use shader_util::colourspace::Oklab;
+        let consts = FragmentConstants {
+            palette: Palette::default().with_colourer(Colourer::OklchRainbow),
+            ..Default::default()
+        };
+        let colours = (1..40)
+            .map(|iters| {
+                let data = PointResult::new_outside(iters, 0.0, 1.0, 0., 0.);
+                super::colour_data(data, &consts, 0.0, Lookups::default())
+            })
+            .collect::<Vec<_>>();
+        // The hue goes round, but the lightness stays the same
+        assert!(colours.windows(2).all(|w| w[0].distance(w[1]) > 0.001));
+        for colour in colours {
+            let lightness = Oklab::from(colour).l;
+            assert!((lightness - 0.72).abs() < 0.01, "{colour}: {lightness}");
+        }
+    }
+
+    #[test]
+    fn mappings() {
+        use shader_common::{enums::Mapping, histogram::ReductionParams};
+        // A deep view: the counts in view are a small part of the range up to max_iter
+        let points =
+            [400, 500, 600, 700, 800, 1000].map(|i| PointResult::new_outside(i, 0., 1.0, 0., 0.));
+        let grid = points.map(|p| p.a());
+        let params = ReductionParams::new(
+            shader_util::Size {
+                width: 3,
+                height: 2,
+            },
+            ColourStyle::Discrete,
+        );
+        let stats = crate::histogram::reduce(&params, &grid);
+        let lookups = Lookups {
+            stats: &stats,
+            ..Lookups::default()
+        };
+        let lightness = |colourer, mapping, point| {
+            let mut palette = Palette::default()
+                .with_colourer(colourer)
+                .with_style(ColourStyle::Discrete);
+            palette.mapping = mapping;
+            palette.gamma = 1.0;
+            let consts = FragmentConstants {
+                max_iter: 100_000,
+                palette,
+                ..Default::default()
+            };
+            let rgb = super::colour_data(point, &consts, 0.0, lookups);
+            shader_util::colourspace::Oklab::from(rgb).l
+        };
+        let (first, last) = (points[0], points[5]);
+        let spread = |mapping| {
+            lightness(Colourer::Monochrome, mapping, last)
+                - lightness(Colourer::Monochrome, mapping, first)
+        };
+        // With the fixed mapping, the view uses a sliver of the palette; adapting, it uses all of it
+        assert!(spread(Mapping::Fixed) < 0.1, "{}", spread(Mapping::Fixed));
+        assert!(spread(Mapping::AutoRange) > 0.99);
+        assert!(spread(Mapping::Equalised) > 0.8);
+        // The colour maps run once from end to end
+        assert!(lightness(Colourer::Viridis, Mapping::AutoRange, first) < 0.5);
+        assert!(lightness(Colourer::Viridis, Mapping::AutoRange, last) > 0.9);
+        for mapping in [Mapping::AutoRange, Mapping::Equalised] {
+            let mut previous = -1.0;
+            for point in points {
+                let l = lightness(Colourer::Magma, mapping, point);
+                assert!(l > previous, "{mapping:?}");
+                previous = l;
+            }
+        }
+    }
 }
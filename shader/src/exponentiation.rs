@@ -7,6 +7,8 @@ pub trait Exponentiator: Copy + Clone {
     fn apply_to(self, z: Complex) -> Complex;
     /// For the function z := z^k + c, what is the derivative of the z^k term?
     fn derivative(self) -> Complex;
+    /// The exponent itself
+    fn value(self) -> Complex;
 }
 /// Special case for raising to the power 2
 #[derive(Copy, Clone, Debug)]
@@ -36,6 +38,10 @@ impl Exponentiator for Exp2 {
     fn derivative(self) -> Complex {
         Complex { re: 2.0, im: 0.0 }
     }
+    #[inline(always)]
+    fn value(self) -> Complex {
+        Complex { re: 2.0, im: 0.0 }
+    }
 }
 
 impl Exponentiator for ExpIntN {
@@ -54,6 +60,13 @@ impl Exponentiator for ExpIntN {
             im: 0.0,
         }
     }
+    #[inline(always)]
+    fn value(self) -> Complex {
+        Complex {
+            re: self.0 as f32,
+            im: 0.0,
+        }
+    }
 }
 
 impl Exponentiator for ExpFloat {
@@ -73,6 +86,13 @@ impl Exponentiator for ExpFloat {
             im: 0.0,
         }
     }
+    #[inline(always)]
+    fn value(self) -> Complex {
+        Complex {
+            re: self.0,
+            im: 0.0,
+        }
+    }
 }
 
 impl Exponentiator for ExpComplex {
@@ -96,6 +116,10 @@ impl Exponentiator for ExpComplex {
             im: self.0.im,
         }
     }
+    #[inline(always)]
+    fn value(self) -> Complex {
+        self.0
+    }
 }
 
 impl From<PushExponent> for ExpComplex {
@@ -232,4 +256,15 @@ mod tests {
             Complex::new(1.5, 3.0),
         );
     }
+
+    #[test]
+    fn values() {
+        assert_eq!(Exp2.value(), Complex::ONE * 2.0);
+        assert_eq!(ExpIntN(5).value(), Complex::ONE * 5.0);
+        assert_eq!(ExpFloat(-1.5).value(), Complex::ONE * -1.5);
+        assert_eq!(
+            ExpComplex(Complex::new(2.5, 3.0)).value(),
+            Complex::new(2.5, 3.0)
+        );
+    }
 }
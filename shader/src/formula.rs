@@ -0,0 +1,447 @@
+//! Interpreter for user-defined formulas.
+//! Can also be called on the host, which is how the CPU renderer matches the GPU.
+//!
+//! See [`shader_common::formula`] for the bytecode.
+
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+use core::ops::{Add, Neg, Sub};
+use spirv_std::glam::{vec2, Mat2, Vec2};
+
+use super::Complex;
+use crate::fractal::{abs_derivative, complex_matrix, sin_cos};
+use shader_common::formula::{op, FORMULA_HEADER_SIZE, FORMULA_STACK_SIZE};
+
+/// The degree of the formula in z, as recorded in the program header
+pub fn degree(program: &[u32]) -> f32 {
+    if program.len() < FORMULA_HEADER_SIZE {
+        return 2.0;
+    }
+    f32::from_bits(program[1])
+}
+
+/// Is the program holomorphic? It is unless it folds or conjugates the plane
+/// (by [`op::ABS`], [`op::RE`], [`op::IM`] or [`op::CONJ`]), in which case the derivative
+/// must be carried as a [`Mat2`].
+pub fn analytic(program: &[u32]) -> bool {
+    if program.len() < FORMULA_HEADER_SIZE {
+        return true;
+    }
+    let end = (FORMULA_HEADER_SIZE + program[0] as usize).min(program.len());
+    let mut analytic = true;
+    let mut pc = FORMULA_HEADER_SIZE;
+    while pc < end {
+        let opcode = program[pc];
+        analytic &= !matches!(opcode, op::ABS | op::RE | op::IM | op::CONJ);
+        pc += match opcode {
+            op::CONST => 3,
+            op::POWI => 2,
+            _ => 1,
+        };
+    }
+    analytic
+}
+
+/// The derivative carried along with each value on the stack
+pub trait Derivative: Copy + Add<Output = Self> + Sub<Output = Self> + Neg<Output = Self> {
+    /// `dc` times the identity
+    fn scalar(dc: f32) -> Self;
+    /// The chain rule, for a holomorphic function whose derivative is `w`
+    fn times(self, w: Complex) -> Self;
+    /// The chain rule, for a holomorphic function whose derivative is `1/w`
+    fn over(self, w: Complex) -> Self;
+    /// The chain rule, for the fold or projection `opcode` of the value `a`
+    fn fold(self, opcode: u32, a: Complex) -> Self;
+}
+
+/// For [analytic](analytic) programs, the complex derivative.
+///
+/// (For the others, this is only the derivative in the direction of `dz`.)
+impl Derivative for Complex {
+    #[inline(always)]
+    fn scalar(dc: f32) -> Self {
+        Complex::new(dc, 0.0)
+    }
+    #[inline(always)]
+    fn times(self, w: Complex) -> Self {
+        w * self
+    }
+    #[inline(always)]
+    fn over(self, w: Complex) -> Self {
+        self / w
+    }
+    #[inline(always)]
+    fn fold(self, opcode: u32, a: Complex) -> Self {
+        match opcode {
+            op::ABS => Complex::new(
+                abs_derivative(a.re) * self.re,
+                abs_derivative(a.im) * self.im,
+            ),
+            op::RE => Complex::new(self.re, 0.0),
+            op::IM => Complex::new(self.im, 0.0),
+            _ => self.conjugate(),
+        }
+    }
+}
+
+/// The Jacobian, as a map of the real plane
+impl Derivative for Mat2 {
+    #[inline(always)]
+    fn scalar(dc: f32) -> Self {
+        Mat2::from_diagonal(Vec2::splat(dc))
+    }
+    #[inline(always)]
+    fn times(self, w: Complex) -> Self {
+        complex_matrix(w) * self
+    }
+    #[inline(always)]
+    fn over(self, w: Complex) -> Self {
+        complex_matrix(Complex::ONE / w) * self
+    }
+    #[inline(always)]
+    fn fold(self, opcode: u32, a: Complex) -> Self {
+        let fold = match opcode {
+            op::ABS => Mat2::from_diagonal(vec2(abs_derivative(a.re), abs_derivative(a.im))),
+            op::RE => Mat2::from_diagonal(vec2(1.0, 0.0)),
+            // The imaginary part moves to the real axis
+            op::IM => Mat2::from_cols(Vec2::ZERO, vec2(1.0, 0.0)),
+            _ => Mat2::from_diagonal(vec2(1.0, -1.0)),
+        };
+        fold * self
+    }
+}
+
+/// Evaluates one iteration of a formula, by forward-mode automatic differentiation.
+///
+/// `dz` and `dc` are the derivatives of `z` and `c` with respect to the pixel.
+/// Returns the new value of z, and its derivative.
+///
+/// The program must have been checked by the host compiler. An empty program evaluates to zero.
+pub fn evaluate<D: Derivative>(
+    program: &[u32],
+    z: Complex,
+    dz: D,
+    c: Complex,
+    dc: f32,
+    n: u32,
+) -> (Complex, D) {
+    let zero = D::scalar(0.0);
+    if program.len() < FORMULA_HEADER_SIZE {
+        return (Complex::ZERO, zero);
+    }
+    let end = (FORMULA_HEADER_SIZE + program[0] as usize).min(program.len());
+    let mut value = [Complex::ZERO; FORMULA_STACK_SIZE];
+    let mut deriv = [zero; FORMULA_STACK_SIZE];
+    // Number of entries on the stack
+    let mut sp = 0;
+    let mut pc = FORMULA_HEADER_SIZE;
+
+    while pc < end {
+        let opcode = program[pc];
+        pc += 1;
+        match opcode {
+            op::Z => {
+                value[sp] = z;
+                deriv[sp] = dz;
+                sp += 1;
+            }
+            op::C => {
+                value[sp] = c;
+                deriv[sp] = D::scalar(dc);
+                sp += 1;
+            }
+            op::N => {
+                value[sp] = Complex::new(n as f32, 0.0);
+                deriv[sp] = zero;
+                sp += 1;
+            }
+            op::CONST => {
+                value[sp] =
+                    Complex::new(f32::from_bits(program[pc]), f32::from_bits(program[pc + 1]));
+                deriv[sp] = zero;
+                sp += 1;
+                pc += 2;
+            }
+            op::POWI => {
+                let k = program[pc] as i32;
+                pc += 1;
+                (value[sp - 1], deriv[sp - 1]) = powi(value[sp - 1], deriv[sp - 1], k);
+            }
+            op::ADD..=op::POW => {
+                sp -= 1;
+                (value[sp - 1], deriv[sp - 1]) =
+                    binary(opcode, value[sp - 1], deriv[sp - 1], value[sp], deriv[sp]);
+            }
+            _ => {
+                (value[sp - 1], deriv[sp - 1]) = unary(opcode, value[sp - 1], deriv[sp - 1]);
+            }
+        }
+    }
+    if sp == 0 {
+        return (Complex::ZERO, zero);
+    }
+    (value[sp - 1], deriv[sp - 1])
+}
+
+/// `a^k` for integer k, with its derivative
+#[inline(always)]
+fn powi<D: Derivative>(a: Complex, da: D, k: i32) -> (Complex, D) {
+    match k {
+        0 => (Complex::ONE, D::scalar(0.0)),
+        1 => (a, da),
+        // Special case, for parity with the built-in algorithms
+        2 => (a * a, da.times(Complex::new(2.0, 0.0) * a)),
+        _ => (
+            a.powi(k).to_rectangular(),
+            da.times(Complex::new(k as f32, 0.0) * a.powi(k - 1).to_rectangular()),
+        ),
+    }
+}
+
+/// `a op b`, with its derivative
+#[inline(always)]
+fn binary<D: Derivative>(opcode: u32, a: Complex, da: D, b: Complex, db: D) -> (Complex, D) {
+    match opcode {
+        op::ADD => (a + b, da + db),
+        op::SUB => (a - b, da - db),
+        op::MUL => (a * b, da.times(b) + db.times(a)),
+        op::DIV => (a / b, (da.times(b) - db.times(a)).over(b * b)),
+        op::POW => {
+            // 0^b is 0, and the logarithm is undefined
+            if a == Complex::ZERO {
+                return (Complex::ZERO, D::scalar(0.0));
+            }
+            // a^b = exp(b.ln(a))
+            let ln_a = a.ln();
+            let v = (b * ln_a).exp().to_rectangular();
+            (v, (db.times(ln_a) + da.times(b).over(a)).times(v))
+        }
+        _ => (Complex::ZERO, D::scalar(0.0)),
+    }
+}
+
+/// `f(a)`, with its derivative
+#[inline(always)]
+fn unary<D: Derivative>(opcode: u32, a: Complex, da: D) -> (Complex, D) {
+    match opcode {
+        op::NEG => (-a, -da),
+        op::ABS => (Complex::new(a.re.abs(), a.im.abs()), da.fold(opcode, a)),
+        op::RE => (Complex::new(a.re, 0.0), da.fold(opcode, a)),
+        op::IM => (Complex::new(a.im, 0.0), da.fold(opcode, a)),
+        op::CONJ => (a.conjugate(), da.fold(opcode, a)),
+        op::SIN => {
+            let (sin, cos) = sin_cos(a);
+            (sin, da.times(cos))
+        }
+        op::COS => {
+            let (sin, cos) = sin_cos(a);
+            (cos, da.times(-sin))
+        }
+        op::TAN => {
+            let (sin, cos) = sin_cos(a);
+            (sin / cos, da.over(cos * cos))
+        }
+        op::SINH | op::COSH => {
+            let e = a.exp().to_rectangular();
+            let e_neg = (-a).exp().to_rectangular();
+            let sinh = (e - e_neg) * 0.5;
+            let cosh = (e + e_neg) * 0.5;
+            if opcode == op::SINH {
+                (sinh, da.times(cosh))
+            } else {
+                (cosh, da.times(sinh))
+            }
+        }
+        op::EXP => {
+            let e = a.exp().to_rectangular();
+            (e, da.times(e))
+        }
+        op::LOG => (a.ln(), da.over(a)),
+        op::SQRT => {
+            if a == Complex::ZERO {
+                return (Complex::ZERO, D::scalar(0.0));
+            }
+            let v = (a.ln() * 0.5).exp().to_rectangular();
+            (v, da.over(v * 2.0))
+        }
+        _ => (Complex::ZERO, D::scalar(0.0)),
+    }
+}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{analytic, degree, evaluate, Complex};
+    use shader_common::formula::op;
+    use spirv_std::glam::Mat2;
+
+    /// Wraps some instructions in a program header
+    fn program(code: &[u32], degree: f32) -> Vec<u32> {
+        let mut program = vec![code.len() as u32, degree.to_bits()];
+        program.extend_from_slice(code);
+        program
+    }
+
+    fn constant(re: f32, im: f32) -> [u32; 3] {
+        [op::CONST, re.to_bits(), im.to_bits()]
+    }
+
+    fn assert_close(a: Complex, b: Complex, what: &str) {
+        assert!(
+            (a - b).abs() <= 1e-3 * (1.0 + b.abs()),
+            "{what}: {a} differs from {b}"
+        );
+    }
+
+    #[test]
+    fn mandelbrot() {
+        // z^2 + c, as the compiler emits it, must agree exactly with the built-in algorithm
+        let prog = program(&[op::Z, op::POWI, 2, op::C, op::ADD], 2.0);
+        let (z, dz, c) = (
+            Complex::new(0.3, -0.7),
+            Complex::new(1.5, 0.25),
+            Complex::new(-0.4, 0.6),
+        );
+        let (new_z, new_dz) = evaluate(&prog, z, dz, c, 1.0, 0);
+        assert_eq!(new_z, z * z + c);
+        assert_eq!(new_dz, Complex::new(2.0, 0.0) * z * dz + 1.0);
+        assert_eq!(degree(&prog), 2.0);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            evaluate(&[], Complex::ONE, Complex::ONE, Complex::ONE, 1.0, 0),
+            (Complex::ZERO, Complex::ZERO)
+        );
+        assert_eq!(degree(&[]), 2.0);
+    }
+
+    #[test]
+    fn operands() {
+        let mut code = vec![op::N];
+        code.extend(constant(0.5, -2.0));
+        code.push(op::MUL);
+        let (v, dv) = evaluate(
+            &program(&code, 2.0),
+            Complex::ZERO,
+            Complex::ZERO,
+            Complex::ZERO,
+            1.0,
+            4,
+        );
+        assert_eq!(v, Complex::new(2.0, -8.0));
+        assert_eq!(dv, Complex::ZERO);
+    }
+
+    #[test]
+    fn derivatives() {
+        // Each operation of z, as a function of z, compared with a numerical derivative
+        let mut cases: Vec<(&str, Vec<u32>)> = [
+            ("neg", op::NEG),
+            ("abs", op::ABS),
+            ("re", op::RE),
+            ("im", op::IM),
+            ("conj", op::CONJ),
+            ("sin", op::SIN),
+            ("cos", op::COS),
+            ("tan", op::TAN),
+            ("sinh", op::SINH),
+            ("cosh", op::COSH),
+            ("exp", op::EXP),
+            ("log", op::LOG),
+            ("sqrt", op::SQRT),
+        ]
+        .into_iter()
+        .map(|(name, opcode)| (name, vec![op::Z, opcode]))
+        .collect();
+        cases.push(("powi 0", vec![op::Z, op::POWI, 0]));
+        cases.push(("powi 1", vec![op::Z, op::POWI, 1]));
+        cases.push(("powi 5", vec![op::Z, op::POWI, 5]));
+        cases.push(("powi -2", vec![op::Z, op::POWI, (-2i32) as u32]));
+        cases.push(("z*z", vec![op::Z, op::Z, op::MUL]));
+        cases.push(("z/(z+c)", vec![op::Z, op::Z, op::C, op::ADD, op::DIV]));
+        cases.push(("z-c", vec![op::Z, op::C, op::SUB]));
+        cases.push(("z^z", vec![op::Z, op::Z, op::POW]));
+        let mut pow = vec![op::Z];
+        pow.extend(constant(2.5, 0.5));
+        pow.push(op::POW);
+        cases.push(("z^(2.5+0.5i)", pow));
+
+        let z = Complex::new(0.6, 0.3);
+        let c = Complex::new(-0.2, 0.1);
+        let h = 1e-3;
+        for (name, code) in cases {
+            let prog = program(&code, 2.0);
+            let (v, dv) = evaluate(&prog, z, Complex::ONE, c, 0.0, 0);
+            let (v_plus, _) = evaluate(&prog, z + h, Complex::ONE, c, 0.0, 0);
+            let (v_minus, _) = evaluate(&prog, z - h, Complex::ONE, c, 0.0, 0);
+            assert!(v.re.is_finite() && v.im.is_finite(), "{name}: {v}");
+            assert_close(dv, (v_plus - v_minus) / (2.0 * h), name);
+        }
+    }
+
+    #[test]
+    fn jacobians() {
+        // Folds are not holomorphic, so each column of the Jacobian is compared with
+        // the numerical derivative along that axis
+        for (name, opcode) in [
+            ("abs", op::ABS),
+            ("re", op::RE),
+            ("im", op::IM),
+            ("conj", op::CONJ),
+        ] {
+            let prog = program(&[op::Z, opcode, op::Z, op::MUL, op::C, op::ADD], 2.0);
+            assert!(!analytic(&prog), "{name}");
+            let z = Complex::new(-0.6, 0.3);
+            let c = Complex::new(-0.2, 0.1);
+            let h = 1e-3;
+            let (_, jacobian) = evaluate(&prog, z, Mat2::IDENTITY, c, 1.0, 0);
+            for (step, column) in [
+                (Complex::new(h, 0.0), jacobian.x_axis),
+                (Complex::new(0.0, h), jacobian.y_axis),
+            ] {
+                let (v_plus, _) = evaluate(&prog, z + step, Complex::ONE, c + step, 1.0, 0);
+                let (v_minus, _) = evaluate(&prog, z - step, Complex::ONE, c - step, 1.0, 0);
+                assert_close(
+                    Complex::new(column.x, column.y),
+                    (v_plus - v_minus) / (2.0 * h),
+                    name,
+                );
+            }
+        }
+        // The operand of POWI is not an opcode
+        assert!(analytic(&program(
+            &[op::Z, op::POWI, op::ABS, op::C, op::ADD],
+            2.0
+        )));
+        assert!(analytic(&[]));
+    }
+
+    #[test]
+    fn zero_powers() {
+        // These have no logarithm, and must not produce NaN
+        let mut code = vec![op::Z];
+        code.extend(constant(2.5, 0.0));
+        code.push(op::POW);
+        let (v, dv) = evaluate(
+            &program(&code, 2.5),
+            Complex::ZERO,
+            Complex::ONE,
+            Complex::ZERO,
+            1.0,
+            0,
+        );
+        assert_eq!((v, dv), (Complex::ZERO, Complex::ZERO));
+        let (v, _) = evaluate(
+            &program(&[op::Z, op::SQRT], 2.0),
+            Complex::ZERO,
+            Complex::ONE,
+            Complex::ZERO,
+            1.0,
+            0,
+        );
+        assert_eq!(v, Complex::ZERO);
+    }
+}
@@ -4,8 +4,29 @@
 #[cfg(not(target_arch = "spirv"))]
 const DEBUG_FRACTAL: bool = false;
 
-pub(crate) const ESCAPE_THRESHOLD: f32 = 10.0;
-pub(crate) const ESCAPE_THRESHOLD_SQ: f32 = ESCAPE_THRESHOLD * ESCAPE_THRESHOLD;
+/// Reference orbits for perturbation rendering stop when `|Z|²` exceeds this.
+///
+/// A perturbed orbit rebases when the reference runs out, so this only needs to be large enough
+/// that most points escape first, whatever the escape radius and bailout shape.
+pub const REFERENCE_ESCAPE_SQ: f32 =
+    4.0 * FragmentConstants::MAX_ESCAPE_RADIUS * FragmentConstants::MAX_ESCAPE_RADIUS;
+
+/// An orbit which returns to within this fraction of a pixel of an earlier point is
+/// considered to be periodic, so the point is inside the set.
+const PERIODICITY_TOLERANCE: f32 = 1e-3;
+
+/// Transcendental algorithms escape when the relevant part of z exceeds this.
+/// (They grow exponentially, so this is more than enough.)
+const TRANSCENDENTAL_ESCAPE: f32 = 50.0;
+
+/// Number of stripes per turn around the origin, for the stripe average
+const STRIPE_DENSITY: f32 = 5.0;
+
+/// Newton's method refines a point of an attracting cycle in at most this many steps
+const INTERIOR_NEWTON_STEPS: u32 = 8;
+
+/// Convergent algorithms stop when a step moves the orbit by less than this distance (squared).
+const CONVERGENCE_TOLERANCE_SQ: f32 = 1e-10;
 
 macro_rules! deprintln {
     ($($arg:tt)*) => {
@@ -19,58 +40,208 @@ macro_rules! deprintln {
 #[cfg(target_arch = "spirv")]
 use spirv_std::num_traits::real::Real;
 
-use super::{Complex, FragmentConstants, PointResult, Vec2};
+use super::{vec2, vec3, Complex, Flags, FragmentConstants, PointResult, Vec2, Vec3};
 use crate::exponentiation::Exponentiator;
+use shader_common::{
+    data::{OrbitState, PointResultC},
+    enums::{Algorithm, Bailout, OrbitTrap},
+    hybrid::HybridStep,
+    NumericType, Palette, PushExponent,
+};
+use spirv_std::glam::Mat2;
+
+use core::{f32::consts::FRAC_PI_2, marker::PhantomData};
 
-use core::marker::PhantomData;
+/// User-defined algorithms, which are too large for the push constants and travel in storage buffers
+#[derive(Clone, Copy, Default)]
+pub struct Custom<'a> {
+    /// Bytecode for [`Algorithm::Formula`], as compiled by the host
+    pub formula: &'a [u32],
+    /// The sequence for [`Algorithm::Hybrid`]
+    pub hybrid: &'a [HybridStep],
+}
 
+/// Renders a point.
+///
+/// This cannot render the user-defined algorithms; use [`render_custom`] for those.
 pub fn render(constants: &FragmentConstants, point: Vec2) -> PointResult {
-    use shader_common::{enums::Algorithm, NumericType};
-    let c = Complex::from(point);
+    render_custom(constants, point, Custom::default())
+}
+
+/// Renders a point, with access to the user-defined algorithms
+pub fn render_custom(constants: &FragmentConstants, point: Vec2, custom: Custom) -> PointResult {
+    render_slice(constants, point, custom, false, &mut OrbitState::default())
+}
+
+/// Renders a point given as an offset from the centre of the view: by perturbation against
+/// the reference orbit if [`Flags::PERTURBED`] is set, and directly otherwise
+pub fn render_point(
+    constants: &FragmentConstants,
+    offset: Vec2,
+    reference_orbit: &[Vec2],
+    custom: Custom,
+) -> PointResult {
+    if constants.flags.contains(Flags::PERTURBED) {
+        // The reference orbit is computed for the centre of the viewport
+        render_perturbed(constants, offset, reference_orbit)
+    } else {
+        render_custom(constants, offset + constants.viewport_translate, custom)
+    }
+}
+
+/// Renders a point, taking its orbit only as far as [`FragmentConstants::iteration_limit`]
+/// (for [`Flags::PROGRESSIVE`]).
+///
+/// If `resume` is set, the orbit carries on from `orbit`, which is where the last slice left it
+/// after `constants.slice_start` iterations; otherwise it starts afresh.
+/// If the orbit is still unfinished at the end of the slice, it is saved in `orbit` and the
+/// point is reported as inside the set.
+///
+/// Only the settings which pass [`supports_progressive`] can be resumed.
+pub fn render_slice(
+    constants: &FragmentConstants,
+    point: Vec2,
+    custom: Custom,
+    resume: bool,
+    orbit: &mut OrbitState,
+) -> PointResult {
+    let point = Complex::from(point);
+    // Mandeldrop is the same algorithm but with the pixel inverted
+    let point = if constants.algorithm == Algorithm::Mandeldrop {
+        point.recip()
+    } else {
+        point
+    };
+    // In Julia mode the pixel is the starting point of the orbit and c is fixed;
+    // otherwise the orbit starts at zero and the pixel is c.
+    // Newton's method has no c, so the pixel is always the starting point.
+    // Nova starts from 1, a critical point of the Newton map; the sine algorithms
+    // start from pi/2, a critical point of sin.
+    let julia = constants.flags.contains(Flags::JULIA);
+    let (z0, c) = match constants.algorithm {
+        Algorithm::Newton | Algorithm::NewtonRoots => (point, Complex::ZERO),
+        _ if julia => (point, Complex::from(constants.julia_parameter)),
+        Algorithm::Nova => (Complex::ONE, point),
+        Algorithm::Sine | Algorithm::SinePlusC => (Complex::new(FRAC_PI_2, 0.0), point),
+        // the critical point of the logistic map
+        Algorithm::Lambda => (Complex::new(0.5, 0.0), point),
+        _ => (Complex::ZERO, point),
+    };
+
+    // The shortcut has no orbit, so can't be used when colouring by the orbit trap.
+    // (Nor does it apply to resumed orbits, which didn't take it the first time.)
+    if quadratic_mandelbrot(constants, julia) && !constants.palette.uses_trap() && !resume {
+        let period = mandelbrot_component(c);
+        if period != 0 {
+            // There is no orbit to report, but we know where the cycle is
+            let result = PointResult::new_inside(0., 0., 0., period);
+            if !constants.palette.uses_interior() {
+                return result;
+            }
+            let z = if period == 1 {
+                (Complex::ONE - (Complex::ONE - c * 4.0).powf(0.5).to_rectangular()) * 0.5
+            } else {
+                ((c * -4.0 - 3.0).powf(0.5).to_rectangular() - 1.0) * 0.5
+            };
+            return result.with_extra(mandelbrot_interior(z, c, period));
+        }
+    }
+
+    // The size of a pixel in the plane we are iterating over.
+    // Mandeldrop's inversion scales it by |c|² (the derivative of 1/p is -1/p²).
+    let pixel_spacing = if constants.algorithm == Algorithm::Mandeldrop {
+        constants.pixel_spacing() * point.abs_sq()
+    } else {
+        constants.pixel_spacing()
+    };
+    let tolerance = pixel_spacing * PERIODICITY_TOLERANCE;
+    let periodicity_tolerance_sq = tolerance * tolerance;
+
     macro_rules! builder {
-        ($fractal:ident, $c_value:expr) => {{
+        // For algorithms which do not use the exponent
+        (@fixed $fractal:ident) => {{
+            Runner {
+                constants,
+                algo: PhantomData::<$fractal>,
+                expo: crate::exponentiation::Exp2,
+                z0,
+                c,
+                julia,
+                periodicity_tolerance_sq,
+                custom,
+            }
+            .run(resume, orbit)
+        }};
+        ($fractal:ident) => {{
             match constants.exponent.typ {
                 NumericType::Integer if constants.exponent.int == 2 => Runner {
                     constants,
                     algo: PhantomData::<$fractal>,
                     expo: crate::exponentiation::Exp2,
-                    c: $c_value,
+                    z0,
+                    c,
+                    julia,
+                    periodicity_tolerance_sq,
+                    custom,
                 }
-                .run(),
+                .run(resume, orbit),
                 NumericType::Integer => Runner {
                     constants,
                     algo: PhantomData::<$fractal>,
                     expo: crate::exponentiation::ExpIntN(constants.exponent.int),
-                    c: $c_value,
+                    z0,
+                    c,
+                    julia,
+                    periodicity_tolerance_sq,
+                    custom,
                 }
-                .run(),
+                .run(resume, orbit),
                 NumericType::Float => Runner {
                     constants,
                     algo: PhantomData::<$fractal>,
                     expo: crate::exponentiation::ExpFloat(constants.exponent.real),
-                    c: $c_value,
+                    z0,
+                    c,
+                    julia,
+                    periodicity_tolerance_sq,
+                    custom,
                 }
-                .run(),
+                .run(resume, orbit),
                 NumericType::Complex => Runner {
                     constants,
                     algo: PhantomData::<$fractal>,
                     expo: crate::exponentiation::ExpComplex::from(constants.exponent),
-                    c: $c_value,
+                    z0,
+                    c,
+                    julia,
+                    periodicity_tolerance_sq,
+                    custom,
                 }
-                .run(),
+                .run(resume, orbit),
                 _ => todo!(),
             }
         }};
     }
     match constants.algorithm {
-        Algorithm::Mandelbrot => builder!(Mandelbrot, c),
-        // Mandeldrop is the same algorithm but with a different c
-        Algorithm::Mandeldrop => builder!(Mandelbrot, c.recip()),
-        Algorithm::Mandelbar => builder!(Mandelbar, c),
-        Algorithm::BurningShip => builder!(BurningShip, c),
-        Algorithm::Celtic => builder!(Celtic, c),
-        Algorithm::Variant => builder!(Variant, c),
-        Algorithm::BirdOfPrey => builder!(BirdOfPrey, c),
+        Algorithm::Mandelbrot | Algorithm::Mandeldrop => builder!(Mandelbrot),
+        Algorithm::Mandelbar => builder!(Mandelbar),
+        Algorithm::BurningShip => builder!(BurningShip),
+        Algorithm::Celtic => builder!(Celtic),
+        Algorithm::Variant => builder!(Variant),
+        Algorithm::BirdOfPrey => builder!(BirdOfPrey),
+        Algorithm::Newton => builder!(Newton),
+        Algorithm::NewtonRoots => builder!(@fixed NewtonRoots),
+        Algorithm::Nova => builder!(Nova),
+        Algorithm::Sine => builder!(@fixed Sine),
+        Algorithm::Cosine => builder!(@fixed Cosine),
+        Algorithm::Exponential => builder!(@fixed Exponential),
+        Algorithm::SinePlusC => builder!(@fixed SinePlusC),
+        Algorithm::Phoenix => builder!(Phoenix),
+        Algorithm::MagnetI => builder!(@fixed MagnetI),
+        Algorithm::MagnetII => builder!(@fixed MagnetII),
+        Algorithm::Lambda => builder!(@fixed Lambda),
+        Algorithm::Formula => builder!(@fixed Formula),
+        Algorithm::Hybrid => builder!(@fixed Hybrid),
         _ => todo!(),
     }
 }
@@ -83,7 +254,15 @@ where
     constants: &'a FragmentConstants,
     algo: PhantomData<F>,
     expo: E,
+    /// Starting point of the orbit
+    z0: Complex,
     c: Complex,
+    /// Are we rendering a Julia set? (This changes how the derivative is tracked.)
+    julia: bool,
+    /// An orbit which returns to within this distance (squared) of an earlier point is periodic
+    periodicity_tolerance_sq: f32,
+    /// User-defined algorithms
+    custom: Custom<'a>,
 }
 
 impl<F, E> Runner<'_, F, E>
@@ -91,77 +270,735 @@ where
     F: AlgorithmDetail<E>,
     E: Exponentiator,
 {
-    fn run(self) -> PointResult {
-        use shader_common::NumericType;
+    /// Iterates the orbit, or its next slice: see [`render_slice`] for `resume` and `orbit`.
+    fn run(self, resume: bool, orbit: &mut OrbitState) -> PointResult {
+        if F::CONVERGENT {
+            return self.run_convergent();
+        }
+        // The derivative is taken with respect to the pixel, which is z0 for a Julia set and c otherwise.
+        let (mut dz, dc) = if self.julia {
+            (Complex::ONE, 0.0)
+        } else {
+            (Complex::ZERO, 1.0)
+        };
+        let (mut iters, mut z) = if resume {
+            dz = orbit.dz();
+            (self.constants.slice_start, orbit.z())
+        } else {
+            (0, self.z0)
+        };
+        let mut norm_sqr = z.abs_sq();
+        // The point before z, for smoothing and colouring an escape.
+        // A resumed orbit was saved because it hadn't escaped, so it takes a step (which
+        // updates these) before it can; it starts from the saved point all the same.
+        let (mut prev_z, mut prev_norm_sqr) = if resume {
+            (z, norm_sqr)
+        } else {
+            (Complex::ZERO, 0.0)
+        };
+        let max_iter = self.constants.iteration_limit();
+
+        // Brent's cycle detection: compare each point of the orbit with a snapshot,
+        // which is retaken whenever the iteration count reaches a power of two.
+        // (A resumed orbit takes its first snapshot where it starts.)
+        let mut snapshot = z;
+        let mut snapshot_iters = iters;
+        let mut next_snapshot = 1u32;
+        while next_snapshot <= iters {
+            next_snapshot = next_snapshot.saturating_mul(2);
+        }
+        let mut period = 0;
+        let mut state = F::initial_state(self.constants);
+        // For algorithms which aren't holomorphic, dz is not enough to describe how the orbit
+        // depends on the pixel: we need the full Jacobian (as a real 2x2 matrix).
+        let mut jacobian = if self.julia {
+            Mat2::IDENTITY
+        } else {
+            Mat2::ZERO
+        };
 
-        let loglog2_escape_threshold: f32 = ESCAPE_THRESHOLD.log2().log2();
+        let analytic = F::analytic(&self.custom);
 
-        let mut iters = 0;
-        let mut z = Complex::ZERO;
-        let mut dz = Complex::ZERO;
-        let mut prev_z = Complex::ZERO;
-        let mut norm_sqr = z.abs_sq();
-        let mut prev_norm_sqr = 0.0;
-        let max_iter = self.constants.max_iter;
+        let mut trap = f32::MAX;
+        let mut averages = Averages::new(self.constants, self.z0);
 
-        deprintln!("DBG: run for c={:?}", self.c);
-        // TODO: Cardoid and period-2 bulb checks in original?
+        deprintln!("DBG: run for z0={:?}, c={:?}", self.z0, self.c);
 
-        while norm_sqr < ESCAPE_THRESHOLD_SQ && iters < max_iter {
+        while !F::escaped(z, self.constants) && iters < max_iter {
+            if !analytic {
+                jacobian = F::iterate_jacobian(
+                    z,
+                    jacobian,
+                    self.expo,
+                    self.c,
+                    dc,
+                    iters,
+                    &state,
+                    &self.custom,
+                );
+            }
             F::pre_modify_point(&mut z);
             prev_z = z;
             prev_norm_sqr = norm_sqr;
-            (z, dz) = F::iterate_algorithm(z, dz, self.expo, self.c, iters);
+            (z, dz) = F::iterate_with_state(
+                z,
+                dz,
+                self.expo,
+                self.c,
+                dc,
+                iters,
+                &mut state,
+                &self.custom,
+            );
             iters += 1;
             norm_sqr = z.abs_sq();
+            trap = trap.min(trap_distance(z, self.constants));
+            averages.add(z, self.c);
             deprintln!("DBG: iters={iters}, z={z}, dz={dz}, |z|^2={norm_sqr}");
+            if (z - snapshot).abs_sq() < self.periodicity_tolerance_sq {
+                period = iters - snapshot_iters;
+                deprintln!("DBG: period {period} detected at iters={iters}");
+                break;
+            }
+            if iters == next_snapshot {
+                snapshot = z;
+                snapshot_iters = iters;
+                next_snapshot = next_snapshot.saturating_mul(2);
+            }
         }
-        let inside = iters == max_iter && (norm_sqr < ESCAPE_THRESHOLD_SQ);
-
-        // distance estimate, angle
-        let za = z.abs();
-        let distance = 2.0 * za.ln() * za / dz.abs();
-        let angle = prev_z.arg();
-        let radius_sqr = prev_norm_sqr;
-
-        // Fractional escape count: See http://linas.org/art-gallery/escape/escape.html
-        // The log(exponent) term is necessary for powers other than 2.
-        // Note that log2_exponent is not allowed to be 0 or subnormal (we divide by
-        // it below), so we special case those regions.
-        let exp = &self.constants.exponent;
-        let log2_exponent = match exp.typ {
-            NumericType::Integer if exp.int <= 2 => 1.0,
-            NumericType::Integer => (exp.int as f32).abs().log2(),
-            NumericType::Float if exp.real <= 2.0 => 1.0,
-            NumericType::Float => exp.real.abs().log2(),
-            NumericType::Complex => {
-                // For now, we'll take abs(z) so we can compute a log in ℝ.
-                // c.abs().log() === (c.abs_sq() ^ 0.5).log() === 0.5 * c.abs_sq().log()
-                let abssq = Complex::from(exp).abs_sq();
-                // For parity with Int and Floats, we'll special case where abs < 2 i.e. abs_sq < 4
-                if abssq <= 4.0 {
-                    1.0
-                } else {
-                    0.5 * abssq.log2()
-                }
+        let inside = period != 0 || (iters == max_iter && !F::escaped(z, self.constants));
+        if inside && period == 0 {
+            // Keep the orbit, in case this is a slice and it has further to go
+            *orbit = OrbitState::new(z, dz);
+        }
+        let dz = if analytic {
+            dz
+        } else {
+            jacobian_gradient(jacobian, z)
+        };
+        let (distance, smoothed_iters) = F::escape_estimates(z, dz, self.constants, &self.custom);
+        let result = finish(
+            inside,
+            period,
+            iters,
+            distance,
+            smoothed_iters,
+            prev_z,
+            prev_norm_sqr,
+        )
+        .with_trap(trap);
+        if !inside {
+            result
+                .with_normal(surface_normal(z, dz))
+                .with_extra(averages.finish(smoothed_iters))
+        } else if period != 0 && self.constants.palette.uses_interior() {
+            result.with_extra(self.interior(z, period, iters, state))
+        } else {
+            result
+        }
+    }
+
+    /// Data about the attracting cycle of the given period through `z`.
+    ///
+    /// `iters` and `state` are the iteration count and extra state at `z`.
+    fn interior(&self, z: Complex, period: u32, iters: u32, state: F::State) -> PointResultC {
+        if quadratic_mandelbrot(self.constants, self.julia) {
+            return mandelbrot_interior(z, self.c, period);
+        }
+        // The multiplier is the derivative of the cycle with respect to its starting point
+        let mut z = z;
+        let mut dz = Complex::ONE;
+        let mut jacobian = Mat2::IDENTITY;
+        let mut state = state;
+        let analytic = F::analytic(&self.custom);
+        let mut i = 0;
+        while i < period {
+            if !analytic {
+                jacobian = F::iterate_jacobian(
+                    z,
+                    jacobian,
+                    self.expo,
+                    self.c,
+                    0.0,
+                    iters + i,
+                    &state,
+                    &self.custom,
+                );
+            }
+            F::pre_modify_point(&mut z);
+            (z, dz) = F::iterate_with_state(
+                z,
+                dz,
+                self.expo,
+                self.c,
+                0.0,
+                iters + i,
+                &mut state,
+                &self.custom,
+            );
+            i += 1;
+        }
+        if analytic {
+            PointResultC::interior(dz.abs().min(1.0), dz.arg(), f32::INFINITY)
+        } else {
+            // There is no complex multiplier. The geometric mean of the magnitudes of the
+            // eigenvalues of the Jacobian behaves in the same way.
+            let magnitude = jacobian.determinant().abs().sqrt();
+            PointResultC::interior(magnitude.min(1.0), 0.0, f32::INFINITY)
+        }
+    }
+
+    /// Iterates an algorithm which converges to a root, until it gets there (or gives up).
+    ///
+    /// Points which do not converge are treated as inside the set.
+    fn run_convergent(self) -> PointResult {
+        let mut iters = 0;
+        let mut z = self.z0;
+        let mut delta_sq = f32::INFINITY;
+        let mut prev_delta_sq = f32::INFINITY;
+        let max_iter = self.constants.max_iter;
+        let mut trap = f32::MAX;
+        let mut averages = Averages::new(self.constants, self.z0);
+
+        deprintln!("DBG: convergent run for z0={:?}, c={:?}", self.z0, self.c);
+
+        while iters < max_iter {
+            let next = F::converge(z, self.expo, self.c, self.constants);
+            iters += 1;
+            prev_delta_sq = delta_sq;
+            delta_sq = (next - z).abs_sq();
+            z = next;
+            trap = trap.min(trap_distance(z, self.constants));
+            averages.add(z, self.c);
+            deprintln!("DBG: iters={iters}, z={z}, |delta|^2={delta_sq}");
+            // N.B. this also stops on NaN, which is not convergence
+            if delta_sq < CONVERGENCE_TOLERANCE_SQ || !delta_sq.is_finite() {
+                break;
             }
-            _ => unimplemented!(),
+        }
+        let converged = delta_sq < CONVERGENCE_TOLERANCE_SQ;
+        if !converged {
+            return PointResult::new_inside(0., z.arg(), z.abs_sq(), 0)
+                .with_trap(trap)
+                .with_extra(averages.finish(1.0));
+        }
+        // Newton's method converges quadratically: the logarithm of the step size doubles
+        // each time. So we can tell what fraction of the final step was needed.
+        let fraction = if prev_delta_sq < 1.0 {
+            (CONVERGENCE_TOLERANCE_SQ.ln() / prev_delta_sq.ln())
+                .log2()
+                .clamp(0.0, 1.0)
+        } else {
+            1.0
         };
+        // There is no distance estimate for these algorithms.
+        PointResult::new_outside(iters - 1, fraction, f32::INFINITY, z.arg(), z.abs_sq())
+            .with_basin(F::basin(z, self.expo, self.constants))
+            .with_trap(trap)
+            .with_extra(averages.finish(fraction))
+    }
+}
+
+/// Is `c` in the main cardioid or the period-2 bulb of the (exponent 2) Mandelbrot set?
+///
+/// Returns the period of the component, or 0 if it is in neither.
+/// These two regions make up most of the area of the set, and can be tested for directly.
+fn mandelbrot_component(c: Complex) -> u32 {
+    let y2 = c.im * c.im;
+    let x = c.re - 0.25;
+    let q = x * x + y2;
+    if q * (q + x) < 0.25 * y2 {
+        1
+    } else if (c.re + 1.0) * (c.re + 1.0) + y2 < 0.0625 {
+        2
+    } else {
+        0
+    }
+}
+
+/// Are we rendering the parameter plane of the Mandelbrot set (exponent 2)?
+///
+/// More is known about this than about any other fractal.
+#[inline(always)]
+fn quadratic_mandelbrot(constants: &FragmentConstants, julia: bool) -> bool {
+    !julia
+        && matches!(
+            constants.algorithm,
+            Algorithm::Mandelbrot | Algorithm::Mandeldrop
+        )
+        && constants.exponent.typ == NumericType::Integer
+        && constants.exponent.int == 2
+}
+
+/// Data about the attracting cycle of the given period, for a point `c` inside the
+/// (exponent 2) Mandelbrot set, given a point `z` near the cycle.
+///
+/// The interior distance estimate is from
+/// <https://en.wikibooks.org/wiki/Fractals/Iterations_in_the_complex_plane/Mandelbrot_set_interior>.
+fn mandelbrot_interior(z: Complex, c: Complex, period: u32) -> PointResultC {
+    // Refine z onto the cycle, by Newton's method on f^p(z) - z
+    let mut z0 = z;
+    let mut step = 0;
+    while step < INTERIOR_NEWTON_STEPS {
+        let mut z = z0;
+        let mut dz = Complex::ONE;
+        let mut i = 0;
+        while i < period {
+            dz = z * dz * 2.0;
+            z = z * z + c;
+            i += 1;
+        }
+        let delta = (z - z0) / (dz - 1.0);
+        if !(delta.re.is_finite() && delta.im.is_finite()) {
+            break;
+        }
+        z0 -= delta;
+        if delta.abs_sq() < CONVERGENCE_TOLERANCE_SQ {
+            break;
+        }
+        step += 1;
+    }
+    // Derivatives of f^p with respect to z and c, once round the cycle
+    let mut z = z0;
+    let mut dz = Complex::ONE;
+    let mut dc = Complex::ZERO;
+    let mut dzdz = Complex::ZERO;
+    let mut dcdz = Complex::ZERO;
+    let mut i = 0;
+    while i < period {
+        dcdz = (dz * dc + z * dcdz) * 2.0;
+        dzdz = (dz * dz + z * dzdz) * 2.0;
+        dc = z * dc * 2.0 + 1.0;
+        dz = z * dz * 2.0;
+        z = z * z + c;
+        i += 1;
+    }
+    let multiplier_abs = dz.abs().min(1.0);
+    let distance = (1.0 - dz.abs_sq()) / (dcdz + dzdz * dc / (Complex::ONE - dz)).abs();
+    PointResultC::interior(multiplier_abs, dz.arg(), distance.max(0.0))
+}
+
+/// The derivative of `z^e`
+#[inline(always)]
+fn power_derivative<E: Exponentiator>(e: E, z: Complex) -> Complex {
+    if z == Complex::ZERO {
+        return Complex::ZERO;
+    }
+    e.value() * e.apply_to(z) / z
+}
+
+/// The matrix of multiplication by `a`, as a map of the real plane
+#[inline(always)]
+pub(crate) fn complex_matrix(a: Complex) -> Mat2 {
+    Mat2::from_cols(vec2(a.re, a.im), vec2(-a.im, a.re))
+}
+
+/// The derivative of the `abs` function, taking it to be 1 at 0
+#[inline(always)]
+pub(crate) fn abs_derivative(x: f32) -> f32 {
+    if x < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+/// Reduces a Jacobian to the complex number whose magnitude is the rate of change of `|z|`
+/// with respect to the pixel. This is the gradient of `|z|`, so the distance estimate works
+/// unchanged. (For a holomorphic map, this has the same magnitude as dz.)
+#[inline(always)]
+fn jacobian_gradient(jacobian: Mat2, z: Complex) -> Complex {
+    let za = z.abs();
+    if za == 0.0 {
+        return Complex::ZERO;
+    }
+    let gradient = jacobian.transpose() * vec2(z.re, z.im) / za;
+    Complex::new(gradient.x, gradient.y)
+}
+
+/// Computes the output data for a point, from the state of its orbit at the end of iteration
+#[inline(always)]
+fn finish(
+    inside: bool,
+    period: u32,
+    iters: u32,
+    distance: f32,
+    smoothed_iters: f32,
+    prev_z: Complex,
+    prev_norm_sqr: f32,
+) -> PointResult {
+    let angle = prev_z.arg();
+    let radius_sqr = prev_norm_sqr;
+    if inside {
+        PointResult::new_inside(distance, angle, radius_sqr, period)
+    } else {
+        PointResult::new_outside(iters, smoothed_iters, distance, angle, radius_sqr)
+    }
+}
+
+/// Direction of the surface normal, taking the distance estimate as a height field.
+///
+/// This is the direction of `z / dz`, in which the distance estimate increases.
+#[inline(always)]
+fn surface_normal(z: Complex, dz: Complex) -> f32 {
+    // arg(z * conj(dz)) == arg(z / dz)
+    (z * dz.conjugate()).arg()
+}
+
+/// Distance from a point of the orbit to the orbit trap
+#[inline(always)]
+pub fn trap_distance(z: Complex, constants: &FragmentConstants) -> f32 {
+    match constants.trap {
+        OrbitTrap::Point => z.abs(),
+        OrbitTrap::Line => z.im.abs(),
+        OrbitTrap::Cross => z.re.abs().min(z.im.abs()),
+        OrbitTrap::Circle => (z.abs() - constants.trap_radius).abs(),
+        OrbitTrap::UserPoint => (z - Complex::from(constants.trap_point)).abs(),
+        _ => todo!(),
+    }
+}
 
-        // by the logarithm of a power law,
-        // z.norm().log() === z.norm_sqr().log() * 0.5
-        let log_zn = z.abs_sq().log2() * 0.5;
-        let smoothed_iters = 1. + loglog2_escape_threshold - log_zn.log2() / log2_exponent;
+/// Accumulates the averaging colouring statistics along an orbit.
+///
+/// Each point of the orbit from `z_2` onwards contributes a value in 0..1 to each average.
+/// The averages with and without the final point are blended by the fractional iteration count,
+/// which makes them continuous across the iteration bands.
+/// See Jussi Härkönen, "On smooth fractal coloring techniques" (2007).
+#[derive(Clone, Copy)]
+struct Averages {
+    /// The statistics cost time on every iteration, so are only collected if the palette uses them
+    enabled: bool,
+    /// Running totals of (stripe, triangle inequality, curvature)
+    sum: Vec3,
+    /// The totals before the most recent point
+    prev_sum: Vec3,
+    /// Number of points which have contributed
+    count: u32,
+    /// The previous two points of the orbit
+    z1: Complex,
+    z2: Complex,
+    /// Number of points seen, after the start
+    seen: u32,
+}
+
+impl Averages {
+    #[inline(always)]
+    fn new(constants: &FragmentConstants, z0: Complex) -> Self {
+        Self {
+            enabled: constants.palette.uses_averages(),
+            sum: Vec3::ZERO,
+            prev_sum: Vec3::ZERO,
+            count: 0,
+            z1: z0,
+            z2: z0,
+            seen: 0,
+        }
+    }
+
+    /// Adds the next point of the orbit.
+    ///
+    /// `c` is the constant added at each iteration, which bounds the triangle inequality.
+    #[inline(always)]
+    fn add(&mut self, z: Complex, c: Complex) {
+        if !self.enabled {
+            return;
+        }
+        if self.seen > 0 {
+            let stripe = 0.5 * (STRIPE_DENSITY * z.arg()).sin() + 0.5;
+            // For z := f(z) + c, |z| lies between ||f(z)| - |c|| and |f(z)| + |c|
+            let fz = (z - c).abs();
+            let ca = c.abs();
+            let lo = (fz - ca).abs();
+            let hi = fz + ca;
+            let triangle = if hi > lo {
+                ((z.abs() - lo) / (hi - lo)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            // The turn between successive steps; arg(a * conj(b)) == arg(a / b)
+            let step = z - self.z1;
+            let prev_step = self.z1 - self.z2;
+            let curvature = if step == Complex::ZERO || prev_step == Complex::ZERO {
+                0.0
+            } else {
+                (step * prev_step.conjugate()).arg().abs() / core::f32::consts::PI
+            };
+            self.prev_sum = self.sum;
+            self.sum += vec3(stripe, triangle, curvature);
+            self.count += 1;
+        }
+        self.z2 = self.z1;
+        self.z1 = z;
+        self.seen += 1;
+    }
 
-        if inside {
-            PointResult::new_inside(distance, angle, radius_sqr)
+    /// Computes the averages, blended by the fractional part of the iteration count
+    #[inline(always)]
+    fn finish(&self, fraction: f32) -> PointResultC {
+        if self.count == 0 {
+            return PointResultC::default();
+        }
+        let average = self.sum / self.count as f32;
+        let prev_average = if self.count > 1 {
+            self.prev_sum / (self.count - 1) as f32
         } else {
-            PointResult::new_outside(iters, smoothed_iters, distance, angle, radius_sqr)
+            average
+        };
+        let blended = prev_average.lerp(average, fraction.clamp(0.0, 1.0));
+        PointResultC::averages(blended.x, blended.y, blended.z)
+    }
+}
+
+/// The square of the quantity which the bailout test compares with the escape radius
+#[inline(always)]
+pub fn bailout_norm_sq(z: Complex, bailout: Bailout) -> f32 {
+    let norm = match bailout {
+        Bailout::Circle => return z.abs_sq(),
+        Bailout::Square => z.re.abs().max(z.im.abs()),
+        Bailout::Real => z.re.abs(),
+        Bailout::Imaginary => z.im.abs(),
+        Bailout::Manhattan => z.re.abs() + z.im.abs(),
+        _ => todo!(),
+    };
+    norm * norm
+}
+
+/// Distance estimate and fractional escape count, for algorithms of the form `z := z^k + c`
+#[inline(always)]
+fn polynomial_estimates(z: Complex, dz: Complex, constants: &FragmentConstants) -> (f32, f32) {
+    polynomial_estimates_for(
+        z,
+        dz,
+        log2_exponent(&constants.exponent),
+        inscribed_radius(constants),
+    )
+}
+
+/// The radius of the largest circle inside the bailout boundary.
+///
+/// An orbit which escapes is at least this far from the origin, whatever the shape,
+/// so measuring the fractional escape count from here keeps it below 1.
+#[inline(always)]
+fn inscribed_radius(constants: &FragmentConstants) -> f32 {
+    match constants.bailout {
+        // The diamond's sides are at 45°
+        Bailout::Manhattan => constants.escape_radius * core::f32::consts::FRAC_1_SQRT_2,
+        _ => constants.escape_radius,
+    }
+}
+
+/// The base-2 logarithm of an exponent, as used by the fractional escape count
+#[inline(always)]
+fn log2_exponent(exp: &PushExponent) -> f32 {
+    // The log(exponent) term is necessary for powers other than 2.
+    // Note that log2_exponent is not allowed to be 0 or subnormal (we divide by
+    // it), so we special case those regions.
+    match exp.typ {
+        NumericType::Integer if exp.int <= 2 => 1.0,
+        NumericType::Integer => (exp.int as f32).abs().log2(),
+        NumericType::Float if exp.real <= 2.0 => 1.0,
+        NumericType::Float => exp.real.abs().log2(),
+        NumericType::Complex => {
+            // For now, we'll take abs(z) so we can compute a log in ℝ.
+            // c.abs().log() === (c.abs_sq() ^ 0.5).log() === 0.5 * c.abs_sq().log()
+            let abssq = Complex::from(exp).abs_sq();
+            // For parity with Int and Floats, we'll special case where abs < 2 i.e. abs_sq < 4
+            if abssq <= 4.0 {
+                1.0
+            } else {
+                0.5 * abssq.log2()
+            }
+        }
+        _ => unimplemented!(),
+    }
+}
+
+/// Distance estimate and fractional escape count, for a polynomial of known degree
+///
+/// `log2_exponent` is the base-2 logarithm of the degree; it must not be zero.
+/// `radius` is the [`inscribed_radius`] of the bailout test which ended the orbit.
+#[inline(always)]
+fn polynomial_estimates_for(
+    z: Complex,
+    dz: Complex,
+    log2_exponent: f32,
+    radius: f32,
+) -> (f32, f32) {
+    let loglog2_escape_threshold: f32 = radius.log2().log2();
+
+    // distance estimate
+    let za = z.abs();
+    let distance = 2.0 * za.ln() * za / dz.abs();
+
+    // Fractional escape count: See http://linas.org/art-gallery/escape/escape.html
+    // by the logarithm of a power law,
+    // This uses |z| whatever the bailout shape: once the orbit is large each iteration raises
+    // |z| to the power of the degree, so the count is continuous across the iteration bands.
+    // (For small radii that is only roughly true, whatever the shape.)
+    // z.norm().log() === z.norm_sqr().log() * 0.5
+    let log_zn = z.abs_sq().log2() * 0.5;
+    let smoothed_iters = 1. + loglog2_escape_threshold - log_zn.log2() / log2_exponent;
+    (distance, smoothed_iters)
+}
+
+/// Distance estimate and fractional escape count, for algorithms which grow exponentially.
+///
+/// `escape` is the part of z which is compared with [`TRANSCENDENTAL_ESCAPE`].
+#[inline(always)]
+fn transcendental_estimates(z: Complex, dz: Complex, escape: f32) -> (f32, f32) {
+    // Each iteration roughly exponentiates the escaping quantity, so the number of iterations
+    // to reach a value is its super-logarithm. Taking the difference makes the count continuous.
+    let smoothed_iters = 1. + super_log(TRANSCENDENTAL_ESCAPE) - super_log(escape);
+    // For these maps dz grows in proportion to z, so |z|/|dz| is the distance at which
+    // the final step would have moved the orbit by about one unit.
+    // (dz may have overflowed, which means the point is very close to the set.)
+    let distance = z.abs() / dz.abs();
+    let distance = if distance.is_nan() { 0.0 } else { distance };
+    (distance, smoothed_iters)
+}
+
+/// Approximate super-logarithm (the inverse of tetration) to base e, with linear interpolation
+#[inline(always)]
+fn super_log(x: f32) -> f32 {
+    let mut x = x;
+    let mut result = 0.0;
+    // Even f32::MAX only needs 4 steps; we also need to stop on infinity (or NaN).
+    let mut i = 0;
+    while x > 1.0 && i < 5 {
+        x = x.ln();
+        result += 1.0;
+        i += 1;
+    }
+    result + x.min(1.0) - 1.0
+}
+
+/// Can this algorithm and exponent be rendered by [`render_perturbed`]?
+pub fn supports_perturbation(algorithm: Algorithm, exponent: &PushExponent) -> bool {
+    algorithm == Algorithm::Mandelbrot && exponent.typ == NumericType::Integer && exponent.int == 2
+}
+
+/// Can these settings be rendered progressively, by [`render_slice`]?
+///
+/// An orbit can only be resumed if [`OrbitState`] holds everything needed to carry on with it.
+/// So the algorithm must be holomorphic (otherwise we would need its Jacobian), must not carry
+/// extra state, and must not be one of the convergent algorithms (which take a different path).
+/// A custom `formula` is holomorphic unless it folds or conjugates the plane: see
+/// [`formula::analytic`](crate::formula::analytic).
+/// The palette must not use the orbit trap or the averages, which accumulate along the orbit.
+/// Rendering by perturbation isn't supported either, but that is up to the caller.
+pub fn supports_progressive(algorithm: Algorithm, palette: &Palette, formula: &[u32]) -> bool {
+    let algorithm = match algorithm {
+        Algorithm::Mandelbrot
+        | Algorithm::Mandeldrop
+        | Algorithm::Sine
+        | Algorithm::Cosine
+        | Algorithm::Exponential
+        | Algorithm::SinePlusC
+        | Algorithm::MagnetI
+        | Algorithm::MagnetII
+        | Algorithm::Lambda => true,
+        Algorithm::Formula => crate::formula::analytic(formula),
+        _ => false,
+    };
+    algorithm && !palette.uses_trap() && !palette.uses_averages()
+}
+
+/// Pauldelbrot's glitch criterion: a perturbed orbit has lost precision when
+/// `|Z + δ|² < GLITCH_TOLERANCE · |Z|²`.
+const GLITCH_TOLERANCE: f32 = 1e-6;
+
+/// Renders a point by perturbation theory.
+///
+/// `delta` is the offset of the point from the reference point `C`, and `orbit` is the
+/// reference orbit `Z_0 = 0, Z_1 = C, ...` computed on the host at high precision.
+/// Only the first `constants.reference_orbit_len` entries of `orbit` are valid.
+///
+/// We iterate `δ := 2Zδ + δ² + δc`, which is the Mandelbrot formula (exponent 2) with the
+/// reference subtracted out. This stays accurate in `f32` long after `Z + δ` has run out of
+/// precision, provided that `Z + δ` does not get too close to zero; when it does (which we
+/// detect by Pauldelbrot's criterion, or when `|Z + δ| < |δ|`) or when the reference orbit
+/// runs out, we rebase: the full value becomes the new delta, against the start of the
+/// reference orbit.
+///
+/// The derivative grows as the inverse of the distance to the set, which at these zooms is
+/// beyond the range of `f32`; so we track it in pixels (scaled by the pixel spacing) and
+/// convert the distance estimate back to complex units at the end.
+pub fn render_perturbed(constants: &FragmentConstants, delta: Vec2, orbit: &[Vec2]) -> PointResult {
+    let dc = Complex::from(delta);
+    let orbit_len = constants.reference_orbit_len;
+
+    let mut iters = 0;
+    let mut m = 0; // index into the reference orbit
+    let mut delta = Complex::ZERO;
+    let mut z = Complex::ZERO;
+    let mut dz = Complex::ZERO; // with respect to the pixel position, not c
+    let pixel_spacing = constants.pixel_spacing();
+    let mut prev_z = Complex::ZERO;
+    let mut norm_sqr = 0.0;
+    let mut prev_norm_sqr = 0.0;
+    let max_iter = constants.max_iter;
+    let mut trap = f32::MAX;
+    // The reference orbit starts 0, C
+    let c = if orbit_len > 1 {
+        Complex::from(orbit[1]) + dc
+    } else {
+        dc
+    };
+    let mut averages = Averages::new(constants, Complex::ZERO);
+
+    deprintln!("DBG: perturbed run for dc={dc:?}, orbit_len={orbit_len}");
+
+    let escape_sq = constants.escape_radius * constants.escape_radius;
+    while bailout_norm_sq(z, constants.bailout) < escape_sq && iters < max_iter {
+        prev_z = z;
+        prev_norm_sqr = norm_sqr;
+        dz = z * dz * 2.0 + pixel_spacing;
+        let reference = Complex::from(orbit[m as usize]);
+        delta = (reference * 2.0 + delta) * delta + dc;
+        m += 1;
+        iters += 1;
+        let reference = Complex::from(orbit[m as usize]);
+        z = reference + delta;
+        norm_sqr = z.abs_sq();
+        trap = trap.min(trap_distance(z, constants));
+        averages.add(z, c);
+        deprintln!("DBG: iters={iters}, m={m}, z={z}, delta={delta}, |z|^2={norm_sqr}");
+        if m + 1 >= orbit_len
+            || norm_sqr < delta.abs_sq()
+            || norm_sqr < GLITCH_TOLERANCE * reference.abs_sq()
+        {
+            delta = z;
+            m = 0;
         }
     }
+    let inside = iters == max_iter && bailout_norm_sq(z, constants.bailout) < escape_sq;
+    let (distance, smoothed_iters) = polynomial_estimates(z, dz, constants);
+    finish(
+        inside,
+        0,
+        iters,
+        distance * pixel_spacing,
+        smoothed_iters,
+        prev_z,
+        prev_norm_sqr,
+    )
+    .with_trap(trap)
+    .with_normal(surface_normal(z, dz))
+    .with_extra(averages.finish(smoothed_iters))
 }
 
 pub(crate) trait AlgorithmDetail<E: Exponentiator> {
+    /// Extra state carried along the orbit, for formulas which need more than the current point.
+    ///
+    /// Most algorithms don't, and set this to `()`.
+    type State: Copy + Default;
+
+    /// Sets up the extra state at the start of an orbit.
+    #[inline(always)]
+    fn initial_state(_constants: &FragmentConstants) -> Self::State {
+        Self::State::default()
+    }
+
     /// Pre-modifies a point before applying the algorithm.
     ///
     /// Override as necessary.
@@ -172,55 +1009,185 @@ pub(crate) trait AlgorithmDetail<E: Exponentiator> {
     ///
     /// The provided implementation computes `z := z.pow(e) + c`, but this doesn't
     /// suit all algorithms. Override as necessary.
+    ///
+    /// `dc` is the derivative of `c` with respect to the pixel: 1 normally, or 0 for a Julia set.
     #[inline(always)]
     fn iterate_algorithm(
         z: Complex,
         dz: Complex,
         e: E,
         c: Complex,
+        dc: f32,
         _iters: u32,
     ) -> (Complex /*z*/, Complex /*dz*/) {
-        let dz = e.derivative() * z * dz + 1.0;
+        let dz = e.derivative() * z * dz + dc;
         let z = e.apply_to(z) + c;
         (z, dz)
     }
+
+    /// One iteration of the fractal algorithm, with access to the extra state
+    /// (and the user-defined algorithms).
+    ///
+    /// The provided implementation ignores both and calls [`Self::iterate_algorithm`].
+    /// Override as necessary.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn iterate_with_state(
+        z: Complex,
+        dz: Complex,
+        e: E,
+        c: Complex,
+        dc: f32,
+        iters: u32,
+        _state: &mut Self::State,
+        _custom: &Custom,
+    ) -> (Complex /*z*/, Complex /*dz*/) {
+        Self::iterate_algorithm(z, dz, e, c, dc, iters)
+    }
+
+    /// Has the orbit escaped?
+    ///
+    /// The provided implementation applies the bailout test chosen by the user: see [`bailout_norm_sq`].
+    #[inline(always)]
+    fn escaped(z: Complex, constants: &FragmentConstants) -> bool {
+        bailout_norm_sq(z, constants.bailout) >= constants.escape_radius * constants.escape_radius
+    }
+
+    /// Computes the distance estimate and fractional escape count, at the end of iteration.
+    ///
+    /// The provided implementation suits algorithms of the form `z := z^k + c`.
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        constants: &FragmentConstants,
+        _custom: &Custom,
+    ) -> (f32, f32) {
+        polynomial_estimates(z, dz, constants)
+    }
+
+    /// Is the algorithm holomorphic?
+    ///
+    /// If not (because it folds or conjugates the orbit), `dz` from [`Self::iterate_algorithm`]
+    /// is meaningless. The Runner tracks the Jacobian by [`Self::iterate_jacobian`] instead.
+    const ANALYTIC: bool = true;
+
+    /// Is the algorithm holomorphic, with these user-defined algorithms?
+    ///
+    /// The provided implementation returns [`Self::ANALYTIC`]. Override if it depends on them.
+    #[inline(always)]
+    fn analytic(_custom: &Custom) -> bool {
+        Self::ANALYTIC
+    }
+
+    /// The derivative of [`Self::pre_modify_point`] at z, which must be a diagonal matrix
+    /// (a fold or reflection of each axis), given as its diagonal.
+    #[inline(always)]
+    fn pre_modify_derivative(_z: Complex) -> Vec2 {
+        Vec2::ONE
+    }
+
+    /// The derivative of the fold applied after the power, given as its diagonal.
+    ///
+    /// `zz` is the result of the power.
+    #[inline(always)]
+    fn post_modify_derivative(_zz: Complex, _iters: u32) -> Vec2 {
+        Vec2::ONE
+    }
+
+    /// One iteration of the Jacobian of z with respect to the pixel, for algorithms which are not
+    /// [analytic](Self::analytic). `z` is the point before [`Self::pre_modify_point`].
+    ///
+    /// The provided implementation suits algorithms of the form `z := fold(pre_fold(z)^k) + c`.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn iterate_jacobian(
+        z: Complex,
+        jacobian: Mat2,
+        e: E,
+        _c: Complex,
+        dc: f32,
+        iters: u32,
+        _state: &Self::State,
+        _custom: &Custom,
+    ) -> Mat2 {
+        let mut w = z;
+        Self::pre_modify_point(&mut w);
+        let jacobian = Mat2::from_diagonal(Self::pre_modify_derivative(z)) * jacobian;
+        let jacobian = complex_matrix(power_derivative(e, w)) * jacobian;
+        let post = Self::post_modify_derivative(e.apply_to(w), iters);
+        Mat2::from_diagonal(post) * jacobian + Mat2::from_diagonal(Vec2::splat(dc))
+    }
+
+    /// Does this algorithm iterate until the orbit converges to a root, rather than until it escapes?
+    ///
+    /// If so, [`Self::converge`] and [`Self::basin`] are used instead of [`Self::iterate_algorithm`].
+    const CONVERGENT: bool = false;
+
+    /// One iteration of a convergent algorithm
+    #[inline(always)]
+    fn converge(z: Complex, _e: E, _c: Complex, _constants: &FragmentConstants) -> Complex {
+        z
+    }
+
+    /// Identifies which root a convergent orbit reached, numbered from 1
+    #[inline(always)]
+    fn basin(_z: Complex, _e: E, _constants: &FragmentConstants) -> u32 {
+        0
+    }
 }
 
 struct Mandelbrot {}
-impl<E: Exponentiator> AlgorithmDetail<E> for Mandelbrot {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Mandelbrot {
+    type State = ();
+}
 
 struct Mandelbar {}
 impl<E: Exponentiator> AlgorithmDetail<E> for Mandelbar {
+    type State = ();
     // Same as mandelbrot, but conjugate c each time
     #[inline(always)]
     fn pre_modify_point(z: &mut super::Complex) {
         *z = z.conjugate();
     }
+    const ANALYTIC: bool = false;
+    #[inline(always)]
+    fn pre_modify_derivative(_z: Complex) -> Vec2 {
+        vec2(1.0, -1.0)
+    }
 }
 
 struct BurningShip {}
 impl<E: Exponentiator> AlgorithmDetail<E> for BurningShip {
+    type State = ();
     // Same as mandelbrot, but take abs(re) and abs(im) each time
     #[inline(always)]
     fn pre_modify_point(z: &mut super::Complex) {
         z.re = z.re.abs();
         z.im = z.im.abs();
     }
+    const ANALYTIC: bool = false;
+    #[inline(always)]
+    fn pre_modify_derivative(z: Complex) -> Vec2 {
+        vec2(abs_derivative(z.re), abs_derivative(z.im))
+    }
 }
 
 struct Celtic {}
 impl<E: Exponentiator> AlgorithmDetail<E> for Celtic {
+    type State = ();
     #[inline(always)]
     fn iterate_algorithm(
         z: Complex,
         dz: Complex,
         e: E,
         c: Complex,
+        dc: f32,
         _iters: u32,
     ) -> (Complex, Complex) {
         // Based on mandelbrot, but using the formula:
         //   z := abs(re(z^2)) + i.im(z^2) + c
-        let dz = e.derivative() * z * dz + 1.0;
+        let dz = e.derivative() * z * dz + dc;
         let zz = e.apply_to(z);
         let z2 = Complex {
             re: zz.re.abs(),
@@ -228,28 +1195,41 @@ impl<E: Exponentiator> AlgorithmDetail<E> for Celtic {
         };
         (z2 + c, dz)
     }
+    const ANALYTIC: bool = false;
+    #[inline(always)]
+    fn post_modify_derivative(zz: Complex, _iters: u32) -> Vec2 {
+        vec2(abs_derivative(zz.re), 1.0)
+    }
 }
 
 struct BirdOfPrey {}
 impl<E: Exponentiator> AlgorithmDetail<E> for BirdOfPrey {
+    type State = ();
     // Same as mandelbrot, but take abs(im) each time
     #[inline(always)]
     fn pre_modify_point(z: &mut super::Complex) {
         z.im = z.im.abs();
     }
+    const ANALYTIC: bool = false;
+    #[inline(always)]
+    fn pre_modify_derivative(z: Complex) -> Vec2 {
+        vec2(1.0, abs_derivative(z.im))
+    }
 }
 
 struct Variant {}
 impl<E: Exponentiator> AlgorithmDetail<E> for Variant {
+    type State = ();
     #[inline(always)]
     fn iterate_algorithm(
         z: Complex,
         dz: Complex,
         e: E,
         c: Complex,
+        dc: f32,
         iters: u32,
     ) -> (Complex, Complex) {
-        let dz = e.derivative() * z * dz + 1.0;
+        let dz = e.derivative() * z * dz + dc;
         let zz = e.apply_to(z);
         let z = if (iters % 2) == 1 {
             Complex {
@@ -261,39 +1241,632 @@ impl<E: Exponentiator> AlgorithmDetail<E> for Variant {
         };
         (z, dz)
     }
+    const ANALYTIC: bool = false;
+    #[inline(always)]
+    fn post_modify_derivative(zz: Complex, iters: u32) -> Vec2 {
+        // Celtic on odd iterations
+        if (iters % 2) == 1 {
+            vec2(abs_derivative(zz.re), 1.0)
+        } else {
+            Vec2::ONE
+        }
+    }
 }
 
-#[cfg(all(test, not(target_arch = "spirv")))]
-#[cfg_attr(coverage_nightly, coverage(off))]
-mod tests {
-    use crate::{fractal, vec2, FragmentConstants, Vec2};
-    use const_default::ConstDefault as _;
-    use shader_common::{enums::Algorithm, Flags, NumericType, Palette, PushExponent};
-    use shader_util::Size;
+/// One step of Newton's method for `z^e - 1`
+#[inline(always)]
+fn newton_step<E: Exponentiator>(z: Complex, e: E) -> Complex {
+    // f(z) = z^e - 1, f'(z) = e.z^(e-1) = e.z^e / z
+    let ze = e.apply_to(z);
+    z - (ze - Complex::ONE) * z / (e.value() * ze)
+}
 
-    use pretty_assertions::assert_eq;
+/// Which root of unity is `z` nearest to? (By angle; for non-integer exponents, approximately.)
+#[inline(always)]
+fn root_of_unity_basin<E: Exponentiator>(z: Complex, e: E) -> u32 {
+    let n = e.value().re.abs().round().max(1.0);
+    let k = (z.arg() * n / core::f32::consts::TAU).round();
+    // arg is in -pi..pi, so k may be negative
+    let k = (k + n) % n;
+    k as u32 + 1
+}
 
-    fn test_frag_consts() -> FragmentConstants {
-        FragmentConstants {
-            flags: Flags::NEEDS_REITERATE,
-            viewport_translate: vec2(0., 0.),
-            viewport_zoom: 0.3,
-            size: Size::new(1, 1),
-            max_iter: 10,
-            algorithm: Algorithm::Mandelbrot,
-            exponent: PushExponent::from(2),
-            palette: Palette::DEFAULT,
-            inspector_point_pixel_address: Vec2::default(),
-        }
+/// Newton's method for finding the roots of `z^n - 1`
+struct Newton {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Newton {
+    type State = ();
+    const CONVERGENT: bool = true;
+    #[inline(always)]
+    fn converge(z: Complex, e: E, _c: Complex, _constants: &FragmentConstants) -> Complex {
+        newton_step(z, e)
+    }
+    #[inline(always)]
+    fn basin(z: Complex, e: E, _constants: &FragmentConstants) -> u32 {
+        root_of_unity_basin(z, e)
     }
+}
 
-    #[test]
-    fn mandelbrot_known_answer() {
-        let point = crate::vec2(-0.75, 0.75);
-        eprintln!("{:#?}", test_frag_consts());
-        let result = fractal::render(&test_frag_consts(), point);
-        eprintln!("{result:?}");
-        assert_eq!(result.iters_fraction(), 0.52201414);
+/// Newton's method for a cubic with user-chosen roots
+struct NewtonRoots {}
+impl<E: Exponentiator> AlgorithmDetail<E> for NewtonRoots {
+    type State = ();
+    const CONVERGENT: bool = true;
+    #[inline(always)]
+    fn converge(z: Complex, _e: E, _c: Complex, constants: &FragmentConstants) -> Complex {
+        // For p(z) = (z - r1)(z - r2)(z - r3), p'(z)/p(z) = sum of 1/(z - ri)
+        // (Iterators don't sit well with rust-gpu, so loop by hand.)
+        let mut sum = Complex::ZERO;
+        let mut i = 0;
+        while i < constants.newton_roots.len() {
+            let offset = z - Complex::from(constants.newton_roots[i]);
+            if offset == Complex::ZERO {
+                // Exactly on a root, which would otherwise divide by zero
+                return z;
+            }
+            sum += offset.recip();
+            i += 1;
+        }
+        z - sum.recip()
+    }
+    #[inline(always)]
+    fn basin(z: Complex, _e: E, constants: &FragmentConstants) -> u32 {
+        let mut nearest = 0;
+        let mut nearest_distance = f32::INFINITY;
+        let mut i = 0;
+        while i < constants.newton_roots.len() {
+            let distance = (z - Complex::from(constants.newton_roots[i])).abs_sq();
+            if distance < nearest_distance {
+                nearest = i as u32 + 1;
+                nearest_distance = distance;
+            }
+            i += 1;
+        }
+        nearest
+    }
+}
+
+/// Nova: Newton's method for `z^n - 1`, plus c
+struct Nova {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Nova {
+    type State = ();
+    const CONVERGENT: bool = true;
+    #[inline(always)]
+    fn converge(z: Complex, e: E, c: Complex, _constants: &FragmentConstants) -> Complex {
+        newton_step(z, e) + c
+    }
+    #[inline(always)]
+    fn basin(z: Complex, e: E, _constants: &FragmentConstants) -> u32 {
+        root_of_unity_basin(z, e)
+    }
+}
+
+/// Complex sine and cosine
+#[inline(always)]
+pub(crate) fn sin_cos(z: Complex) -> (Complex, Complex) {
+    let (sin_re, cos_re) = (z.re.sin(), z.re.cos());
+    let (sinh_im, cosh_im) = (z.im.sinh(), z.im.cosh());
+    (
+        Complex::new(sin_re * cosh_im, cos_re * sinh_im),
+        Complex::new(cos_re * cosh_im, -sin_re * sinh_im),
+    )
+}
+
+/// z := c.sin(z)
+struct Sine {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Sine {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let (sin, cos) = sin_cos(z);
+        (c * sin, c * cos * dz + sin * dc)
+    }
+    #[inline(always)]
+    fn escaped(z: Complex, _constants: &FragmentConstants) -> bool {
+        z.im.abs() >= TRANSCENDENTAL_ESCAPE
+    }
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        _constants: &FragmentConstants,
+        _custom: &Custom,
+    ) -> (f32, f32) {
+        transcendental_estimates(z, dz, z.im.abs())
+    }
+}
+
+/// z := c.cos(z)
+struct Cosine {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Cosine {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let (sin, cos) = sin_cos(z);
+        (c * cos, cos * dc - c * sin * dz)
+    }
+    #[inline(always)]
+    fn escaped(z: Complex, _constants: &FragmentConstants) -> bool {
+        z.im.abs() >= TRANSCENDENTAL_ESCAPE
+    }
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        _constants: &FragmentConstants,
+        _custom: &Custom,
+    ) -> (f32, f32) {
+        transcendental_estimates(z, dz, z.im.abs())
+    }
+}
+
+/// z := c.exp(z)
+struct Exponential {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Exponential {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let exp = z.exp().to_rectangular();
+        (c * exp, exp * (c * dz + dc))
+    }
+    // exp(z) is periodic in Im(z), so only the real part can escape
+    #[inline(always)]
+    fn escaped(z: Complex, _constants: &FragmentConstants) -> bool {
+        z.re >= TRANSCENDENTAL_ESCAPE
+    }
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        _constants: &FragmentConstants,
+        _custom: &Custom,
+    ) -> (f32, f32) {
+        transcendental_estimates(z, dz, z.re)
+    }
+}
+
+/// z := sin(z) + c
+struct SinePlusC {}
+impl<E: Exponentiator> AlgorithmDetail<E> for SinePlusC {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let (sin, cos) = sin_cos(z);
+        (sin + c, cos * dz + dc)
+    }
+    #[inline(always)]
+    fn escaped(z: Complex, _constants: &FragmentConstants) -> bool {
+        z.im.abs() >= TRANSCENDENTAL_ESCAPE
+    }
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        _constants: &FragmentConstants,
+        _custom: &Custom,
+    ) -> (f32, f32) {
+        transcendental_estimates(z, dz, z.im.abs())
+    }
+}
+
+/// Phoenix: `z := z^e + c + p.z'`, where `z'` is the previous point of the orbit
+struct Phoenix {}
+
+/// The previous point of the orbit, and the Phoenix parameter
+#[derive(Clone, Copy, Default)]
+struct PhoenixState {
+    prev_z: Complex,
+    prev_dz: Complex,
+    p: Complex,
+}
+
+impl<E: Exponentiator> AlgorithmDetail<E> for Phoenix {
+    type State = PhoenixState;
+    #[inline(always)]
+    fn initial_state(constants: &FragmentConstants) -> PhoenixState {
+        PhoenixState {
+            p: Complex::from(constants.phoenix_parameter),
+            ..Default::default()
+        }
+    }
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn iterate_with_state(
+        z: Complex,
+        dz: Complex,
+        e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+        state: &mut PhoenixState,
+        _custom: &Custom,
+    ) -> (Complex, Complex) {
+        let new_dz = e.derivative() * z * dz + dc + state.p * state.prev_dz;
+        let new_z = e.apply_to(z) + c + state.p * state.prev_z;
+        state.prev_z = z;
+        state.prev_dz = dz;
+        (new_z, new_dz)
+    }
+}
+
+/// Magnet type I: `z := ((z^2 + c - 1) / (2z + c - 2))^2`
+struct MagnetI {}
+impl<E: Exponentiator> AlgorithmDetail<E> for MagnetI {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let numerator = z * z + c - 1.0;
+        let denominator = z * 2.0 + c - 2.0;
+        let d_numerator = z * dz * 2.0 + dc;
+        let d_denominator = dz * 2.0 + dc;
+        magnet_square(numerator, denominator, d_numerator, d_denominator)
+    }
+}
+
+/// Magnet type II:
+/// `z := ((z^3 + 3(c-1)z + (c-1)(c-2)) / (3z^2 + 3(c-2)z + (c-1)(c-2) + 1))^2`
+struct MagnetII {}
+impl<E: Exponentiator> AlgorithmDetail<E> for MagnetII {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let c1 = c - 1.0;
+        let c2 = c - 2.0;
+        let z2 = z * z;
+        let numerator = z2 * z + c1 * z * 3.0 + c1 * c2;
+        let denominator = z2 * 3.0 + c2 * z * 3.0 + c1 * c2 + 1.0;
+        // d/dc of (c-1)(c-2) is 2c-3
+        let dc12 = c * 2.0 - 3.0;
+        let d_numerator = (z2 * 3.0 + c1 * 3.0) * dz + (z * 3.0 + dc12) * dc;
+        let d_denominator = (z * 6.0 + c2 * 3.0) * dz + (z * 3.0 + dc12) * dc;
+        magnet_square(numerator, denominator, d_numerator, d_denominator)
+    }
+}
+
+/// Computes `(n/d)^2` and its derivative, given the derivatives of `n` and `d`
+#[inline(always)]
+fn magnet_square(n: Complex, d: Complex, dn: Complex, dd: Complex) -> (Complex, Complex) {
+    let q = n / d;
+    let dq = (dn * d - n * dd) / (d * d);
+    (q * q, q * dq * 2.0)
+}
+
+/// The lambda (logistic) map: `z := c.z(1 - z)`
+struct Lambda {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Lambda {
+    type State = ();
+    #[inline(always)]
+    fn iterate_algorithm(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        _iters: u32,
+    ) -> (Complex, Complex) {
+        let one_minus_z = Complex::ONE - z;
+        let new_dz = c * (Complex::ONE - z * 2.0) * dz + z * one_minus_z * dc;
+        (c * z * one_minus_z, new_dz)
+    }
+}
+
+/// A formula entered by the user, interpreted from bytecode (see [`crate::formula`])
+struct Formula {}
+impl<E: Exponentiator> AlgorithmDetail<E> for Formula {
+    type State = ();
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn iterate_with_state(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        iters: u32,
+        _state: &mut (),
+        custom: &Custom,
+    ) -> (Complex, Complex) {
+        crate::formula::evaluate(custom.formula, z, dz, c, dc, iters)
+    }
+    #[inline(always)]
+    fn analytic(custom: &Custom) -> bool {
+        crate::formula::analytic(custom.formula)
+    }
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn iterate_jacobian(
+        z: Complex,
+        jacobian: Mat2,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        iters: u32,
+        _state: &(),
+        custom: &Custom,
+    ) -> Mat2 {
+        crate::formula::evaluate(custom.formula, z, jacobian, c, dc, iters).1
+    }
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        constants: &FragmentConstants,
+        custom: &Custom,
+    ) -> (f32, f32) {
+        // As for the built-in polynomials, low degrees are treated as 2
+        let degree = crate::formula::degree(custom.formula).abs();
+        let log2_degree = if degree <= 2.0 { 1.0 } else { degree.log2() };
+        polynomial_estimates_for(z, dz, log2_degree, inscribed_radius(constants))
+    }
+}
+
+/// A user-defined sequence of the simpler algorithms, each with its own exponent
+struct Hybrid {}
+
+/// Where we are in the hybrid sequence
+#[derive(Clone, Copy, Default)]
+struct HybridState {
+    /// Index of the current step
+    step: u32,
+    /// Number of iterations of the current step so far
+    count: u32,
+}
+
+impl<E: Exponentiator> AlgorithmDetail<E> for Hybrid {
+    type State = HybridState;
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn iterate_with_state(
+        z: Complex,
+        dz: Complex,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        iters: u32,
+        state: &mut HybridState,
+        custom: &Custom,
+    ) -> (Complex, Complex) {
+        let steps = custom.hybrid;
+        if steps.is_empty() {
+            return Mandelbrot::iterate_algorithm(z, dz, crate::exponentiation::Exp2, c, dc, iters);
+        }
+        let step = steps[state.step as usize];
+        let result = hybrid_step(&step, z, dz, c, dc, iters);
+        state.count += 1;
+        if state.count >= step.repeat {
+            state.count = 0;
+            state.step += 1;
+            if state.step as usize >= steps.len() || steps[state.step as usize].repeat == 0 {
+                state.step = 0;
+            }
+        }
+        result
+    }
+    const ANALYTIC: bool = false;
+    #[inline(always)]
+    fn iterate_jacobian(
+        z: Complex,
+        jacobian: Mat2,
+        _e: E,
+        c: Complex,
+        dc: f32,
+        iters: u32,
+        state: &HybridState,
+        custom: &Custom,
+    ) -> Mat2 {
+        let steps = custom.hybrid;
+        if steps.is_empty() {
+            return complex_matrix(2.0 * z) * jacobian + Mat2::from_diagonal(Vec2::splat(dc));
+        }
+        // This is called before iterate_with_state, so the state refers to the step to come
+        hybrid_jacobian(&steps[state.step as usize], z, c, jacobian, dc, iters)
+    }
+    #[inline(always)]
+    fn escape_estimates(
+        z: Complex,
+        dz: Complex,
+        constants: &FragmentConstants,
+        custom: &Custom,
+    ) -> (f32, f32) {
+        // The escaping orbit grows by the geometric mean of the exponents
+        let steps = custom.hybrid;
+        let mut total = 0.0;
+        let mut count = 0;
+        let mut i = 0;
+        while i < steps.len() && steps[i].repeat != 0 {
+            total += steps[i].repeat as f32 * log2_exponent(&steps[i].exponent);
+            count += steps[i].repeat;
+            i += 1;
+        }
+        let log2_exponent = if count == 0 {
+            1.0
+        } else {
+            total / count as f32
+        };
+        polynomial_estimates_for(z, dz, log2_exponent, inscribed_radius(constants))
+    }
+}
+
+/// Calls `$apply::<F, E>(exponent, args...)` with the algorithm and exponent of a hybrid step
+macro_rules! with_hybrid_step {
+    (@exponent $fractal:ident, $exponent:expr, $apply:ident, ($($arg:expr),*)) => {{
+        use crate::exponentiation::{Exp2, ExpComplex, ExpFloat, ExpIntN};
+        let exponent = $exponent;
+        match exponent.typ {
+            NumericType::Integer if exponent.int == 2 => $apply::<$fractal, _>(Exp2, $($arg),*),
+            NumericType::Integer => $apply::<$fractal, _>(ExpIntN(exponent.int), $($arg),*),
+            NumericType::Float => $apply::<$fractal, _>(ExpFloat(exponent.real), $($arg),*),
+            _ => $apply::<$fractal, _>(ExpComplex::from(*exponent), $($arg),*),
+        }
+    }};
+    ($step:expr, $apply:ident, $args:tt) => {
+        match $step.algorithm {
+            Algorithm::Mandelbar => with_hybrid_step!(@exponent Mandelbar, &$step.exponent, $apply, $args),
+            Algorithm::BurningShip => with_hybrid_step!(@exponent BurningShip, &$step.exponent, $apply, $args),
+            Algorithm::Celtic => with_hybrid_step!(@exponent Celtic, &$step.exponent, $apply, $args),
+            Algorithm::BirdOfPrey => with_hybrid_step!(@exponent BirdOfPrey, &$step.exponent, $apply, $args),
+            // The host only sends the algorithms in HYBRID_ALGORITHMS
+            _ => with_hybrid_step!(@exponent Mandelbrot, &$step.exponent, $apply, $args),
+        }
+    };
+}
+
+/// One iteration of a step of a hybrid sequence
+#[inline(always)]
+fn hybrid_step(
+    step: &HybridStep,
+    z: Complex,
+    dz: Complex,
+    c: Complex,
+    dc: f32,
+    iters: u32,
+) -> (Complex, Complex) {
+    /// Applies the algorithm with the exponent, as the Runner would
+    #[inline(always)]
+    fn apply<F: AlgorithmDetail<E>, E: Exponentiator>(
+        e: E,
+        z: Complex,
+        dz: Complex,
+        c: Complex,
+        dc: f32,
+        iters: u32,
+    ) -> (Complex, Complex) {
+        let mut z = z;
+        F::pre_modify_point(&mut z);
+        F::iterate_algorithm(z, dz, e, c, dc, iters)
+    }
+
+    with_hybrid_step!(step, apply, (z, dz, c, dc, iters))
+}
+
+/// One iteration of the Jacobian for a step of a hybrid sequence
+#[inline(always)]
+fn hybrid_jacobian(
+    step: &HybridStep,
+    z: Complex,
+    c: Complex,
+    jacobian: Mat2,
+    dc: f32,
+    iters: u32,
+) -> Mat2 {
+    #[inline(always)]
+    fn apply<F: AlgorithmDetail<E>, E: Exponentiator>(
+        e: E,
+        z: Complex,
+        c: Complex,
+        jacobian: Mat2,
+        dc: f32,
+        iters: u32,
+    ) -> Mat2 {
+        F::iterate_jacobian(
+            z,
+            jacobian,
+            e,
+            c,
+            dc,
+            iters,
+            &F::State::default(),
+            &Custom::default(),
+        )
+    }
+
+    with_hybrid_step!(step, apply, (z, c, jacobian, dc, iters))
+}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use crate::{fractal, vec2, FragmentConstants, OrbitState, PointResult, Vec2};
+    use const_default::ConstDefault as _;
+    use shader_common::{
+        data::DEFAULT_GRID_SIZE,
+        enums::{Algorithm, Antialias, Bailout, ColourStyle, Colourer, OrbitTrap},
+        Flags, NumericType, Palette, PushExponent,
+    };
+    use shader_util::Size;
+
+    use pretty_assertions::assert_eq;
+
+    fn test_frag_consts() -> FragmentConstants {
+        FragmentConstants {
+            flags: Flags::NEEDS_REITERATE,
+            viewport_translate: vec2(0., 0.),
+            viewport_zoom: 0.3,
+            size: Size::new(1, 1),
+            max_iter: 10,
+            algorithm: Algorithm::Mandelbrot,
+            exponent: PushExponent::from(2),
+            palette: Palette::DEFAULT,
+            inspector_point_pixel_address: Vec2::default(),
+            julia_parameter: Vec2::default(),
+            reference_orbit_len: 0,
+            newton_roots: FragmentConstants::DEFAULT_NEWTON_ROOTS,
+            phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+            escape_radius: FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+            bailout: Bailout::Circle,
+            trap: OrbitTrap::Point,
+            trap_point: FragmentConstants::DEFAULT_TRAP_POINT,
+            trap_radius: FragmentConstants::DEFAULT_TRAP_RADIUS,
+            grid_size: DEFAULT_GRID_SIZE,
+            grid_origin: Default::default(),
+            pan: Default::default(),
+            preview_offset: Vec2::default(),
+            preview_scale: 1.0,
+            slice_start: 0,
+            slice_end: 0,
+            sample_slice: 0,
+            antialias: Antialias::Off,
+        }
+    }
+
+    #[test]
+    fn mandelbrot_known_answer() {
+        let point = crate::vec2(-0.75, 0.75);
+        eprintln!("{:#?}", test_frag_consts());
+        let result = fractal::render(&test_frag_consts(), point);
+        eprintln!("{result:?}");
+        assert_eq!(result.iters_fraction(), 0.52201414);
     }
     #[test]
     fn mandelbrot_known_answer_cpow() {
@@ -307,4 +1880,1137 @@ mod tests {
         eprintln!("{result:?}");
         assert_eq!(result.iters_fraction(), 0.5220146);
     }
+
+    #[test]
+    fn julia_unit_disc() {
+        // With c = 0 the Julia set is the closed unit disc
+        let consts = FragmentConstants {
+            flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+            max_iter: 50,
+            ..test_frag_consts()
+        };
+        for inside in [vec2(0., 0.), vec2(0.5, 0.5), vec2(-0.9, 0.)] {
+            assert!(fractal::render(&consts, inside).inside(), "{inside}");
+        }
+        for outside in [vec2(1.1, 0.), vec2(0., -2.), vec2(0.8, 0.8)] {
+            assert!(!fractal::render(&consts, outside).inside(), "{outside}");
+        }
+        // 2 -> 4 -> 16 escapes after two iterations
+        assert_eq!(fractal::render(&consts, vec2(2., 0.)).iters_whole(), 2);
+    }
+
+    #[test]
+    fn julia_origin_matches_parameter_plane() {
+        use strum::VariantArray as _;
+        // Starting from z=0, the Julia set for c has the same orbit as the parameter plane at c
+        for algorithm in Algorithm::VARIANTS {
+            if *algorithm == Algorithm::Mandeldrop {
+                // inverts the pixel, so the origin is not a useful test point
+                continue;
+            }
+            if matches!(
+                algorithm,
+                Algorithm::Newton
+                    | Algorithm::NewtonRoots
+                    | Algorithm::Nova
+                    | Algorithm::Sine
+                    | Algorithm::SinePlusC
+                    | Algorithm::Lambda
+            ) {
+                // these start their orbits elsewhere
+                continue;
+            }
+            for exponent in [
+                PushExponent::from(2),
+                PushExponent::from(3),
+                PushExponent::from(2.5),
+            ] {
+                for c in [vec2(-0.75, 0.125), vec2(0.3, 0.5), vec2(-1.6, 0.1)] {
+                    let consts = FragmentConstants {
+                        algorithm: *algorithm,
+                        exponent,
+                        max_iter: 100,
+                        ..test_frag_consts()
+                    };
+                    let julia = FragmentConstants {
+                        flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+                        julia_parameter: c,
+                        ..consts
+                    };
+                    let direct = fractal::render(&consts, c);
+                    let via_julia = fractal::render(&julia, vec2(0., 0.));
+                    assert_eq!(direct.inside(), via_julia.inside(), "{algorithm:?} {c}");
+                    assert_eq!(
+                        direct.iters_whole(),
+                        via_julia.iters_whole(),
+                        "{algorithm:?} {c}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cardioid_and_bulb() {
+        for (c, period) in [
+            (vec2(0., 0.), 1),
+            (vec2(-0.1, 0.3), 1),
+            (vec2(0.24, 0.), 1),
+            (vec2(-1., 0.), 2),
+            (vec2(-1.1, 0.1), 2),
+            (vec2(0.26, 0.), 0),
+            (vec2(-0.1225, 0.7449), 0), // period 3 bulb
+            (vec2(-2., 1.), 0),
+        ] {
+            assert_eq!(fractal::mandelbrot_component(c.into()), period, "{c}");
+        }
+        let consts = FragmentConstants {
+            max_iter: FragmentConstants::MAX_ITER_LIMIT,
+            ..test_frag_consts()
+        };
+        let result = fractal::render(&consts, vec2(-1.05, 0.));
+        assert!(result.inside());
+        assert_eq!(result.period(), 2);
+    }
+
+    #[test]
+    fn periodicity() {
+        let consts = FragmentConstants {
+            max_iter: FragmentConstants::MAX_ITER_LIMIT,
+            size: Size::new(1000, 1000),
+            ..test_frag_consts()
+        };
+        // Centres of the period 3 and 4 bulbs
+        for (c, period) in [(vec2(-0.1226, 0.7449), 3), (vec2(-1.3107, 0.), 4)] {
+            let result = fractal::render(&consts, c);
+            assert!(result.inside(), "{c}");
+            assert_eq!(result.period(), period, "{c}");
+        }
+        // The basilica: c = -1 has a period 2 attractor
+        let julia = FragmentConstants {
+            flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+            julia_parameter: vec2(-1., 0.),
+            ..consts
+        };
+        let result = fractal::render(&julia, vec2(0.1, 0.05));
+        assert!(result.inside());
+        assert_eq!(result.period(), 2);
+        // Escaping points have no period
+        let result = fractal::render(&consts, vec2(0.3, 0.));
+        assert!(!result.inside());
+        assert_eq!(result.period(), 0);
+    }
+
+    #[test]
+    fn periodicity_agrees_with_brute_force() {
+        #![allow(clippy::cast_precision_loss)]
+        // Early exits must not change which points are inside
+        let consts = FragmentConstants {
+            max_iter: 2000,
+            size: Size::new(100, 100),
+            ..test_frag_consts()
+        };
+        let brute_force = |c: Vec2| {
+            let c = c.as_dvec2();
+            let mut z = spirv_std::glam::DVec2::ZERO;
+            for _ in 0..consts.max_iter {
+                z = spirv_std::glam::dvec2(z.x * z.x - z.y * z.y, 2. * z.x * z.y) + c;
+                if z.length() >= f64::from(consts.escape_radius) {
+                    return false;
+                }
+            }
+            true
+        };
+        let mut mismatches = 0;
+        for y in 0..48 {
+            for x in 0..50 {
+                let c = vec2(-2. + x as f32 * 0.05, y as f32 * 0.025);
+                if fractal::render(&consts, c).inside() != brute_force(c) {
+                    eprintln!("mismatch at {c}");
+                    mismatches += 1;
+                }
+            }
+        }
+        assert!(mismatches <= 2, "{mismatches} points differ");
+    }
+
+    #[test]
+    fn perturbed_matches_direct() {
+        // At shallow zoom, perturbation must agree with direct iteration
+        let consts = FragmentConstants {
+            max_iter: 200,
+            ..test_frag_consts()
+        };
+        for centre in [vec2(-0.75, 0.125), vec2(0.25, 0.5), vec2(0.5, 0.0)] {
+            let orbit = util::reference_orbit(
+                &util::BigComplex::try_new(f64::from(centre.x), f64::from(centre.y)).unwrap(),
+                consts.max_iter,
+                fractal::REFERENCE_ESCAPE_SQ,
+            );
+            let consts = FragmentConstants {
+                reference_orbit_len: orbit.len() as u32,
+                ..consts
+            };
+            for delta in [
+                vec2(0.0, 0.0),
+                vec2(0.01, 0.02),
+                vec2(-0.03, 0.0),
+                vec2(-0.5, 0.1),
+            ] {
+                let direct = fractal::render(&consts, centre + delta);
+                let perturbed = fractal::render_perturbed(&consts, delta, &orbit);
+                eprintln!("{centre} + {delta}: {direct:?} vs {perturbed:?}");
+                assert_eq!(direct.inside(), perturbed.inside());
+                assert_eq!(direct.iters_whole(), perturbed.iters_whole());
+                // Allow for f32 error in the direct result
+                let style = shader_common::enums::ColourStyle::Continuous;
+                assert!((direct.iters(style) - perturbed.iters(style)).abs() < 0.05);
+            }
+        }
+    }
+
+    #[test]
+    fn perturbed_deep_zoom() {
+        // Far beyond the precision of f32, compare against iterating each point at high precision
+        use util::{BigComplex, BigVec2};
+        const PRECISION: usize = 192;
+        let consts = FragmentConstants {
+            max_iter: 5000,
+            ..test_frag_consts()
+        };
+        let centre = BigComplex::try_new(-0.743_643_887_037_158_7, 0.131_825_904_205_301_97)
+            .unwrap()
+            .with_precision(PRECISION);
+        let orbit = util::reference_orbit(&centre, consts.max_iter, fractal::REFERENCE_ESCAPE_SQ);
+        let consts = FragmentConstants {
+            reference_orbit_len: orbit.len() as u32,
+            ..consts
+        };
+        let mut escapes = 0;
+        for delta in (-4..4)
+            .map(|i| vec2(3e-13 * i as f32, -2e-13 * i as f32))
+            .chain([vec2(1e-25, 1e-25), vec2(-2e-30, 3e-30)])
+        {
+            let perturbed = fractal::render_perturbed(&consts, delta, &orbit);
+            let point =
+                centre.clone() + BigComplex::from(BigVec2::try_from(delta.as_dvec2()).unwrap());
+            let reference =
+                util::reference_orbit(&point, consts.max_iter, consts.escape_radius.powi(2));
+            let expected_iters = reference.len() as u32 - 1;
+            eprintln!("{delta}: {perturbed:?}, expected {expected_iters}");
+            if perturbed.inside() {
+                assert_eq!(expected_iters, consts.max_iter);
+            } else {
+                // Points extremely close to the set are chaotic, so we allow a small error
+                escapes += 1;
+                assert!(perturbed.iters_whole().abs_diff(expected_iters) <= expected_iters / 200);
+            }
+        }
+        assert!(escapes > 0, "test points should not all be inside the set");
+    }
+
+    #[test]
+    fn perturbed_distance_deep_zoom() {
+        // Near the tip of the set at -2, a pixel is so small that the derivative
+        // (in complex units) overflows f32 before the orbit escapes.
+        // The tip is the nearest point of the set, so the distance to it is known exactly.
+        let consts = FragmentConstants {
+            max_iter: 500,
+            viewport_zoom: 1e36,
+            ..test_frag_consts()
+        };
+        let spacing = consts.pixel_spacing();
+        let orbit = util::reference_orbit(
+            &util::BigComplex::try_new(-2.0, 0.0).unwrap(),
+            consts.max_iter,
+            fractal::REFERENCE_ESCAPE_SQ,
+        );
+        let consts = FragmentConstants {
+            reference_orbit_len: orbit.len() as u32,
+            ..consts
+        };
+        for pixels in [1.0, 3.0, 20.0] {
+            let perturbed = fractal::render_perturbed(&consts, vec2(-pixels * spacing, 0.), &orbit);
+            let distance = perturbed.distance() / spacing;
+            eprintln!("{pixels} pixels: {perturbed:?}, distance {distance}");
+            assert!(!perturbed.inside());
+            // By the Koebe quarter theorem the estimate is between the distance and four times it
+            // (with a little slack for the finite escape radius)
+            assert!(
+                distance > pixels && distance < pixels * 4.1,
+                "{pixels} pixels from the set, but the estimate is {distance}"
+            );
+        }
+    }
+
+    #[test]
+    fn newton_basins() {
+        use core::f32::consts::TAU;
+        let consts = FragmentConstants {
+            algorithm: Algorithm::Newton,
+            exponent: PushExponent::from(3),
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        // Points near each cube root of unity converge to it
+        for k in 0..3 {
+            let angle = TAU * k as f32 / 3.;
+            let point = vec2(angle.cos(), angle.sin()) * 1.1;
+            let result = fractal::render(&consts, point);
+            assert!(!result.inside(), "{point}");
+            assert_eq!(result.basin(), k + 1, "{point}");
+            assert!(result.iters_whole() < 10, "{result:?}");
+            assert!((0.0..=1.0).contains(&result.iters_fraction()));
+        }
+        // Points further away take longer
+        let near = fractal::render(&consts, vec2(1.1, 0.));
+        let far = fractal::render(&consts, vec2(50., 0.));
+        assert_eq!(far.basin(), 1);
+        assert!(far.iters(ColourStyle::Continuous) > near.iters(ColourStyle::Continuous));
+        // The origin is a pole of the Newton map, so never converges
+        let origin = fractal::render(&consts, vec2(0., 0.));
+        assert!(origin.inside());
+        assert_eq!(origin.basin(), 0);
+    }
+
+    #[test]
+    fn newton_roots_basins() {
+        let consts = FragmentConstants {
+            algorithm: Algorithm::NewtonRoots,
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        for (i, root) in consts.newton_roots.iter().enumerate() {
+            let result = fractal::render(&consts, *root + vec2(0.05, -0.03));
+            assert!(!result.inside(), "{root}");
+            assert_eq!(result.basin(), i as u32 + 1, "{root}");
+        }
+        let moved = FragmentConstants {
+            newton_roots: [vec2(2., 2.), vec2(-3., 0.), vec2(0., -4.)],
+            ..consts
+        };
+        assert_eq!(fractal::render(&moved, vec2(1.9, 2.1)).basin(), 1);
+        assert_eq!(fractal::render(&moved, vec2(-2.9, 0.2)).basin(), 2);
+    }
+
+    #[test]
+    fn nova() {
+        let consts = FragmentConstants {
+            algorithm: Algorithm::Nova,
+            exponent: PushExponent::from(3),
+            max_iter: 200,
+            ..test_frag_consts()
+        };
+        // With c = 0, Nova starts at a root and stays there
+        let result = fractal::render(&consts, vec2(0., 0.));
+        assert!(!result.inside());
+        assert_eq!(result.basin(), 1);
+        // Small c perturbs the fixed point but it still attracts
+        let result = fractal::render(&consts, vec2(-0.1, 0.05));
+        assert!(!result.inside());
+        assert!(result.iters_whole() > 1);
+        // Julia mode: the pixel is the starting point
+        let julia = FragmentConstants {
+            flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+            julia_parameter: vec2(-0.1, 0.),
+            ..consts
+        };
+        assert!(!fractal::render(&julia, vec2(1.2, 0.1)).inside());
+    }
+
+    #[test]
+    fn transcendental_bailouts() {
+        let consts = FragmentConstants {
+            algorithm: Algorithm::Exponential,
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        // c.exp(z) with c = 1: 0, 1, e, 15.15, 3.8e6 escapes on the real part
+        let result = fractal::render(&consts, vec2(1., 0.));
+        assert!(!result.inside());
+        assert_eq!(result.iters_whole(), 4);
+        // below c = 1/e there is an attracting fixed point
+        assert!(fractal::render(&consts, vec2(0.3, 0.)).inside());
+
+        // The sine and cosine algorithms escape on the imaginary part, and are bounded on the real line
+        for algorithm in [Algorithm::Sine, Algorithm::Cosine, Algorithm::SinePlusC] {
+            let consts = FragmentConstants {
+                algorithm,
+                ..consts
+            };
+            assert!(
+                fractal::render(&consts, vec2(0.5, 0.)).inside(),
+                "{algorithm:?}"
+            );
+            let result = fractal::render(&consts, vec2(0.5, 3.));
+            assert!(!result.inside(), "{algorithm:?}");
+            assert!(result.distance() >= 0., "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn transcendental_smoothing() {
+        #![allow(clippy::cast_precision_loss)]
+        // The continuous iteration count must decrease steadily as c moves away from the set
+        let consts = FragmentConstants {
+            algorithm: Algorithm::Exponential,
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        let mut previous = f32::INFINITY;
+        for i in 0..40 {
+            let c = vec2(1.0 + i as f32 * 0.1, 0.);
+            let result = fractal::render(&consts, c);
+            let iters = result.iters(ColourStyle::Continuous);
+            assert!(iters < previous, "{c}: {iters} >= {previous}");
+            assert!(
+                i == 0 || previous - iters < 1.5,
+                "{c}: {iters} jumped from {previous}"
+            );
+            assert!((0.0..=1.0).contains(&result.iters_fraction()), "{c}");
+            previous = iters;
+        }
+    }
+
+    #[test]
+    fn super_log() {
+        use float_eq::assert_float_eq;
+        assert_float_eq!(fractal::super_log(1.), 0., abs <= 1e-6);
+        assert_float_eq!(fractal::super_log(core::f32::consts::E), 1., abs <= 1e-6);
+        let ee = core::f32::consts::E.exp();
+        assert_float_eq!(fractal::super_log(ee), 2., abs <= 1e-5);
+        assert!(fractal::super_log(f32::INFINITY).is_finite());
+    }
+
+    #[test]
+    fn phoenix() {
+        // With p = 0, Phoenix is the Mandelbrot set
+        let consts = FragmentConstants {
+            algorithm: Algorithm::Phoenix,
+            phoenix_parameter: vec2(0., 0.),
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        let mandelbrot = FragmentConstants {
+            algorithm: Algorithm::Mandelbrot,
+            ..consts
+        };
+        for c in [vec2(-0.75, 0.125), vec2(0.3, 0.5), vec2(-1.6, 0.1)] {
+            let expected = fractal::render(&mandelbrot, c);
+            let result = fractal::render(&consts, c);
+            assert_eq!(expected.inside(), result.inside(), "{c}");
+            if !expected.inside() {
+                assert_eq!(expected.iters_whole(), result.iters_whole(), "{c}");
+                assert_eq!(expected.iters_fraction(), result.iters_fraction(), "{c}");
+            }
+        }
+        // The classic Phoenix Julia set, p = -0.5, c = 0.5667
+        let julia = FragmentConstants {
+            flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+            julia_parameter: vec2(0.5667, 0.),
+            phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+            ..consts
+        };
+        assert!(fractal::render(&julia, vec2(0., 0.5)).inside());
+        assert!(!fractal::render(&julia, vec2(0., 0.)).inside());
+        // The parameter changes the result
+        let other = FragmentConstants {
+            phoenix_parameter: vec2(0.5, 0.),
+            ..consts
+        };
+        let c = vec2(0.4, 0.3);
+        assert_ne!(
+            fractal::render(&consts, c).iters(ColourStyle::Continuous),
+            fractal::render(&other, c).iters(ColourStyle::Continuous)
+        );
+    }
+
+    #[test]
+    fn magnet() {
+        for (algorithm, escapes) in [
+            (Algorithm::MagnetI, vec2(2.5, 0.5)),
+            (Algorithm::MagnetII, vec2(1.5, 1.)),
+        ] {
+            let consts = FragmentConstants {
+                algorithm,
+                max_iter: 200,
+                size: Size::new(1000, 1000),
+                ..test_frag_consts()
+            };
+            // Far out, z = 1 is an attracting fixed point
+            let result = fractal::render(&consts, vec2(8., 0.5));
+            assert!(result.inside(), "{algorithm:?}");
+            assert_eq!(result.period(), 1, "{algorithm:?}");
+            let result = fractal::render(&consts, escapes);
+            assert!(!result.inside(), "{algorithm:?} {result:?}");
+            assert!(result.distance() > 0., "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn lambda() {
+        let consts = FragmentConstants {
+            algorithm: Algorithm::Lambda,
+            max_iter: 200,
+            size: Size::new(1000, 1000),
+            ..test_frag_consts()
+        };
+        // For 1 < c < 3 the logistic map has an attracting fixed point
+        let result = fractal::render(&consts, vec2(2.5, 0.));
+        assert!(result.inside());
+        assert_eq!(result.period(), 1);
+        // ... and a 2-cycle just beyond
+        assert_eq!(fractal::render(&consts, vec2(3.2, 0.)).period(), 2);
+        // c = 10: 0.5, 2.5, -37.5 escapes
+        let result = fractal::render(&consts, vec2(10., 0.));
+        assert!(!result.inside());
+        assert_eq!(result.iters_whole(), 2);
+    }
+
+    #[test]
+    fn hybrid() {
+        use shader_common::hybrid::HybridStep;
+        let render_hybrid = |consts: &FragmentConstants, steps: &[HybridStep], c: Vec2| {
+            let custom = fractal::Custom {
+                hybrid: steps,
+                ..Default::default()
+            };
+            fractal::render_custom(consts, c, custom)
+        };
+        let hybrid = FragmentConstants {
+            algorithm: Algorithm::Hybrid,
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        let points = [
+            vec2(-1.6, 0.1),
+            vec2(0.3, 0.5),
+            vec2(-0.2, 1.0),
+            vec2(0.45, -0.3),
+        ];
+        let same = |a: &PointResult, b: &PointResult| {
+            a.inside() == b.inside()
+                && a.iters_whole() == b.iters_whole()
+                && a.iters_fraction() == b.iters_fraction()
+        };
+
+        // Alternating Mandelbrot and Celtic steps is the Variant algorithm
+        let variant = FragmentConstants {
+            algorithm: Algorithm::Variant,
+            ..hybrid
+        };
+        let steps = [
+            HybridStep::new(Algorithm::Mandelbrot, 1, PushExponent::from(2)),
+            HybridStep::new(Algorithm::Celtic, 1, PushExponent::from(2)),
+        ];
+        for c in points {
+            let expected = fractal::render(&variant, c);
+            assert!(same(&expected, &render_hybrid(&hybrid, &steps, c)), "{c}");
+        }
+
+        // A single step is that algorithm, with that exponent
+        let burning_ship = FragmentConstants {
+            algorithm: Algorithm::BurningShip,
+            exponent: PushExponent::from(3),
+            ..hybrid
+        };
+        let steps = [HybridStep::new(
+            Algorithm::BurningShip,
+            5,
+            PushExponent::from(3),
+        )];
+        for c in points {
+            let expected = fractal::render(&burning_ship, c);
+            assert!(same(&expected, &render_hybrid(&hybrid, &steps, c)), "{c}");
+        }
+
+        // A step with a repeat count of 0 ends the sequence
+        let steps = [
+            HybridStep::new(Algorithm::Mandelbrot, 1, PushExponent::from(2)),
+            HybridStep::new(Algorithm::Celtic, 1, PushExponent::from(2)),
+            HybridStep::new(Algorithm::Mandelbar, 0, PushExponent::from(2)),
+            HybridStep::new(Algorithm::BirdOfPrey, 3, PushExponent::from(2)),
+        ];
+        for c in points {
+            let expected = fractal::render(&variant, c);
+            assert!(same(&expected, &render_hybrid(&hybrid, &steps, c)), "{c}");
+        }
+
+        // Repeat counts change the result
+        let steps = [
+            HybridStep::new(Algorithm::Mandelbrot, 2, PushExponent::from(2)),
+            HybridStep::new(Algorithm::Celtic, 1, PushExponent::from(2)),
+        ];
+        assert!(points.iter().any(|c| !same(
+            &fractal::render(&variant, *c),
+            &render_hybrid(&hybrid, &steps, *c)
+        )));
+    }
+
+    /// One iteration in f64, given z, c and the iteration count
+    type Step = dyn Fn([f64; 2], [f64; 2], u32) -> [f64; 2];
+
+    /// Distance estimate for an escaping orbit of `step`, from finite differences in f64
+    fn reference_distance(step: &Step, c: Vec2) -> f64 {
+        let orbit = |c: [f64; 2], limit: u32| {
+            let mut z = [0.0_f64, 0.0];
+            let mut n = 0;
+            while n < limit
+                && z[0].hypot(z[1]) < f64::from(FragmentConstants::DEFAULT_ESCAPE_RADIUS)
+            {
+                z = step(z, c, n);
+                n += 1;
+            }
+            (z, n)
+        };
+        let c = [f64::from(c.x), f64::from(c.y)];
+        let (z, n) = orbit(c, 100);
+        let h = 1e-8;
+        let modulus = |dre: f64, dim: f64| {
+            let (z, _) = orbit([c[0] + dre, c[1] + dim], n);
+            z[0].hypot(z[1])
+        };
+        let gradient = [
+            (modulus(h, 0.) - modulus(-h, 0.)) / (2. * h),
+            (modulus(0., h) - modulus(0., -h)) / (2. * h),
+        ];
+        let za = z[0].hypot(z[1]);
+        2. * za.ln() * za / gradient[0].hypot(gradient[1])
+    }
+
+    #[test]
+    fn non_analytic_distance_known_answer() {
+        use shader_common::hybrid::HybridStep;
+        fn square(z: [f64; 2]) -> [f64; 2] {
+            [z[0] * z[0] - z[1] * z[1], 2. * z[0] * z[1]]
+        }
+        fn add(z: [f64; 2], c: [f64; 2]) -> [f64; 2] {
+            [z[0] + c[0], z[1] + c[1]]
+        }
+        fn celtic(z: [f64; 2], c: [f64; 2]) -> [f64; 2] {
+            let w = square(z);
+            add([w[0].abs(), w[1]], c)
+        }
+        fn burning_ship(z: [f64; 2], c: [f64; 2]) -> [f64; 2] {
+            add(square([z[0].abs(), z[1].abs()]), c)
+        }
+        let steps = [
+            HybridStep::new(Algorithm::BurningShip, 1, PushExponent::from(2)),
+            HybridStep::new(Algorithm::Celtic, 2, PushExponent::from(2)),
+        ];
+        let cases: [(Algorithm, &Step); 6] = [
+            (Algorithm::Mandelbar, &|z, c, _| {
+                add(square([z[0], -z[1]]), c)
+            }),
+            (Algorithm::BurningShip, &|z, c, _| burning_ship(z, c)),
+            (Algorithm::Celtic, &|z, c, _| celtic(z, c)),
+            (Algorithm::BirdOfPrey, &|z, c, _| {
+                add(square([z[0], z[1].abs()]), c)
+            }),
+            (Algorithm::Variant, &|z, c, n| {
+                if n % 2 == 1 {
+                    celtic(z, c)
+                } else {
+                    add(square(z), c)
+                }
+            }),
+            (Algorithm::Hybrid, &|z, c, n| {
+                if n.is_multiple_of(3) {
+                    burning_ship(z, c)
+                } else {
+                    celtic(z, c)
+                }
+            }),
+        ];
+        for (algorithm, step) in cases {
+            let consts = FragmentConstants {
+                algorithm,
+                max_iter: 100,
+                ..test_frag_consts()
+            };
+            let mut checked = 0;
+            for c in [
+                vec2(-1.8, 0.05),
+                vec2(0.3, 0.6),
+                vec2(-0.6, 0.9),
+                vec2(0.4, -0.45),
+                vec2(-1.2, -0.6),
+                vec2(0.45, 0.1),
+                vec2(-0.1, -1.2),
+            ] {
+                let custom = fractal::Custom {
+                    hybrid: &steps,
+                    ..Default::default()
+                };
+                let result = fractal::render_custom(&consts, c, custom);
+                if result.inside() {
+                    continue;
+                }
+                checked += 1;
+                let expected = reference_distance(step, c);
+                let actual = f64::from(result.distance());
+                assert!(
+                    (actual - expected).abs() < 0.01 * expected,
+                    "{algorithm:?} {c}: {actual} != {expected}"
+                );
+            }
+            assert!(checked >= 3, "{algorithm:?}: only {checked} points escaped");
+        }
+    }
+
+    #[test]
+    fn formula_folds() {
+        // A formula which folds or conjugates the plane must track the Jacobian,
+        // so its distance estimate agrees with the built-in algorithm's
+        for (source, algorithm) in [
+            ("abs(z)^2 + c", Algorithm::BurningShip),
+            ("conj(z)^2 + c", Algorithm::Mandelbar),
+        ] {
+            let program = util::compile_formula(source).unwrap();
+            let builtin = FragmentConstants {
+                algorithm,
+                max_iter: 100,
+                ..test_frag_consts()
+            };
+            let formula = FragmentConstants {
+                algorithm: Algorithm::Formula,
+                ..builtin
+            };
+            let mut checked = 0;
+            for c in [
+                vec2(-1.8, 0.05),
+                vec2(0.3, 0.6),
+                vec2(-0.6, 0.9),
+                vec2(0.4, -0.45),
+                vec2(-1.2, -0.6),
+                vec2(0.45, 0.1),
+                vec2(-0.1, -1.2),
+            ] {
+                let custom = fractal::Custom {
+                    formula: &program,
+                    ..Default::default()
+                };
+                let expected = fractal::render(&builtin, c);
+                let actual = fractal::render_custom(&formula, c, custom);
+                assert_eq!(actual.inside(), expected.inside(), "{source} {c}");
+                if expected.inside() {
+                    continue;
+                }
+                checked += 1;
+                assert_eq!(actual.iters_whole(), expected.iters_whole(), "{source} {c}");
+                assert!(
+                    (actual.distance() - expected.distance()).abs() < 1e-4 * expected.distance(),
+                    "{source} {c}: {actual:?} != {expected:?}"
+                );
+            }
+            assert!(checked >= 3, "{source}: only {checked} points escaped");
+        }
+    }
+
+    /// Renders a point a slice of `slice` iterations at a time, as a progressive render would
+    fn render_progressively(
+        constants: &FragmentConstants,
+        point: Vec2,
+        custom: fractal::Custom,
+        slice: u32,
+    ) -> PointResult {
+        let mut orbit = OrbitState::default();
+        let mut start = 0;
+        loop {
+            let end = (start + slice).min(constants.max_iter);
+            let sliced = FragmentConstants {
+                flags: constants.flags | Flags::PROGRESSIVE,
+                slice_start: start,
+                slice_end: end,
+                ..*constants
+            };
+            let result = fractal::render_slice(&sliced, point, custom, start > 0, &mut orbit);
+            if end == constants.max_iter || !result.iterations_exhausted() {
+                return result;
+            }
+            start = end;
+        }
+    }
+
+    #[test]
+    fn progressive_seams() {
+        // A point which escapes on the first iteration of a resumed slice is coloured
+        // as if its orbit had been taken in one go
+        for algorithm in [Algorithm::Mandelbrot, Algorithm::Sine, Algorithm::Lambda] {
+            let constants = FragmentConstants {
+                algorithm,
+                max_iter: 100,
+                ..test_frag_consts()
+            };
+            let mut checked = 0;
+            for c in (0..81).map(|i| vec2((i % 9) as f32, (i / 9) as f32) * 0.45 - 1.8) {
+                let whole = fractal::render(&constants, c).with_trap(0.);
+                if whole.inside() || whole.iters_whole() < 2 {
+                    continue;
+                }
+                checked += 1;
+                let slice = whole.iters_whole() - 1;
+                let sliced = render_progressively(&constants, c, fractal::Custom::default(), slice)
+                    .with_trap(0.);
+                assert_eq!(
+                    format!("{sliced:?}"),
+                    format!("{whole:?}"),
+                    "{algorithm:?} {c}"
+                );
+            }
+            assert!(checked >= 3, "{algorithm:?}: only {checked} points escaped");
+        }
+    }
+
+    #[test]
+    fn progressive_formulas() {
+        // A formula which folds or conjugates the plane would lose its Jacobian between slices,
+        // so only holomorphic formulas may be rendered progressively
+        let points = [
+            vec2(-1.8, 0.05),
+            vec2(0.3, 0.6),
+            vec2(-0.6, 0.9),
+            vec2(0.4, -0.45),
+            vec2(-1.2, -0.6),
+            vec2(0.45, 0.1),
+            vec2(-0.1, -1.2),
+        ];
+        for (source, holomorphic) in [
+            ("z^2 + c", true),
+            ("z^3 + c", true),
+            ("abs(z)^2 + c", false),
+            ("conj(z)^2 + c", false),
+        ] {
+            let program = util::compile_formula(source).unwrap();
+            let custom = fractal::Custom {
+                formula: &program,
+                ..Default::default()
+            };
+            let constants = FragmentConstants {
+                algorithm: Algorithm::Formula,
+                max_iter: 100,
+                ..test_frag_consts()
+            };
+            assert_eq!(
+                fractal::supports_progressive(Algorithm::Formula, &constants.palette, &program),
+                holomorphic,
+                "{source}"
+            );
+            // (The orbit trap isn't resumed, and progressive palettes don't use it. Cycles may be
+            // found at another point of the orbit, so only the escaping points are compared.)
+            let differs = points.iter().any(|&c| {
+                let whole = fractal::render_custom(&constants, c, custom).with_trap(0.);
+                let sliced = render_progressively(&constants, c, custom, 3).with_trap(0.);
+                !whole.inside() && format!("{whole:?}") != format!("{sliced:?}")
+            });
+            assert_eq!(differs, !holomorphic, "{source}");
+        }
+    }
+
+    #[test]
+    fn orbit_traps() {
+        use float_eq::assert_float_eq;
+        let z = crate::Complex::new(3., -4.);
+        for (shape, expected) in [
+            (OrbitTrap::Point, 5.),
+            (OrbitTrap::Line, 4.),
+            (OrbitTrap::Cross, 3.),
+            (OrbitTrap::Circle, 4.),
+            (OrbitTrap::UserPoint, 32.5_f32.sqrt()),
+        ] {
+            let consts = FragmentConstants {
+                trap: shape,
+                ..test_frag_consts()
+            };
+            assert_float_eq!(fractal::trap_distance(z, &consts), expected, ulps <= 2);
+        }
+
+        let trap_palette = Palette {
+            colourer: Colourer::Trap,
+            ..Palette::DEFAULT
+        };
+        // The orbit of 0 stays at the origin, so hits the point trap; the cardioid shortcut must not be taken
+        let consts = FragmentConstants {
+            palette: trap_palette,
+            ..test_frag_consts()
+        };
+        let data = fractal::render(&consts, vec2(0., 0.));
+        assert!(data.inside());
+        assert_float_eq!(data.trap_distance(), 0., abs <= 1e-6);
+        // A real orbit runs along the line trap, whether it escapes or not
+        let consts = FragmentConstants {
+            trap: OrbitTrap::Line,
+            ..consts
+        };
+        for c in [vec2(-1.8, 0.), vec2(0.3, 0.), vec2(-1.2, 0.)] {
+            assert_float_eq!(fractal::render(&consts, c).trap_distance(), 0., abs <= 1e-6);
+        }
+        // Off the axis, the orbit passes no closer than its nearest point
+        let c = vec2(0.3, 0.6);
+        let d = fractal::render(&consts, c).trap_distance();
+        assert!(d > 0. && d <= 0.6, "{d}");
+    }
+
+    #[test]
+    fn bailout_shapes() {
+        #![allow(clippy::cast_precision_loss)]
+        use strum::VariantArray as _;
+        let z = crate::Complex::new(3., -4.);
+        for (shape, expected) in [
+            (Bailout::Circle, 25.),
+            (Bailout::Square, 16.),
+            (Bailout::Real, 9.),
+            (Bailout::Imaginary, 16.),
+            (Bailout::Manhattan, 49.),
+        ] {
+            assert_eq!(fractal::bailout_norm_sq(z, shape), expected, "{shape:?}");
+        }
+        let circle = FragmentConstants {
+            max_iter: 1000,
+            escape_radius: 1000.,
+            size: Size::new(1000, 1000),
+            ..test_frag_consts()
+        };
+        for c in [vec2(0.3, 0.6), vec2(-1.8, 0.05), vec2(-0.75, 0.2)] {
+            let reference = fractal::render(&circle, c).iters_whole();
+            for shape in Bailout::VARIANTS {
+                let consts = FragmentConstants {
+                    bailout: *shape,
+                    ..circle
+                };
+                let iters = fractal::render(&consts, c).iters_whole();
+                // Shapes inside the circle let orbits escape sooner, those outside it later
+                match shape {
+                    Bailout::Manhattan => assert!(iters <= reference, "{shape:?} {c}"),
+                    Bailout::Circle => assert_eq!(iters, reference),
+                    _ => assert!(iters >= reference, "{shape:?} {c}"),
+                }
+            }
+        }
+        // The smoothed count is continuous across the iteration bands, whatever the shape,
+        // and its fractional part never exceeds 1.
+        // Small radii only smooth roughly, but must not jump by a whole band.
+        for (radius, tolerance) in [(1000., 0.05), (FragmentConstants::MIN_ESCAPE_RADIUS, 0.5)] {
+            for shape in Bailout::VARIANTS {
+                let consts = FragmentConstants {
+                    bailout: *shape,
+                    escape_radius: radius,
+                    ..circle
+                };
+                let mut previous = None;
+                for i in 0..=200 {
+                    let c = vec2(-0.2, 1.5 - i as f32 * 0.0015);
+                    let result = fractal::render(&consts, c);
+                    assert!(result.iters_fraction() <= 1.0, "{shape:?} {radius} {c}");
+                    let iters = result.iters(ColourStyle::Continuous);
+                    if let Some(previous) = previous {
+                        let step: f32 = iters - previous;
+                        assert!(
+                            step.abs() < tolerance,
+                            "{shape:?} {radius} {c}: {previous} -> {iters}"
+                        );
+                    }
+                    previous = Some(iters);
+                }
+            }
+        }
+        // A small radius still escapes consistently
+        let small = FragmentConstants {
+            escape_radius: FragmentConstants::MIN_ESCAPE_RADIUS,
+            ..circle
+        };
+        let result = fractal::render(&small, vec2(0.3, 0.6));
+        assert!(!result.inside());
+        assert!(result.iters_whole() < fractal::render(&circle, vec2(0.3, 0.6)).iters_whole());
+    }
+
+    #[test]
+    fn averages() {
+        #![allow(clippy::cast_precision_loss)]
+        use float_eq::assert_float_eq;
+        use shader_common::enums::Modifier;
+        let consts = FragmentConstants {
+            max_iter: 1000,
+            escape_radius: 1000.,
+            size: Size::new(1000, 1000),
+            palette: Palette::default().with_brightness(Modifier::StripeAverage),
+            ..test_frag_consts()
+        };
+        // A real, positive orbit: every point is at angle 0, and moves the same way as the last.
+        // |z| = |z²| + |c| exactly, the upper bound of the triangle inequality.
+        let data = fractal::render(&consts, vec2(0.3, 0.));
+        assert!(!data.inside());
+        assert_float_eq!(data.stripe_average(), 0.5, abs <= 1e-5);
+        assert_float_eq!(data.triangle_average(), 1.0, abs <= 1e-5);
+        assert_float_eq!(data.curvature_average(), 0.0, abs <= 1e-5);
+
+        // They are not collected unless the palette uses them
+        let plain = FragmentConstants {
+            palette: Palette::default(),
+            ..consts
+        };
+        let data = fractal::render(&plain, vec2(0.3, 0.6));
+        assert!(!data.inside());
+        assert_float_eq!(data.stripe_average(), 0.0, abs <= 0.0);
+        assert_float_eq!(data.triangle_average(), 0.0, abs <= 0.0);
+        assert_float_eq!(data.curvature_average(), 0.0, abs <= 0.0);
+
+        // They are continuous across the iteration bands
+        let mut previous: Option<PointResult> = None;
+        for i in 0..=200 {
+            let c = vec2(-0.2, 1.5 - i as f32 * 0.0015);
+            let data = fractal::render(&consts, c);
+            for get in [
+                PointResult::stripe_average,
+                PointResult::triangle_average,
+                PointResult::curvature_average,
+            ] {
+                assert!((0.0..=1.0).contains(&get(&data)), "{c}: {data:?}");
+                if let Some(previous) = previous {
+                    let step: f32 = get(&data) - get(&previous);
+                    assert!(step.abs() < 0.05, "{c}: {previous:?} -> {data:?}");
+                }
+            }
+            previous = Some(data);
+        }
+
+        // Perturbation collects the same statistics
+        let centre = vec2(-0.75, 0.125);
+        let orbit = util::reference_orbit(
+            &util::BigComplex::try_new(f64::from(centre.x), f64::from(centre.y)).unwrap(),
+            consts.max_iter,
+            fractal::REFERENCE_ESCAPE_SQ,
+        );
+        let consts = FragmentConstants {
+            reference_orbit_len: orbit.len() as u32,
+            ..consts
+        };
+        for c in [
+            vec2(0.3, 0.6),
+            vec2(-1.8, 0.05),
+            vec2(-0.6, 0.9),
+            vec2(0.4, -0.45),
+        ] {
+            let delta = c - centre;
+            let direct = fractal::render(&consts, c);
+            let perturbed = fractal::render_perturbed(&consts, delta, &orbit);
+            assert!(!direct.inside(), "{c}");
+            let (a, b) = (direct.c(), perturbed.c());
+            for (x, y) in [
+                (direct.stripe_average(), perturbed.stripe_average()),
+                (direct.triangle_average(), perturbed.triangle_average()),
+                (direct.curvature_average(), perturbed.curvature_average()),
+            ] {
+                assert_float_eq!(x, y, abs <= 0.01, "{c}: {a:?} vs {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn interior_data() {
+        use float_eq::assert_float_eq;
+        use shader_common::enums::Interior;
+        let consts = FragmentConstants {
+            max_iter: 1000,
+            palette: Palette {
+                interior: Interior::Multiplier,
+                ..Palette::default()
+            },
+            ..test_frag_consts()
+        };
+        // The centres of the main cardioid and the period 2 bulb are superattracting
+        for (c, period) in [(vec2(0., 0.), 1), (vec2(-1., 0.), 2)] {
+            let data = fractal::render(&consts, c);
+            assert!(data.inside());
+            assert_eq!(data.period(), period);
+            assert_float_eq!(data.multiplier_abs(), 0., abs <= 1e-5);
+        }
+        // At 0, the estimate is exactly 0.5; the true distance (0.25) lies within a factor of 4
+        assert_float_eq!(
+            fractal::render(&consts, vec2(0., 0.)).interior_distance(),
+            0.5,
+            abs <= 1e-5
+        );
+        // In the bulb the multiplier is 4(c + 1)
+        let data = fractal::render(&consts, vec2(-1.1, 0.1));
+        assert_float_eq!(data.multiplier_abs(), 0.4 * 2f32.sqrt(), abs <= 1e-4);
+
+        // Iterating the orbit (which colouring by the trap forces) finds the same cycle as the shortcut
+        let iterated = FragmentConstants {
+            palette: Palette {
+                colourer: shader_common::enums::Colourer::Trap,
+                ..consts.palette
+            },
+            ..consts
+        };
+        for c in [
+            vec2(-0.1, 0.1),
+            vec2(0.2, 0.3),
+            vec2(-1.1, 0.1),
+            vec2(-1.0, -0.05),
+        ] {
+            let quick = fractal::render(&consts, c);
+            let slow = fractal::render(&iterated, c);
+            assert_eq!(quick.period(), slow.period(), "{c}");
+            assert_float_eq!(quick.multiplier_abs(), slow.multiplier_abs(), abs <= 1e-3);
+            assert_float_eq!(quick.multiplier_arg(), slow.multiplier_arg(), abs <= 1e-3);
+            assert_float_eq!(
+                quick.interior_distance(),
+                slow.interior_distance(),
+                rmax <= 1e-2
+            );
+        }
+        // Elsewhere in the set, the cycles found have multipliers too
+        let data = fractal::render(&consts, vec2(-0.12, 0.75));
+        assert_eq!(data.period(), 3);
+        assert!(data.multiplier_abs() < 1.0);
+        assert!(data.interior_distance() > 0. && data.interior_distance() < 0.1);
+
+        // Other fractals have a multiplier but no distance estimate
+        let julia = FragmentConstants {
+            flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+            julia_parameter: vec2(-1., 0.),
+            ..consts
+        };
+        let data = fractal::render(&julia, vec2(0.1, 0.));
+        assert_eq!(data.period(), 2);
+        assert!(data.multiplier_abs() < 0.05);
+        assert!(data.interior_distance().is_infinite());
+
+        // No interior data is collected unless the palette wants it
+        let plain = FragmentConstants {
+            palette: Palette::default(),
+            ..consts
+        };
+        assert_eq!(
+            fractal::render(&plain, vec2(-0.12, 0.75)).multiplier_abs(),
+            0.
+        );
+    }
+
+    #[test]
+    fn surface_normal() {
+        use core::f32::consts::{FRAC_PI_2, PI};
+        use float_eq::assert_float_eq;
+        // The normal points away from the set (roughly: the set isn't round)
+        let consts = FragmentConstants {
+            max_iter: 100,
+            ..test_frag_consts()
+        };
+        for (c, expected) in [
+            (vec2(0.5, 0.), 0.),
+            (vec2(0., 1.5), FRAC_PI_2),
+            (vec2(0., -1.5), -FRAC_PI_2),
+        ] {
+            let data = fractal::render(&consts, c);
+            assert!(!data.inside());
+            assert_float_eq!(data.normal(), expected, abs <= 0.4);
+        }
+        // (either side of the branch cut)
+        assert_float_eq!(
+            fractal::render(&consts, vec2(-2.5, 0.)).normal().abs(),
+            PI,
+            abs <= 0.4
+        );
+    }
 }
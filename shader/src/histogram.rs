@@ -0,0 +1,221 @@
+//! Reduction of the cached iteration counts to [`IterationStats`], for the palette mappings which adapt to the view.
+//!
+//! On the GPU this is three compute passes, run one after another: [`find_range_cs`], [`count_cs`], [`accumulate_cs`].
+//! On the host, [`reduce`] runs the same steps in turn.
+
+use spirv_std::glam::{UVec2, UVec3, Vec3Swizzles as _};
+use spirv_std::spirv;
+
+use shader_common::{
+    data::PointResultA,
+    histogram::{IterationStats, ReductionParams, HISTOGRAM_BINS},
+};
+
+/// Size of the workgroups of the per-pixel passes (this must match their `threads` attributes)
+pub const WORKGROUP_SIZE: UVec2 = UVec2::new(8, 8);
+
+/// Lowers `target` to `value`, if that is smaller
+fn atomic_min(target: &mut u32, value: u32) {
+    #[cfg(target_arch = "spirv")]
+    unsafe {
+        use spirv_std::memory::{Scope, Semantics};
+        spirv_std::arch::atomic_u_min::<u32, { Scope::Device as u32 }, { Semantics::NONE.bits() }>(
+            target, value,
+        );
+    }
+    #[cfg(not(target_arch = "spirv"))]
+    {
+        *target = (*target).min(value);
+    }
+}
+
+/// Raises `target` to `value`, if that is larger
+fn atomic_max(target: &mut u32, value: u32) {
+    #[cfg(target_arch = "spirv")]
+    unsafe {
+        use spirv_std::memory::{Scope, Semantics};
+        spirv_std::arch::atomic_u_max::<u32, { Scope::Device as u32 }, { Semantics::NONE.bits() }>(
+            target, value,
+        );
+    }
+    #[cfg(not(target_arch = "spirv"))]
+    {
+        *target = (*target).max(value);
+    }
+}
+
+/// Adds one to `target`
+fn atomic_increment(target: &mut u32) {
+    #[cfg(target_arch = "spirv")]
+    unsafe {
+        use spirv_std::memory::{Scope, Semantics};
+        spirv_std::arch::atomic_i_increment::<
+            u32,
+            { Scope::Device as u32 },
+            { Semantics::NONE.bits() },
+        >(target);
+    }
+    #[cfg(not(target_arch = "spirv"))]
+    {
+        *target += 1;
+    }
+}
+
+/// The measure of the point at a pixel, if it is in view and escaped; otherwise a negative number
+fn measure_at(pixel: UVec2, params: &ReductionParams, grid_a: &[PointResultA]) -> f32 {
+    if pixel.x >= params.size.width || pixel.y >= params.size.height {
+        return -1.0;
+    }
+    let point = grid_a[params.index(pixel)];
+    if point.inside() {
+        return -1.0;
+    }
+    IterationStats::measure(point.iters(params.colour_style))
+}
+
+/// First pass: finds the range of the measures
+pub fn find_range(
+    pixel: UVec2,
+    params: &ReductionParams,
+    grid_a: &[PointResultA],
+    stats: &mut IterationStats,
+) {
+    let measure = measure_at(pixel, params, grid_a);
+    if measure >= 0.0 {
+        // Non-negative floats sort in the same order as their bits
+        atomic_min(&mut stats.min_bits, measure.to_bits());
+        atomic_max(&mut stats.max_bits, measure.to_bits());
+    }
+}
+
+/// Second pass: counts the points into the bins
+pub fn count(
+    pixel: UVec2,
+    params: &ReductionParams,
+    grid_a: &[PointResultA],
+    stats: &mut IterationStats,
+) {
+    let measure = measure_at(pixel, params, grid_a);
+    if measure >= 0.0 {
+        let (bin, _) = stats.bin(measure);
+        atomic_increment(&mut stats.bins[bin as usize]);
+        atomic_increment(&mut stats.count);
+    }
+}
+
+/// Third pass: turns the bin counts into running totals
+pub fn accumulate(stats: &mut IterationStats) {
+    let mut i = 1;
+    while i < HISTOGRAM_BINS as usize {
+        stats.bins[i] += stats.bins[i - 1];
+        i += 1;
+    }
+}
+
+/// SPIRV `compute` entrypoint for [`find_range`], one invocation per pixel
+#[spirv(compute(threads(8, 8)))]
+pub fn find_range_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] grid_a: &[PointResultA],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] stats: &mut IterationStats,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] params: &ReductionParams,
+) {
+    find_range(id.xy(), params, grid_a, stats);
+}
+
+/// SPIRV `compute` entrypoint for [`count`], one invocation per pixel
+#[spirv(compute(threads(8, 8)))]
+pub fn count_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] grid_a: &[PointResultA],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] stats: &mut IterationStats,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] params: &ReductionParams,
+) {
+    count(id.xy(), params, grid_a, stats);
+}
+
+/// SPIRV `compute` entrypoint for [`accumulate`], in a single invocation
+#[spirv(compute(threads(1)))]
+pub fn accumulate_cs(
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] stats: &mut IterationStats,
+) {
+    accumulate(stats);
+}
+
+/// Reduces a grid of points on the host, as the GPU does
+#[cfg(not(target_arch = "spirv"))]
+pub fn reduce(params: &ReductionParams, grid_a: &[PointResultA]) -> IterationStats {
+    let pixels = || {
+        (0..params.size.height)
+            .flat_map(move |y| (0..params.size.width).map(move |x| UVec2::new(x, y)))
+    };
+    let mut stats = IterationStats::EMPTY;
+    for pixel in pixels() {
+        find_range(pixel, params, grid_a, &mut stats);
+    }
+    for pixel in pixels() {
+        count(pixel, params, grid_a, &mut stats);
+    }
+    accumulate(&mut stats);
+    stats
+}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::reduce;
+    use shader_common::{
+        data::PointResult, enums::ColourStyle, histogram::ReductionParams,
+        histogram::HISTOGRAM_BINS,
+    };
+    use shader_util::Size;
+
+    #[test]
+    fn reduction() {
+        // A 3x2 view in a grid 4 wide: the last column is out of view
+        let iters = [10, 20, 40, 99, 1000, u32::MAX, 100, 99];
+        let grid = iters
+            .iter()
+            .map(|&i| {
+                if i == u32::MAX {
+                    PointResult::new_inside(0., 0., 0., 0).a()
+                } else {
+                    PointResult::new_outside(i, 0.5, 0., 0., 0.).a()
+                }
+            })
+            .collect::<Vec<_>>();
+        let params = ReductionParams {
+            grid: Size {
+                width: 4,
+                height: 2,
+            },
+            ..ReductionParams::new(
+                Size {
+                    width: 3,
+                    height: 2,
+                },
+                ColourStyle::Discrete,
+            )
+        };
+        let stats = reduce(&params, &grid);
+        // The inside point and those out of view aren't counted
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.bins[HISTOGRAM_BINS as usize - 1], 5);
+        assert!((stats.min() - 10f32.ln()).abs() < 1e-6);
+        assert!((stats.max() - 1000f32.ln()).abs() < 1e-6);
+        // 10..1000 is two decades, so 100 is half way
+        assert!((stats.auto_range(100.) - 0.5).abs() < 1e-6);
+        // Two of the points have fewer iterations than 40, and one has as many
+        assert!((0.4..0.6).contains(&stats.equalised(40.)));
+
+        // The continuous style counts the fractional iterations
+        let continuous = reduce(
+            &ReductionParams {
+                colour_style: ColourStyle::Continuous,
+                ..params
+            },
+            &grid,
+        );
+        assert!((continuous.min() - 10.5f32.ln()).abs() < 1e-6);
+    }
+}
@@ -4,64 +4,245 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
-use spirv_std::glam::{f32, vec2, vec3, Vec2, Vec3, Vec4, Vec4Swizzles as _};
+use spirv_std::glam::{f32, ivec2, vec2, vec3, IVec2, UVec2, Vec2, Vec3, Vec4, Vec4Swizzles as _};
 use spirv_std::spirv;
 
 use shader_common::{
-    data::{PointResult, PointResultA, PointResultB},
-    Flags, FragmentConstants, GRID_SIZE,
+    data::{OrbitState, PointResult, PointResultA, PointResultB, PointResultC, Supersamples},
+    gradient::GradientStop,
+    histogram::IterationStats,
+    hybrid::HybridStep,
+    Flags, FragmentConstants, PushConstants, ViewParameters,
 };
 use shader_util::grid::{GridRef, GridRefMut};
 
 pub use shader_common::{Complex, INSPECTOR_MARKER_SIZE};
 
+pub mod antialias;
 pub mod colour;
 pub mod exponentiation;
+pub mod formula;
 pub mod fractal;
+pub mod histogram;
 
 fn new_york_distance(a: Vec2, b: Vec2) -> f32 {
     (a.x - b.x).abs() + (a.y - b.y).abs()
 }
 
+/// Is a pixel on an edge, for [`Flags::ADAPTIVE_ANTIALIAS`]?
+/// This looks at its distance estimate, and at the neighbouring pixels which the cache holds.
+fn on_edge(
+    constants: &FragmentConstants,
+    pixel: UVec2,
+    point: &PointResult,
+    grid_a: &[PointResultA],
+) -> bool {
+    let cache = GridRef::new(constants.grid_size, grid_a);
+    let size = constants.size.as_uvec2().as_ivec2();
+    let differs = |step: IVec2| {
+        let neighbour = pixel.as_ivec2() + step;
+        neighbour.cmpge(IVec2::ZERO).all()
+            && neighbour.cmplt(size).all()
+            && !constants.needs_iterating(neighbour.as_uvec2())
+            && antialias::across_edge(
+                point.a(),
+                cache.get(constants.grid_cell(neighbour.as_uvec2())),
+            )
+    };
+    antialias::near_edge(point, constants.pixel_spacing())
+        || differs(ivec2(-1, 0))
+        || differs(ivec2(1, 0))
+        || differs(ivec2(0, -1))
+        || differs(ivec2(0, 1))
+}
+
+/// Takes the orbit of a supersample through the next [`FragmentConstants::sample_slice`]
+/// iterations, as a slice of a progressive render would, starting from `orbit` if it has
+/// already been through `start`.
+/// Returns the result so far, and how many iterations the orbit has been through.
+fn sample_slice(
+    constants: &FragmentConstants,
+    point: Vec2,
+    custom: fractal::Custom,
+    start: u32,
+    orbit: &mut OrbitState,
+) -> (PointResult, u32) {
+    let end = start
+        .saturating_add(constants.sample_slice)
+        .min(constants.max_iter);
+    let slice = FragmentConstants {
+        flags: constants.flags | Flags::PROGRESSIVE,
+        slice_start: start,
+        slice_end: end,
+        ..*constants
+    };
+    let result = fractal::render_slice(&slice, point, custom, start > 0, orbit);
+    (result, end)
+}
+
 /// SPIRV `fragment` entrypoint.
 /// This does the iteration and rendering work.
 #[spirv(fragment)]
+#[allow(clippy::too_many_arguments)]
 pub fn main_fs(
     #[spirv(frag_coord)] frag_coord: Vec4,
     #[cfg(not(feature = "emulate_constants"))]
     #[spirv(push_constant)]
-    constants: &FragmentConstants,
+    push: &PushConstants,
     #[cfg(feature = "emulate_constants")]
     #[spirv(storage_buffer, descriptor_set = 1, binding = 0)]
-    constants: &FragmentConstants,
+    push: &PushConstants,
     #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] grid_a: &mut [PointResultA],
     #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] grid_b: &mut [PointResultB],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] reference_orbit: &[Vec2],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] formula: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] hybrid: &[HybridStep],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 5)] grid_c: &mut [PointResultC],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 6)] gradient: &[GradientStop],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 7)] stats: &IterationStats,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 8)] grid_d: &mut [OrbitState],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 9)] grid_e: &mut [Supersamples],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 10)] view: &ViewParameters,
     output: &mut Vec4,
 ) {
+    let constants = &FragmentConstants::join(push, view);
     // window-relative coords (0,W) x (0,H) (they might be half pixels e.g. 0.5 to 1023.5); we ignore depth & 1/w
     let coord = frag_coord.xy();
     // viewport pixel size e.g. 1920x1080
     let size = constants.size.as_vec2();
     let pixel_spacing = constants.pixel_spacing();
 
-    let render_data = if constants.flags.contains(Flags::NEEDS_REITERATE) {
-        // convert pixel coordinates to complex units such that (0,0) is at the centre of the viewport
-        let cplx = (coord - 0.5 * size) * pixel_spacing;
-        let render_data = fractal::render(constants, cplx + constants.viewport_translate);
-        let mut cache_a = GridRefMut::new(GRID_SIZE, grid_a);
-        cache_a.set(coord.as_uvec2(), render_data.a());
-        let mut cache_b = GridRefMut::new(GRID_SIZE, grid_b);
-        cache_b.set(coord.as_uvec2(), render_data.b());
+    // While previewing a zoom, the cache holds another view: look for the pixel there,
+    // and don't overwrite what we can't yet replace
+    let preview = constants.flags.contains(Flags::ZOOM_PREVIEW);
+    let (source, cached) = if preview {
+        let source = constants.preview_coord(coord);
+        (
+            source,
+            source.cmpge(Vec2::ZERO).all()
+                && source.cmplt(size).all()
+                && constants.in_grid(source.as_uvec2()),
+        )
+    } else {
+        (coord, !constants.needs_iterating(coord.as_uvec2()))
+    };
+    // Pixels beyond the cache grids, if the view is larger, aren't stored
+    let store = !preview && constants.in_grid(coord.as_uvec2());
+    let grid_size = constants.grid_size;
+    let cell = constants.grid_cell(source.as_uvec2());
+    let cached_data = if cached {
+        let cache_a = GridRef::new(grid_size, grid_a);
+        let a = cache_a.get(cell);
+        let cache_b = GridRef::new(grid_size, grid_b);
+        let b = cache_b.get(cell);
+        let cache_c = GridRef::new(grid_size, grid_c);
+        let c = cache_c.get(cell);
+        PointResult::join(a, b, c)
+    } else {
+        PointResult::default()
+    };
+    // Rendering progressively, the orbits which the last slice left unfinished carry on
+    let progressive = constants.flags.contains(Flags::PROGRESSIVE) && store;
+    let resume = progressive && cached && cached_data.iterations_exhausted();
+
+    // convert pixel coordinates to complex units such that (0,0) is at the centre of the viewport
+    let cplx = (coord - 0.5 * size) * pixel_spacing;
+    let custom = fractal::Custom { formula, hybrid };
+    let render_data = if !cached || resume {
+        let render_data = if constants.flags.contains(Flags::PERTURBED) {
+            fractal::render_point(constants, cplx, reference_orbit, custom)
+        } else {
+            let mut orbit = if resume {
+                GridRef::new(grid_size, grid_d).get(cell)
+            } else {
+                OrbitState::default()
+            };
+            let point = cplx + constants.viewport_translate;
+            let render_data = fractal::render_slice(constants, point, custom, resume, &mut orbit);
+            if progressive {
+                GridRefMut::new(grid_size, grid_d).set(cell, orbit);
+            }
+            render_data
+        };
+        if store {
+            let mut cache_a = GridRefMut::new(grid_size, grid_a);
+            cache_a.set(cell, render_data.a());
+            let mut cache_b = GridRefMut::new(grid_size, grid_b);
+            cache_b.set(cell, render_data.b());
+            let mut cache_c = GridRefMut::new(grid_size, grid_c);
+            cache_c.set(cell, render_data.c());
+        }
         render_data
     } else {
-        let cache_a = GridRef::new(GRID_SIZE, grid_a);
-        let a = cache_a.get(coord.as_uvec2());
-        let cache_b = GridRef::new(GRID_SIZE, grid_b);
-        let b = cache_b.get(coord.as_uvec2());
-        PointResult::join(a, b)
+        cached_data
     };
 
-    let mut colour = colour::colour_data(render_data, constants, pixel_spacing);
+    let lookups = colour::Lookups { gradient, stats };
+    let mut colour = colour::colour_data(render_data, constants, pixel_spacing, lookups);
+
+    // Supersampling adds a sample to the pixel in each frame, once its point has settled in the cache
+    let samples = constants.antialias.samples();
+    if samples > 1 && store {
+        let mut cache_e = GridRefMut::new(grid_size, grid_e);
+        let mut taken = if !cached || resume || constants.flags.contains(Flags::RESAMPLE) {
+            Supersamples::default()
+        } else {
+            cache_e.get(cell)
+        };
+        let settled = cached && !progressive;
+        if settled && !taken.complete(samples) {
+            taken = if taken.count() == 0
+                && constants.flags.contains(Flags::ADAPTIVE_ANTIALIAS)
+                && !on_edge(constants, coord.as_uvec2(), &render_data, grid_a)
+            {
+                Supersamples::smooth()
+            } else {
+                let offset =
+                    antialias::sample_offset(constants.antialias, coord.as_uvec2(), taken.count());
+                // (progress is 0 once the sample's orbit has finished)
+                let (sample, progress) = if offset == Vec2::ZERO {
+                    (render_data, 0)
+                } else if constants.sample_slice == 0 || constants.flags.contains(Flags::PERTURBED)
+                {
+                    let offset = cplx + offset * pixel_spacing;
+                    let sample = fractal::render_point(constants, offset, reference_orbit, custom);
+                    (sample, 0)
+                } else {
+                    // The orbit is taken through a slice of its iterations in each frame,
+                    // carrying on from where the last frame saved it
+                    let mut cache_d = GridRefMut::new(grid_size, grid_d);
+                    let start = taken.progress();
+                    let mut orbit = if start > 0 {
+                        cache_d.get(cell)
+                    } else {
+                        OrbitState::default()
+                    };
+                    let point = cplx + offset * pixel_spacing + constants.viewport_translate;
+                    let (sample, end) = sample_slice(constants, point, custom, start, &mut orbit);
+                    if end < constants.max_iter && sample.iterations_exhausted() {
+                        cache_d.set(cell, orbit);
+                        (sample, end)
+                    } else {
+                        (sample, 0)
+                    }
+                };
+                if progress == 0 {
+                    taken.with_sample(colour::colour_data(
+                        sample,
+                        constants,
+                        pixel_spacing,
+                        lookups,
+                    ))
+                } else {
+                    taken.with_progress(progress)
+                }
+            };
+        }
+        cache_e.set(cell, taken);
+        if taken.count() > 0 {
+            colour = taken.mean();
+        }
+    }
 
     // Draw the inspector marker
     if constants.flags.contains(Flags::INSPECTOR_ACTIVE) {
@@ -74,6 +255,20 @@ pub fn main_fs(
         }
     }
 
+    // Draw the orbit trap marker, as a ring so it can't be mistaken for the inspector
+    if constants.flags.contains(Flags::TRAP_MARKER) {
+        let centre =
+            (constants.trap_point - constants.viewport_translate) / pixel_spacing + 0.5 * size;
+        let dist = centre.distance(coord);
+        if dist < INSPECTOR_MARKER_SIZE * 0.5 {
+            // leave the trap itself visible
+        } else if dist < INSPECTOR_MARKER_SIZE * 0.75 {
+            colour = Vec3::splat(0.0);
+        } else if dist < INSPECTOR_MARKER_SIZE {
+            colour = Vec3::splat(1.0);
+        }
+    }
+
     *output = colour.extend(1.0);
 }
 
@@ -94,13 +289,20 @@ pub fn main_vs(
 #[cfg(all(test, not(target_arch = "spirv")))]
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
-    use super::{new_york_distance, FragmentConstants, PointResultA, PointResultB, GRID_SIZE};
+    use super::{
+        new_york_distance, FragmentConstants, IterationStats, OrbitState, PointResultA,
+        PointResultB, PointResultC, Supersamples,
+    };
 
     use const_default::ConstDefault as _;
     use float_eq::assert_float_eq;
-    use shader_common::{enums::Algorithm, Flags, Palette, PushExponent};
+    use shader_common::{
+        data::DEFAULT_GRID_SIZE,
+        enums::{Algorithm, Antialias, Bailout, OrbitTrap},
+        Flags, Palette, PushExponent,
+    };
     use shader_util::Size;
-    use spirv_std::glam::{vec2, vec4, Vec2, Vec3, Vec4};
+    use spirv_std::glam::{ivec2, uvec2, vec2, vec4, IVec2, UVec2, Vec2, Vec3, Vec4};
 
     #[test]
     fn vertex() {
@@ -130,6 +332,24 @@ mod tests {
             exponent: PushExponent::from(2),
             palette: Palette::DEFAULT,
             inspector_point_pixel_address: Vec2::default(),
+            julia_parameter: Vec2::default(),
+            reference_orbit_len: 0,
+            newton_roots: FragmentConstants::DEFAULT_NEWTON_ROOTS,
+            phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+            escape_radius: FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+            bailout: Bailout::Circle,
+            trap: OrbitTrap::Point,
+            trap_point: FragmentConstants::DEFAULT_TRAP_POINT,
+            trap_radius: FragmentConstants::DEFAULT_TRAP_RADIUS,
+            grid_size: DEFAULT_GRID_SIZE,
+            grid_origin: UVec2::ZERO,
+            pan: IVec2::ZERO,
+            preview_offset: Vec2::ZERO,
+            preview_scale: 1.0,
+            slice_start: 0,
+            slice_end: 0,
+            sample_slice: 0,
+            antialias: Antialias::Off,
         }
     }
 
@@ -137,8 +357,12 @@ mod tests {
     fn render_save_retrieve() {
         use shader_common::Flags;
         let mut res = Vec4::default();
-        let mut grid_a = vec![PointResultA::default(); (GRID_SIZE.x * GRID_SIZE.y) as usize];
-        let mut grid_b = vec![PointResultB::default(); (GRID_SIZE.x * GRID_SIZE.y) as usize];
+        let mut grid_a =
+            vec![PointResultA::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+        let mut grid_b =
+            vec![PointResultB::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+        let mut grid_c =
+            vec![PointResultC::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
 
         let no_iterate = FragmentConstants {
             flags: Flags::empty(),
@@ -148,9 +372,18 @@ mod tests {
         // Cache starts out empty (you probably couldn't run this on an actual GPU, the NaN might trigger an abort)
         super::main_fs(
             vec4(0., 0., 0., 0.),
-            &no_iterate,
+            &no_iterate.push(),
             &mut grid_a,
             &mut grid_b,
+            &[],
+            &[],
+            &[],
+            &mut grid_c,
+            &[],
+            &IterationStats::EMPTY,
+            &mut [],
+            &mut [],
+            &no_iterate.view(),
             &mut res,
         );
         assert!(res[0].is_nan());
@@ -161,9 +394,18 @@ mod tests {
         // Pass 1 populates cache
         super::main_fs(
             vec4(0., 0., 0., 0.),
-            &test_frag_consts(),
+            &test_frag_consts().push(),
             &mut grid_a,
             &mut grid_b,
+            &[],
+            &[],
+            &[],
+            &mut grid_c,
+            &[],
+            &IterationStats::EMPTY,
+            &mut [],
+            &mut [],
+            &test_frag_consts().view(),
             &mut res,
         );
         let expected = vec4(0.0, 1.0, 0.1414485, 1.0);
@@ -175,9 +417,18 @@ mod tests {
         // Pass 2: Retrieve from cache
         super::main_fs(
             vec4(0., 0., 0., 0.),
-            &no_iterate,
+            &no_iterate.push(),
             &mut grid_a,
             &mut grid_b,
+            &[],
+            &[],
+            &[],
+            &mut grid_c,
+            &[],
+            &IterationStats::EMPTY,
+            &mut [],
+            &mut [],
+            &no_iterate.view(),
             &mut res,
         );
         assert!(
@@ -186,6 +437,374 @@ mod tests {
         );
     }
 
+    /// The cache grids: the `PointResult` shards, the saved orbits and the supersamples
+    type Grids = (
+        Vec<PointResultA>,
+        Vec<PointResultB>,
+        Vec<PointResultC>,
+        Vec<OrbitState>,
+        Vec<Supersamples>,
+    );
+
+    fn new_grids(grid_size: UVec2) -> Grids {
+        let n = (grid_size.x * grid_size.y) as usize;
+        (
+            vec![PointResultA::default(); n],
+            vec![PointResultB::default(); n],
+            vec![PointResultC::default(); n],
+            vec![OrbitState::default(); n],
+            vec![Supersamples::default(); n],
+        )
+    }
+
+    /// Draws every pixel of the view, returning the colours
+    #[allow(clippy::cast_precision_loss)]
+    fn draw(consts: &FragmentConstants, grids: &mut Grids) -> Vec<Vec4> {
+        let size = consts.size.as_uvec2();
+        (0..size.x * size.y)
+            .map(|i| {
+                let mut res = Vec4::default();
+                super::main_fs(
+                    vec4((i % size.x) as f32 + 0.5, (i / size.x) as f32 + 0.5, 0., 0.),
+                    &consts.push(),
+                    &mut grids.0,
+                    &mut grids.1,
+                    &[],
+                    &[],
+                    &[],
+                    &mut grids.2,
+                    &[],
+                    &IterationStats::EMPTY,
+                    &mut grids.3,
+                    &mut grids.4,
+                    &consts.view(),
+                    &mut res,
+                );
+                res
+            })
+            .collect()
+    }
+
+    /// Pans a view, checking that the pixels which the cache holds are taken from it,
+    /// and the rest are iterated
+    fn check_pan(grid_size: UVec2, grid_origin: UVec2) {
+        // The pixel spacing of this view is exact in binary, so panning by whole pixels
+        // gives exactly the points a fresh render would.
+        let view = FragmentConstants {
+            size: Size::new(16, 16),
+            viewport_zoom: 0.25,
+            viewport_translate: vec2(-1., 0.),
+            max_iter: 50,
+            grid_size,
+            grid_origin,
+            ..test_frag_consts()
+        };
+        let pan = ivec2(3, -2);
+        let moved = FragmentConstants {
+            viewport_translate: view.viewport_translate + pan.as_vec2() * view.pixel_spacing(),
+            ..view
+        };
+        // To tell which pixels were iterated, the panned frame draws a different fractal
+        let moved_ship = FragmentConstants {
+            algorithm: Algorithm::BurningShip,
+            ..moved
+        };
+        let mut grids = new_grids(grid_size);
+        let expected_cached = draw(&moved, &mut grids);
+        let expected_iterated = draw(&moved_ship, &mut grids);
+
+        // The first frame fills the cache; the second pans it
+        let _ = draw(&view, &mut grids);
+        let panned = FragmentConstants {
+            flags: Flags::empty(),
+            grid_origin: (grid_origin.as_ivec2() + pan)
+                .rem_euclid(grid_size.as_ivec2())
+                .as_uvec2(),
+            pan,
+            ..moved_ship
+        };
+        let result = draw(&panned, &mut grids);
+
+        // The part of the view which the cache holds
+        let held = uvec2(16, 16).min(grid_size).as_ivec2();
+        for (i, res) in result.iter().enumerate() {
+            let pixel = uvec2(i as u32 % 16, i as u32 / 16);
+            let previous = pixel.as_ivec2() + pan;
+            let expected = if pixel.cmplt(grid_size).all()
+                && previous.cmpge(IVec2::ZERO).all()
+                && previous.cmplt(held).all()
+            {
+                expected_cached[i]
+            } else {
+                expected_iterated[i]
+            };
+            assert_eq!(*res, expected, "grid {grid_size}, pixel {pixel}");
+        }
+    }
+
+    #[test]
+    fn pan() {
+        check_pan(DEFAULT_GRID_SIZE, UVec2::ZERO);
+    }
+
+    #[test]
+    fn odd_grid_sizes() {
+        // Grids which don't match the view, including some too small for it
+        for grid_size in [uvec2(17, 13), uvec2(7, 5), uvec2(16, 3), uvec2(1, 1)] {
+            check_pan(grid_size, uvec2(5, 9) % grid_size);
+
+            // Pixels beyond the grid can't be resumed, but are iterated afresh
+            let view = FragmentConstants {
+                size: Size::new(16, 16),
+                viewport_zoom: 2.,
+                viewport_translate: vec2(-0.75, 0.1),
+                max_iter: 200,
+                grid_size,
+                ..test_frag_consts()
+            };
+            let expected = draw(&view, &mut new_grids(grid_size));
+            assert_eq!(draw_progressively(&view, grid_size).last(), Some(&expected));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn zoom_preview() {
+        let view = FragmentConstants {
+            size: Size::new(16, 16),
+            viewport_zoom: 0.25,
+            viewport_translate: vec2(-1., 0.),
+            max_iter: 50,
+            ..test_frag_consts()
+        };
+        let mut grids = new_grids(view.grid_size);
+        let zoomed_out = FragmentConstants {
+            viewport_zoom: 0.125,
+            ..view
+        };
+        let expected_out = draw(&zoomed_out, &mut grids);
+        let cached = draw(&view, &mut grids);
+        // Colour of the cached pixel under a pixel of the zoomed view
+        let from_cache = |consts: &FragmentConstants, i: u32| {
+            let coord = vec2((i % 16) as f32 + 0.5, (i / 16) as f32 + 0.5);
+            let source = consts.preview_coord(coord).as_uvec2();
+            cached[(source.y * 16 + source.x) as usize]
+        };
+
+        // Zooming in about the centre: everything comes from the cache
+        let zoomed_in = FragmentConstants {
+            flags: Flags::ZOOM_PREVIEW,
+            viewport_zoom: 0.5,
+            preview_offset: vec2(4., 4.),
+            preview_scale: 0.5,
+            ..view
+        };
+        for (i, res) in draw(&zoomed_in, &mut grids).into_iter().enumerate() {
+            assert_eq!(res, from_cache(&zoomed_in, i as u32), "zoom in, pixel {i}");
+        }
+
+        // Zooming out: the border isn't in the cache, so it is iterated
+        let preview_out = FragmentConstants {
+            flags: Flags::ZOOM_PREVIEW,
+            preview_offset: vec2(-8., -8.),
+            preview_scale: 2.,
+            ..zoomed_out
+        };
+        for (i, res) in draw(&preview_out, &mut grids).into_iter().enumerate() {
+            let (x, y) = (i % 16, i / 16);
+            let expected = if (4..12).contains(&x) && (4..12).contains(&y) {
+                from_cache(&preview_out, i as u32)
+            } else {
+                expected_out[i]
+            };
+            assert_eq!(res, expected, "zoom out, pixel {x},{y}");
+        }
+
+        // Previews leave the cache alone
+        let unchanged = FragmentConstants {
+            flags: Flags::empty(),
+            ..view
+        };
+        assert_eq!(draw(&unchanged, &mut grids), cached);
+    }
+
+    /// Draws a view progressively, in slices of 30 iterations, returning the colours after each
+    fn draw_progressively(view: &FragmentConstants, grid_size: UVec2) -> Vec<Vec<Vec4>> {
+        let mut grids = new_grids(grid_size);
+        let mut slices = Vec::new();
+        let mut slice_start = 0;
+        while slice_start < view.max_iter {
+            let slice_end = (slice_start + 30).min(view.max_iter);
+            let mut consts = FragmentConstants {
+                slice_start,
+                slice_end,
+                ..*view
+            };
+            consts.flags |= Flags::PROGRESSIVE;
+            // Only the first slice iterates everything
+            consts.flags.set(Flags::NEEDS_REITERATE, slice_start == 0);
+            slices.push(draw(&consts, &mut grids));
+            slice_start = slice_end;
+        }
+        slices
+    }
+
+    #[test]
+    fn progressive() {
+        // Slices of the orbits, each carrying on from where the last left off, add up to the whole
+        let view = FragmentConstants {
+            size: Size::new(16, 16),
+            viewport_zoom: 2.,
+            viewport_translate: vec2(-0.75, 0.1),
+            max_iter: 200,
+            ..test_frag_consts()
+        };
+        let julia = FragmentConstants {
+            flags: Flags::NEEDS_REITERATE | Flags::JULIA,
+            julia_parameter: vec2(-0.75, 0.1),
+            viewport_zoom: 0.5,
+            viewport_translate: Vec2::ZERO,
+            ..view
+        };
+        for view in [view, julia] {
+            let expected = draw(&view, &mut new_grids(view.grid_size));
+            let slices = draw_progressively(&view, view.grid_size);
+            assert_ne!(slices[0], expected, "the first slice should be incomplete");
+            assert_eq!(slices.last(), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn supersampling() {
+        // Each frame from the cache adds a sample to every pixel, until the pattern is complete
+        let view = FragmentConstants {
+            size: Size::new(16, 16),
+            viewport_zoom: 2.,
+            viewport_translate: vec2(-0.75, 0.1),
+            max_iter: 50,
+            antialias: Antialias::Grid2x2,
+            ..test_frag_consts()
+        };
+        let cached = FragmentConstants {
+            flags: Flags::empty(),
+            ..view
+        };
+        let counts = |grids: &Grids| {
+            grids.4[..16]
+                .iter()
+                .map(Supersamples::count)
+                .collect::<Vec<_>>()
+        };
+        let mut grids = new_grids(view.grid_size);
+        let first = draw(&view, &mut grids);
+        assert_eq!(counts(&grids), [0; 16]);
+        let mut frames = Vec::new();
+        for n in 1..=4 {
+            frames.push(draw(&cached, &mut grids));
+            assert_eq!(counts(&grids), [n; 16]);
+        }
+        assert_ne!(frames[3], first);
+        assert_eq!(draw(&cached, &mut grids), frames[3]);
+
+        // A change of colouring starts again, and slices of a progressive render don't sample
+        let _ = draw(
+            &FragmentConstants {
+                flags: Flags::RESAMPLE,
+                ..cached
+            },
+            &mut grids,
+        );
+        assert_eq!(counts(&grids), [1; 16]);
+        let _ = draw(
+            &FragmentConstants {
+                flags: Flags::PROGRESSIVE,
+                slice_start: 50,
+                slice_end: 50,
+                ..cached
+            },
+            &mut grids,
+        );
+        assert_eq!(counts(&grids), [1; 16]);
+
+        // Adaptive anti-aliasing passes over the smooth pixels, and samples the rest as before
+        let adaptive = FragmentConstants {
+            flags: Flags::ADAPTIVE_ANTIALIAS,
+            ..cached
+        };
+        let mut grids = new_grids(view.grid_size);
+        let _ = draw(&view, &mut grids);
+        for _ in 0..4 {
+            let _ = draw(&adaptive, &mut grids);
+        }
+        let result = draw(&adaptive, &mut grids);
+        let smooth = grids.4[..16].iter().filter(|s| s.is_smooth()).count();
+        assert!(smooth > 0 && smooth < 16, "{smooth} smooth pixels");
+        for (i, (res, s)) in result.iter().zip(&grids.4).enumerate().take(16) {
+            let expected = if s.is_smooth() {
+                first[i]
+            } else {
+                frames[3][i]
+            };
+            assert_eq!(*res, expected, "pixel {i}");
+        }
+    }
+
+    #[test]
+    fn supersample_slices() {
+        // With a budget, each frame takes a sample's orbit no further than that;
+        // the orbits carry on in the next frame, and the samples come out the same
+        let view = FragmentConstants {
+            size: Size::new(16, 16),
+            viewport_zoom: 2.,
+            viewport_translate: vec2(-0.75, 0.1),
+            max_iter: 50,
+            antialias: Antialias::Grid2x2,
+            ..test_frag_consts()
+        };
+        let cached = FragmentConstants {
+            flags: Flags::empty(),
+            ..view
+        };
+        let sliced = FragmentConstants {
+            sample_slice: 20,
+            ..cached
+        };
+        let pixels = |grids: &Grids| {
+            (0..16 * 16)
+                .map(|i| grids.4[(i / 16 * view.grid_size.x + i % 16) as usize])
+                .collect::<Vec<_>>()
+        };
+        let mut grids = new_grids(view.grid_size);
+        let _ = draw(&view, &mut grids);
+        let expected = (0..4).map(|_| draw(&cached, &mut grids)).last();
+
+        let mut grids = new_grids(view.grid_size);
+        let _ = draw(&view, &mut grids);
+        let mut last = pixels(&grids);
+        let mut result = None;
+        let mut unfinished = 0;
+        // At most 3 frames for each of the 4 samples
+        for _ in 0..12 {
+            result = Some(draw(&sliced, &mut grids));
+            let taken = pixels(&grids);
+            for (i, (before, after)) in last.iter().zip(&taken).enumerate() {
+                if before.complete(4) {
+                    assert_eq!(after.count(), 4, "pixel {i}");
+                } else if after.count() == before.count() {
+                    assert_eq!(after.progress(), before.progress() + 20, "pixel {i}");
+                    unfinished += 1;
+                } else {
+                    assert_eq!(after.count(), before.count() + 1, "pixel {i}");
+                    assert_eq!(after.progress(), 0, "pixel {i}");
+                }
+            }
+            last = taken;
+        }
+        assert!(unfinished > 0, "no sample took more than one frame");
+        assert!(last.iter().all(|s| s.complete(4)));
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn an_inspector_calls() {
         let cases = &[
@@ -203,8 +822,12 @@ mod tests {
 
         for (point, expect_rgb) in cases {
             let mut res = Vec4::default();
-            let mut grid_a = vec![PointResultA::default(); (GRID_SIZE.x * GRID_SIZE.y) as usize];
-            let mut grid_b = vec![PointResultB::default(); (GRID_SIZE.x * GRID_SIZE.y) as usize];
+            let mut grid_a =
+                vec![PointResultA::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+            let mut grid_b =
+                vec![PointResultB::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+            let mut grid_c =
+                vec![PointResultC::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
 
             // Set up to inspect the pixel we're rendering
             let inspector = FragmentConstants {
@@ -214,9 +837,18 @@ mod tests {
             };
             super::main_fs(
                 vec4(0., 0., 0., 0.),
-                &inspector,
+                &inspector.push(),
                 &mut grid_a,
                 &mut grid_b,
+                &[],
+                &[],
+                &[],
+                &mut grid_c,
+                &[],
+                &IterationStats::EMPTY,
+                &mut [],
+                &mut [],
+                &inspector.view(),
                 &mut res,
             );
             let expected = Vec3::from(*expect_rgb).extend(1.0);
@@ -227,6 +859,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn trap_marker() {
+        // Distance in pixels from the rendered pixel to the trap, and the expected colour
+        let cases = &[(6.0, Some(0.0)), (8.0, Some(1.0)), (12.0, None)];
+        let plain = test_frag_consts();
+        for (offset, expect) in cases {
+            let mut res = Vec4::default();
+            let mut grid_a =
+                vec![PointResultA::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+            let mut grid_b =
+                vec![PointResultB::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+            let mut grid_c =
+                vec![PointResultC::default(); (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize];
+            let pixel = vec2(*offset, 0.) - 0.5 * plain.size.as_vec2();
+            let consts = FragmentConstants {
+                flags: Flags::TRAP_MARKER | Flags::NEEDS_REITERATE,
+                trap: OrbitTrap::UserPoint,
+                trap_point: pixel * plain.pixel_spacing(),
+                ..plain
+            };
+            super::main_fs(
+                vec4(0., 0., 0., 0.),
+                &consts.push(),
+                &mut grid_a,
+                &mut grid_b,
+                &[],
+                &[],
+                &[],
+                &mut grid_c,
+                &[],
+                &IterationStats::EMPTY,
+                &mut [],
+                &mut [],
+                &consts.view(),
+                &mut res,
+            );
+            if let Some(v) = expect {
+                let expected = Vec3::splat(*v).extend(1.0);
+                assert!(
+                    res.abs_diff_eq(expected, 0.000_001),
+                    "mismatch at offset {offset}: {res} vs {expected}"
+                );
+            } else {
+                assert!(
+                    !res.abs_diff_eq(Vec4::ONE, 0.000_001) && !res.abs_diff_eq(Vec4::W, 0.000_001),
+                    "marker drawn at offset {offset}: {res}"
+                );
+            }
+        }
+    }
+
     #[test]
     fn big_apple() {
         let cases = &[
@@ -1,24 +1,38 @@
 //! Fractal data structures
 
-use crate::enums::ColourStyle;
+use crate::{enums::ColourStyle, Complex};
+
+#[cfg(not(target_arch = "spirv"))]
+use glam::{uvec2, vec3, UVec2, Vec3};
+#[cfg(target_arch = "spirv")]
+use spirv_std::glam::{uvec2, vec3, UVec2, Vec3};
 
-use super::GRID_SIZE;
 use bytemuck::NoUninit;
 
-/// Raw data from a fractal invocation
+/// Size of the cache grids, if the device allows: enough for a window to grow to 3840x2160.
 ///
-/// This structure is split into two sub-structs because of the 128MB default limit on data sizes.
-/// With a GRID_SIZE of 3840x2160, the default limit allows us 16.18 bytes per grid pixel.
-/// Therefore, split our data into shards, each of which is up to 16 bytes in size.
-/// If somehow we need to make GRID_SIZE larger, might need to refactor this to split it differently.
+/// The host chooses the actual size when it creates the grids: see [`grid_size_for`].
+pub const DEFAULT_GRID_SIZE: UVec2 = uvec2(3840, 2160);
+
+/// The largest shard of the cache, in bytes per pixel
+pub const MAX_SHARD_SIZE: usize = 16;
+
+/// The push constants which every device supports, in bytes
+/// (`wgpu::Limits::max_push_constant_size`, as Vulkan guarantees it): see
+/// [`PushConstants`](crate::PushConstants)
+pub const MAX_PUSH_CONSTANTS_SIZE: usize = 128;
+
+/// Raw data from a fractal invocation
 ///
-/// (Yes, we could check the operational capabilities and request more... but that would involve
-/// making things dynamic. Not for today.)
+/// Each shard of the cache is a storage buffer, and the device limits how large a storage
+/// buffer binding can be (128MB by default). So we split our data into shards of up to
+/// [`MAX_SHARD_SIZE`] bytes per pixel, which allows a grid of about 8 million pixels.
 #[derive(Copy, Clone, Debug, Default, NoUninit)]
 #[repr(C)]
 pub struct PointResult {
     a: PointResultA,
     b: PointResultB,
+    c: PointResultC,
 }
 
 /// Constituent part A of `PointResult`
@@ -31,6 +45,8 @@ pub struct PointResultA {
     iters_fraction: f32,
     /// distance estimate from fractal
     distance: f32,
+    /// closest approach of the orbit to the orbit trap
+    trap_distance: f32,
 }
 
 /// Constituent part B of `PointResult`
@@ -41,26 +57,226 @@ pub struct PointResultB {
     pub angle: f32,
     /// final complex distance, squared
     pub radius_sqr: f32,
+    /// period of the attracting cycle, if the point is inside and one was found (else 0)
+    pub period: u32,
+    /// basin of attraction the orbit converged to, numbered from 1 (else 0)
+    pub basin: u32,
+}
+
+/// Constituent part C of `PointResult`: extra colouring data.
+///
+/// Apart from `normal`, this is only collected if the palette uses it (otherwise it is all zero).
+/// Points outside the set hold the averaging statistics, smoothed by the fractional
+/// iteration count. Points inside the set hold data about the cycle which attracted the orbit.
+#[derive(Copy, Clone, Debug, Default, NoUninit)]
+#[repr(C)]
+pub struct PointResultC {
+    /// outside: stripe average (range 0..1); inside: magnitude of the multiplier of the cycle (range 0..1)
+    first: f32,
+    /// outside: triangle inequality average (range 0..1); inside: angle of the multiplier (range -pi..pi)
+    second: f32,
+    /// outside: curvature average (range 0..1); inside: interior distance estimate
+    third: f32,
+    /// direction of the surface normal, if the distance estimate is taken as a height field (range -pi..pi)
+    normal: f32,
+}
+
+impl PointResultC {
+    /// Averaging statistics, for a point outside the set
+    pub fn averages(stripe: f32, triangle: f32, curvature: f32) -> Self {
+        Self {
+            first: stripe,
+            second: triangle,
+            third: curvature,
+            normal: 0.,
+        }
+    }
+    /// Data about the attracting cycle, for a point inside the set
+    pub fn interior(multiplier_abs: f32, multiplier_arg: f32, distance: f32) -> Self {
+        Self {
+            first: multiplier_abs,
+            second: multiplier_arg,
+            third: distance,
+            normal: 0.,
+        }
+    }
+}
+
+/// Where an orbit had got to when a slice of a progressive render stopped iterating it:
+/// see [`Flags::PROGRESSIVE`](crate::Flags::PROGRESSIVE).
+///
+/// This is kept in a grid of its own, alongside the `PointResult` shards. It only has room for
+/// `z` and its derivative: algorithms which need more state than that can't be resumed.
+#[derive(Copy, Clone, Debug, Default, NoUninit)]
+#[repr(C)]
+pub struct OrbitState {
+    z_re: f32,
+    z_im: f32,
+    dz_re: f32,
+    dz_im: f32,
+}
+
+impl OrbitState {
+    pub fn new(z: Complex, dz: Complex) -> Self {
+        Self {
+            z_re: z.re,
+            z_im: z.im,
+            dz_re: dz.re,
+            dz_im: dz.im,
+        }
+    }
+    /// The current point of the orbit
+    pub fn z(&self) -> Complex {
+        Complex::new(self.z_re, self.z_im)
+    }
+    /// The derivative of `z` with respect to the pixel
+    pub fn dz(&self) -> Complex {
+        Complex::new(self.dz_re, self.dz_im)
+    }
+}
+
+/// The supersamples of a pixel taken so far, for anti-aliasing:
+/// see [`FragmentConstants::antialias`](crate::FragmentConstants::antialias).
+///
+/// This is kept in a grid of its own, alongside the `PointResult` shards.
+/// If the orbit of the next sample is taken over several frames
+/// (see [`FragmentConstants::sample_slice`](crate::FragmentConstants::sample_slice)),
+/// it is saved in the [`OrbitState`] grid meanwhile, and this records how far it has got.
+#[derive(Copy, Clone, Debug, Default, NoUninit)]
+#[repr(C)]
+pub struct Supersamples {
+    /// sum of the colours of the samples (linear RGB)
+    red: f32,
+    green: f32,
+    blue: f32,
+    /// number of samples taken (in the low `COUNT_BITS`) and iterations of the next sample's
+    /// orbit so far (in the rest), or `SMOOTH`.
+    /// The shard is already as large as it may be, so they have to share.
+    count: u32,
+}
+
+impl Supersamples {
+    const SMOOTH: u32 = u32::MAX;
+    const COUNT_BITS: u32 = 8;
+    const COUNT_MASK: u32 = (1 << Self::COUNT_BITS) - 1;
+
+    /// Marks a pixel which isn't on an edge, so has no need of supersampling
+    /// (for [`Flags::ADAPTIVE_ANTIALIAS`](crate::Flags::ADAPTIVE_ANTIALIAS))
+    pub fn smooth() -> Self {
+        Self {
+            count: Self::SMOOTH,
+            ..Self::default()
+        }
+    }
+    /// Adds the colour of a sample
+    #[must_use]
+    pub fn with_sample(self, colour: Vec3) -> Self {
+        Self {
+            red: self.red + colour.x,
+            green: self.green + colour.y,
+            blue: self.blue + colour.z,
+            count: self.count() + 1,
+        }
+    }
+    /// Records how many iterations the orbit of the next sample has been taken through
+    #[must_use]
+    pub fn with_progress(self, iterations: u32) -> Self {
+        Self {
+            count: self.count() | iterations << Self::COUNT_BITS,
+            ..self
+        }
+    }
+    /// Number of samples taken
+    pub fn count(&self) -> u32 {
+        if self.is_smooth() {
+            0
+        } else {
+            self.count & Self::COUNT_MASK
+        }
+    }
+    /// How many iterations the orbit of the next sample has been taken through
+    pub fn progress(&self) -> u32 {
+        if self.is_smooth() {
+            0
+        } else {
+            self.count >> Self::COUNT_BITS
+        }
+    }
+    pub fn is_smooth(&self) -> bool {
+        self.count == Self::SMOOTH
+    }
+    /// Have all the samples of the pattern been taken?
+    pub fn complete(&self, samples: u32) -> bool {
+        self.is_smooth() || self.count() >= samples
+    }
+    /// The mean colour of the samples taken
+    pub fn mean(&self) -> Vec3 {
+        vec3(self.red, self.green, self.blue) / self.count() as f32
+    }
 }
 
-// compile time assertion: confirm that neither buffer will runtime fail in wgpu
+// compile time assertion: confirm that every shard fits the default grid, within wgpu's default limit
 const _: () = {
-    const N_POINTS: usize = (GRID_SIZE.x * GRID_SIZE.y) as usize;
+    const N_POINTS: usize = (DEFAULT_GRID_SIZE.x * DEFAULT_GRID_SIZE.y) as usize;
     const LIMIT: usize = 128 * 1024 * 1024; // == wgpu::Limits::max_storage_buffer_binding_size
-    assert!(core::mem::size_of::<PointResultA>() * N_POINTS < LIMIT);
-    assert!(core::mem::size_of::<PointResultB>() * N_POINTS < LIMIT);
+    assert!(core::mem::size_of::<PointResultA>() <= MAX_SHARD_SIZE);
+    assert!(core::mem::size_of::<PointResultB>() <= MAX_SHARD_SIZE);
+    assert!(core::mem::size_of::<PointResultC>() <= MAX_SHARD_SIZE);
+    assert!(core::mem::size_of::<OrbitState>() <= MAX_SHARD_SIZE);
+    assert!(core::mem::size_of::<Supersamples>() <= MAX_SHARD_SIZE);
+    assert!(MAX_SHARD_SIZE * N_POINTS < LIMIT);
+    // that the push constants fit the space which every device guarantees
+    assert!(core::mem::size_of::<crate::PushConstants>() <= MAX_PUSH_CONSTANTS_SIZE);
+    // and that a supersample's progress fits alongside the count
+    assert!(crate::FragmentConstants::MAX_ITER_LIMIT < 1 << (32 - Supersamples::COUNT_BITS));
 };
 
+/// Chooses the size of the cache grids, for a window of the given size (in pixels), when a
+/// storage buffer binding may be up to `max_binding_size` bytes.
+///
+/// The grids cover at least [`DEFAULT_GRID_SIZE`], so that the window has room to grow, and
+/// the whole window if it is larger. If the device can't hold that much, they cover as much
+/// of the window as it can, keeping its shape; the pixels beyond are iterated every frame.
+#[cfg(not(target_arch = "spirv"))]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn grid_size_for(window: UVec2, max_binding_size: u64) -> UVec2 {
+    let max_points = max_binding_size / MAX_SHARD_SIZE as u64;
+    let fits = |size: UVec2| u64::from(size.x) * u64::from(size.y) <= max_points;
+    let window = window.max(UVec2::ONE);
+    let wanted = window.max(DEFAULT_GRID_SIZE);
+    if fits(wanted) {
+        return wanted;
+    }
+    if fits(window) {
+        return window;
+    }
+    let scale = (max_points as f64 / (f64::from(window.x) * f64::from(window.y))).sqrt();
+    let x = ((f64::from(window.x) * scale) as u32).clamp(1, window.x);
+    let y = (max_points / u64::from(x)).min(u64::from(window.y)) as u32;
+    uvec2(x, y.max(1))
+}
+
 impl PointResult {
     // CONSTRUCTORS //////////////////////////////////////////////////////////
-    pub fn new_inside(distance: f32, angle: f32, radius_sqr: f32) -> Self {
+    pub fn new_inside(distance: f32, angle: f32, radius_sqr: f32, period: u32) -> Self {
         Self {
             a: PointResultA {
                 iters: u32::MAX,
                 iters_fraction: 0.,
                 distance,
+                trap_distance: f32::MAX,
+            },
+            b: PointResultB {
+                angle,
+                radius_sqr,
+                period,
+                basin: 0,
             },
-            b: PointResultB { angle, radius_sqr },
+            c: PointResultC::default(),
         }
     }
     pub fn new_outside(
@@ -75,13 +291,43 @@ impl PointResult {
                 iters,
                 iters_fraction,
                 distance,
+                trap_distance: f32::MAX,
+            },
+            b: PointResultB {
+                angle,
+                radius_sqr,
+                period: 0,
+                basin: 0,
             },
-            b: PointResultB { angle, radius_sqr },
+            c: PointResultC::default(),
         }
     }
+    /// Records the basin of attraction that a converging orbit ended up in
+    pub fn with_basin(mut self, basin: u32) -> Self {
+        self.b.basin = basin;
+        self
+    }
+    /// Records the closest approach of the orbit to the orbit trap
+    pub fn with_trap(mut self, trap_distance: f32) -> Self {
+        self.a.trap_distance = trap_distance;
+        self
+    }
+    /// Records the extra colouring data: see [`PointResultC`]
+    pub fn with_extra(mut self, extra: PointResultC) -> Self {
+        self.c = PointResultC {
+            normal: self.c.normal,
+            ..extra
+        };
+        self
+    }
+    /// Records the direction of the surface normal: see [`Self::normal`]
+    pub fn with_normal(mut self, normal: f32) -> Self {
+        self.c.normal = normal;
+        self
+    }
     /// Reconstitutes a `PointResult` from its storage shards
-    pub fn join(a: PointResultA, b: PointResultB) -> Self {
-        Self { a, b }
+    pub fn join(a: PointResultA, b: PointResultB, c: PointResultC) -> Self {
+        Self { a, b, c }
     }
     // ACCESSORS ////////////////////////////////////////////////////////////
     pub fn a(&self) -> PointResultA {
@@ -90,12 +336,12 @@ impl PointResult {
     pub fn b(&self) -> PointResultB {
         self.b
     }
+    pub fn c(&self) -> PointResultC {
+        self.c
+    }
     /// Iterations
     pub fn iters(&self, style: ColourStyle) -> f32 {
-        match style {
-            ColourStyle::Discrete => self.iters_whole() as f32,
-            ColourStyle::Continuous | _ => self.iters_whole() as f32 + self.iters_fraction(),
-        }
+        self.a.iters(style)
     }
 
     /// Whole part of iterations
@@ -110,6 +356,10 @@ impl PointResult {
     pub fn distance(&self) -> f32 {
         self.a.distance
     }
+    /// Closest approach of the orbit to the orbit trap (`f32::MAX` if there was no orbit)
+    pub fn trap_distance(&self) -> f32 {
+        self.a.trap_distance
+    }
     /// Final angle (-pi .. pi)
     pub fn angle(&self) -> f32 {
         self.b.angle
@@ -118,9 +368,169 @@ impl PointResult {
     pub fn radius_sqr(&self) -> f32 {
         self.b.radius_sqr
     }
+    /// Period of the cycle the orbit was attracted to, for points inside the set.
+    ///
+    /// This is 0 if the point is outside, or if no cycle was detected.
+    pub fn period(&self) -> u32 {
+        self.b.period
+    }
+    /// Basin of attraction, for algorithms which iterate until the orbit converges.
+    ///
+    /// Basins are numbered from 1. This is 0 if the algorithm does not converge, or did not identify the basin.
+    pub fn basin(&self) -> u32 {
+        self.b.basin
+    }
+    /// Stripe average: how the orbit was distributed around the origin (0..1).
+    /// Only meaningful for points outside the set.
+    pub fn stripe_average(&self) -> f32 {
+        self.c.first
+    }
+    /// Triangle inequality average: where each step of the orbit fell between the bounds
+    /// the triangle inequality puts on it (0..1). Only meaningful for points outside the set.
+    pub fn triangle_average(&self) -> f32 {
+        self.c.second
+    }
+    /// Curvature average: how sharply the orbit turned at each step (0..1).
+    /// Only meaningful for points outside the set.
+    pub fn curvature_average(&self) -> f32 {
+        self.c.third
+    }
+    /// Magnitude of the multiplier of the attracting cycle (0..1), for points inside the set.
+    ///
+    /// This is 0 at the centre of a hyperbolic component and 1 at its edge.
+    pub fn multiplier_abs(&self) -> f32 {
+        self.c.first
+    }
+    /// Angle of the multiplier of the attracting cycle (-pi..pi), for points inside the set
+    pub fn multiplier_arg(&self) -> f32 {
+        self.c.second
+    }
+    /// Estimated distance to the edge of the set, for points inside it.
+    ///
+    /// This is infinite if the algorithm has no interior distance estimate.
+    pub fn interior_distance(&self) -> f32 {
+        self.c.third
+    }
+    /// Direction of the surface normal, if the distance estimate is taken as a height field
+    /// (-pi..pi), for points outside the set.
+    ///
+    /// This is the argument of `z / dz` at escape, which points away from the set.
+    pub fn normal(&self) -> f32 {
+        self.c.normal
+    }
     // COMPUTED ACCESSORS ///////////////////////////////////////////////////
     /// Is this point inside the set? If so, the iterations count is effectively infinite.
     pub fn inside(&self) -> bool {
-        self.a.iters == u32::MAX
+        self.a.inside()
+    }
+    /// Did the orbit run out of iterations, without escaping or finding a cycle?
+    ///
+    /// Until the last slice of a progressive render, this means the orbit is unfinished.
+    pub fn iterations_exhausted(&self) -> bool {
+        self.inside() && self.b.period == 0
+    }
+}
+
+impl PointResultA {
+    /// Iterations
+    pub fn iters(&self, style: ColourStyle) -> f32 {
+        match style {
+            ColourStyle::Discrete => self.iters as f32,
+            ColourStyle::Continuous | _ => self.iters as f32 + self.iters_fraction,
+        }
+    }
+    /// Is this point inside the set?
+    pub fn inside(&self) -> bool {
+        self.iters == u32::MAX
+    }
+}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{grid_size_for, Supersamples, DEFAULT_GRID_SIZE, MAX_SHARD_SIZE};
+    use crate::FragmentConstants;
+    use glam::{uvec2, vec3, UVec2};
+
+    const MB: u64 = 1024 * 1024;
+
+    fn bytes(size: UVec2) -> u64 {
+        u64::from(size.x) * u64::from(size.y) * MAX_SHARD_SIZE as u64
+    }
+
+    #[test]
+    fn small_windows_get_the_default() {
+        for window in [uvec2(800, 600), uvec2(1, 1), uvec2(1023, 767), UVec2::ZERO] {
+            assert_eq!(
+                grid_size_for(window, 128 * MB),
+                DEFAULT_GRID_SIZE,
+                "{window}"
+            );
+        }
+    }
+
+    #[test]
+    fn large_windows_are_covered() {
+        // 5K, 8K, and a tall odd-sized one
+        for window in [uvec2(5120, 2880), uvec2(7680, 4320), uvec2(2161, 3841)] {
+            let limit = bytes(window.max(DEFAULT_GRID_SIZE));
+            assert_eq!(grid_size_for(window, limit), window.max(DEFAULT_GRID_SIZE));
+        }
+        // If the default headroom doesn't fit, the window alone may
+        let window = uvec2(4001, 1999);
+        assert_eq!(grid_size_for(window, bytes(window)), window);
+    }
+
+    #[test]
+    fn limited_devices_cover_what_they_can() {
+        for (window, limit) in [
+            (uvec2(7680, 4320), 128 * MB),
+            (uvec2(5119, 2881), 128 * MB),
+            (uvec2(999, 333), MB),
+        ] {
+            let grid = grid_size_for(window, limit);
+            assert!(bytes(grid) <= limit, "{window}: {grid} is too big");
+            assert!(
+                grid.cmple(window).all(),
+                "{window}: {grid} is larger than the window"
+            );
+            // It keeps the shape of the window, and uses most of the space
+            let aspect = |s: UVec2| f64::from(s.x) / f64::from(s.y);
+            assert!(
+                (aspect(grid) / aspect(window) - 1.).abs() < 0.1,
+                "{window}: {grid}"
+            );
+            assert!(
+                bytes(grid + UVec2::ONE) > limit,
+                "{window}: {grid} is too small"
+            );
+        }
+    }
+
+    #[test]
+    fn narrow_windows() {
+        // There's only room for a fraction of a column, so it gets one
+        assert_eq!(grid_size_for(uvec2(3, 100_000), 4096), uvec2(1, 256));
+    }
+
+    #[test]
+    fn never_empty() {
+        assert_eq!(grid_size_for(uvec2(4000, 3000), 0), UVec2::ONE);
+    }
+
+    #[test]
+    fn supersample_progress() {
+        // The count and the progress of the next sample share a field without disturbing each other
+        let progress = FragmentConstants::MAX_ITER_LIMIT;
+        let taken = Supersamples::default()
+            .with_sample(vec3(0.5, 0.25, 1.))
+            .with_progress(progress);
+        assert_eq!((taken.count(), taken.progress()), (1, progress));
+        assert!(!taken.is_smooth());
+        assert_eq!(taken.mean(), vec3(0.5, 0.25, 1.));
+        let taken = taken.with_sample(vec3(0.5, 0.75, 0.));
+        assert_eq!((taken.count(), taken.progress()), (2, 0));
+        assert_eq!(taken.mean(), vec3(0.5, 0.5, 0.5));
+        assert!(taken.complete(2) && !taken.complete(3));
     }
 }
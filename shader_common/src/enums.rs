@@ -40,7 +40,20 @@ enumdef!(
     BurningShip,
     Celtic,
     Variant,
-    BirdOfPrey
+    BirdOfPrey,
+    Newton,
+    NewtonRoots,
+    Nova,
+    Sine,
+    Cosine,
+    Exponential,
+    SinePlusC,
+    Phoenix,
+    MagnetI,
+    MagnetII,
+    Lambda,
+    Formula,
+    Hybrid
 );
 
 enumdef!(
@@ -52,7 +65,22 @@ enumdef!(
     BlackFade,
     OneLoneCoder,
     LchGradient,
-    Monochrome
+    Monochrome,
+    Basin,
+    Trap,
+    Gradient,
+    Viridis,
+    Magma,
+    Cividis,
+    OklchRainbow
+);
+
+enumdef!(
+    doc = "How the colourers which run through their palette once scale the iteration counts to it",
+    Mapping,
+    Fixed,
+    AutoRange,
+    Equalised
 );
 
 enumdef!(
@@ -70,9 +98,81 @@ enumdef!(
     Filaments1,
     Filaments2,
     FinalAngle,
-    FinalRadius
+    FinalRadius,
+    Trap,
+    StripeAverage,
+    TriangleInequality,
+    CurvatureAverage,
+    Lighting
 );
 
+impl Modifier {
+    /// Is this one of the averaging colouring statistics?
+    pub fn is_average(self) -> bool {
+        matches!(
+            self,
+            Modifier::StripeAverage | Modifier::TriangleInequality | Modifier::CurvatureAverage
+        )
+    }
+}
+
+enumdef!(
+    doc = "Colouring of the inside of the set",
+    Interior,
+    Black,
+    Period,
+    Multiplier,
+    Distance
+);
+
+enumdef!(
+    doc = "Colour space in which the gradient colourer blends between its stops",
+    Interpolation,
+    Rgb,
+    Hsl,
+    Lch
+);
+
+enumdef!(
+    doc = "Shape of the region an orbit must leave to escape",
+    Bailout,
+    Circle,
+    Square,
+    Real,
+    Imaginary,
+    Manhattan
+);
+
+enumdef!(
+    doc = "Shape of the orbit trap, whose closest approach to the orbit is recorded",
+    OrbitTrap,
+    Point,
+    Line,
+    Cross,
+    Circle,
+    UserPoint
+);
+
+enumdef!(
+    doc = "Supersampling pattern for anti-aliasing: where each pixel is sampled",
+    Antialias,
+    Off,
+    Grid2x2,
+    Grid3x3,
+    Jittered
+);
+
+impl Antialias {
+    /// How many samples the pattern takes in each pixel
+    pub fn samples(self) -> u32 {
+        match self {
+            Antialias::Off => 1,
+            Antialias::Grid2x2 => 4,
+            Antialias::Grid3x3 | Antialias::Jittered => 9,
+        }
+    }
+}
+
 macro_rules! incrementable {
     ($enum:ty) => {
         #[cfg(not(target_arch = "spirv"))]
@@ -0,0 +1,64 @@
+//! Bytecode for user-defined iteration formulas.
+//!
+//! The host compiles a formula to a program of `u32` words, which the shader interprets
+//! once per iteration on a small stack machine. Each stack entry is a complex number
+//! together with its derivative with respect to the pixel.
+//!
+//! The program layout is:
+//! - word 0: the number of instruction words which follow the header
+//! - word 1: the degree of the formula in z, as `f32` bits (used to smooth the escape count)
+//! - the instructions. Most are a single word; [`op::CONST`] is followed by the real and
+//!   imaginary parts of the constant as `f32` bits, and [`op::POWI`] by its exponent as an `i32`.
+//!
+//! The host checks that programs fit in [`FORMULA_SIZE`] words and never need more than
+//! [`FORMULA_STACK_SIZE`] stack entries.
+
+/// Capacity of the formula buffer, in words
+pub const FORMULA_SIZE: u32 = 256;
+/// Number of words in the program header
+pub const FORMULA_HEADER_SIZE: usize = 2;
+/// Depth of the interpreter's stack
+pub const FORMULA_STACK_SIZE: usize = 16;
+
+/// Opcodes
+pub mod op {
+    // Operands
+    /// Pushes the current point of the orbit
+    pub const Z: u32 = 1;
+    /// Pushes the parameter c
+    pub const C: u32 = 2;
+    /// Pushes the iteration count
+    pub const N: u32 = 3;
+    /// Pushes a constant (two operand words)
+    pub const CONST: u32 = 4;
+
+    // Binary operators: pop b, pop a, push (a op b)
+    pub const ADD: u32 = 10;
+    pub const SUB: u32 = 11;
+    pub const MUL: u32 = 12;
+    pub const DIV: u32 = 13;
+    /// Complex power, by way of the principal logarithm
+    pub const POW: u32 = 14;
+
+    // Unary operators: pop a, push f(a)
+    pub const NEG: u32 = 20;
+    /// Integer power (one operand word)
+    pub const POWI: u32 = 21;
+    /// Absolute value of the real and imaginary parts (the Burning Ship fold)
+    pub const ABS: u32 = 22;
+    /// Real part
+    pub const RE: u32 = 23;
+    /// Imaginary part, as a real number
+    pub const IM: u32 = 24;
+    pub const CONJ: u32 = 25;
+    pub const SIN: u32 = 26;
+    pub const COS: u32 = 27;
+    pub const TAN: u32 = 28;
+    pub const SINH: u32 = 29;
+    pub const COSH: u32 = 30;
+    pub const EXP: u32 = 31;
+    /// Principal natural logarithm
+    pub const LOG: u32 = 32;
+    /// Principal square root
+    pub const SQRT: u32 = 33;
+}
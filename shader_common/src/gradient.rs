@@ -0,0 +1,191 @@
+//! Gradient palettes, which blend between user-defined colour stops
+
+use bytemuck::NoUninit;
+
+#[cfg(not(target_arch = "spirv"))]
+use glam::{vec3, Vec3};
+#[cfg(target_arch = "spirv")]
+use spirv_std::glam::{vec3, Vec3};
+
+/// Capacity of the gradient buffer, in stops (enough for a Fractint map)
+pub const GRADIENT_SIZE: u32 = 256;
+
+/// One colour stop of a gradient.
+///
+/// The stops are in order of position. The gradient ends at the end of the buffer,
+/// or at the first stop with an infinite position; any space left in the buffer
+/// must be filled with [`GradientStop::END`].
+#[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+#[repr(C)]
+pub struct GradientStop {
+    /// Position along the gradient (0..1)
+    pub position: f32,
+    /// Red component of the colour (sRGB, 0..1)
+    pub red: f32,
+    /// Green component of the colour (sRGB, 0..1)
+    pub green: f32,
+    /// Blue component of the colour (sRGB, 0..1)
+    pub blue: f32,
+}
+
+impl GradientStop {
+    /// Marks the end of a gradient which doesn't fill the buffer
+    pub const END: Self = Self {
+        position: f32::INFINITY,
+        red: 0.,
+        green: 0.,
+        blue: 0.,
+    };
+
+    pub fn new(position: f32, colour: Vec3) -> Self {
+        Self {
+            position,
+            red: colour.x,
+            green: colour.y,
+            blue: colour.z,
+        }
+    }
+
+    pub fn colour(&self) -> Vec3 {
+        vec3(self.red, self.green, self.blue)
+    }
+
+    /// The gradient we start with: dark blue through white and orange, then back again
+    pub fn default_gradient() -> [Self; 6] {
+        [
+            Self::new(0.0, vec3(0.0, 0.027, 0.392)),
+            Self::new(0.16, vec3(0.125, 0.42, 0.796)),
+            Self::new(0.42, vec3(0.929, 1.0, 1.0)),
+            Self::new(0.6425, vec3(1.0, 0.667, 0.0)),
+            Self::new(0.8575, vec3(0.0, 0.008, 0.0)),
+            Self::new(1.0, vec3(0.0, 0.027, 0.392)),
+        ]
+    }
+}
+
+/// Sorts stops into the order the shader needs
+#[cfg(not(target_arch = "spirv"))]
+pub fn sort_stops(stops: &mut [GradientStop]) {
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+}
+
+/// Parses a colour written in CSS hex notation: `#RRGGBB` or `#RGB` (the `#` is optional)
+#[cfg(not(target_arch = "spirv"))]
+pub fn parse_hex_colour(s: &str) -> Option<Vec3> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let component = |i: usize, len: usize| {
+        let value = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+        // In the short form, each digit is doubled
+        let value = if len == 1 { value * 17 } else { value };
+        Some(f32::from(value) / 255.)
+    };
+    let len = match hex.len() {
+        3 => 1,
+        6 => 2,
+        _ => return None,
+    };
+    Some(vec3(
+        component(0, len)?,
+        component(1, len)?,
+        component(2, len)?,
+    ))
+}
+
+/// Writes a colour in CSS hex notation (`#rrggbb`)
+#[cfg(not(target_arch = "spirv"))]
+pub fn hex_colour(colour: Vec3) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let [r, g, b] = (colour.clamp(Vec3::ZERO, Vec3::ONE) * 255.)
+        .round()
+        .to_array()
+        .map(|c| c as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Formats a stop in the syntax accepted by its [`FromStr`](core::str::FromStr) implementation
+#[cfg(not(target_arch = "spirv"))]
+impl std::fmt::Display for GradientStop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.position, hex_colour(self.colour()))
+    }
+}
+
+/// Parses a stop written as `POSITION:#RRGGBB`, for example `0.25:#ff8800`.
+///
+/// The position is between 0 and 1.
+#[cfg(not(target_arch = "spirv"))]
+impl std::str::FromStr for GradientStop {
+    type Err = ParseGradientStopError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, colour) = s.trim().split_once(':').ok_or_else(|| {
+            ParseGradientStopError("expected a stop written as POSITION:#RRGGBB".into())
+        })?;
+        let position: f32 = position
+            .trim()
+            .parse()
+            .ok()
+            .filter(|p| (0.0..=1.0).contains(p))
+            .ok_or_else(|| ParseGradientStopError("position must be between 0 and 1".into()))?;
+        let colour = parse_hex_colour(colour.trim())
+            .ok_or_else(|| ParseGradientStopError(format!("invalid colour '{}'", colour.trim())))?;
+        Ok(Self::new(position, colour))
+    }
+}
+
+/// Error returned when parsing a [`GradientStop`] fails
+#[cfg(not(target_arch = "spirv"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseGradientStopError(String);
+
+#[cfg(not(target_arch = "spirv"))]
+impl std::fmt::Display for ParseGradientStopError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(not(target_arch = "spirv"))]
+impl std::error::Error for ParseGradientStopError {}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{hex_colour, parse_hex_colour, GradientStop};
+    use glam::vec3;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn hex() {
+        assert_eq!(parse_hex_colour("#ff0000"), Some(vec3(1., 0., 0.)));
+        assert_eq!(parse_hex_colour("00FF00"), Some(vec3(0., 1., 0.)));
+        assert_eq!(parse_hex_colour("#00f"), Some(vec3(0., 0., 1.)));
+        assert_eq!(parse_hex_colour("#fff"), parse_hex_colour("#ffffff"));
+        assert_eq!(parse_hex_colour("#12345"), None);
+        assert_eq!(parse_hex_colour("#gg0000"), None);
+        assert_eq!(parse_hex_colour("#ffé"), None);
+        assert_eq!(hex_colour(vec3(1., 0.5, 0.)), "#ff8000");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "0.25:#ff8800".parse(),
+            Ok(GradientStop::new(0.25, vec3(1., 0x88 as f32 / 255., 0.)))
+        );
+        assert!("#ff8800".parse::<GradientStop>().is_err());
+        assert!("1.5:#ff8800".parse::<GradientStop>().is_err());
+        assert!("0.5:orange".parse::<GradientStop>().is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for s in ["0:#000000", "0.5:#ff8800", "1:#123abc"] {
+            let stop: GradientStop = s.parse().unwrap();
+            assert_eq!(stop.to_string(), s);
+        }
+    }
+}
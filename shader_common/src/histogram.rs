@@ -0,0 +1,169 @@
+//! Statistics of the iteration counts in view, for palettes which adapt to them
+
+use bytemuck::NoUninit;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+use crate::enums::ColourStyle;
+use shader_util::{Size, UVec2};
+
+/// Number of bins in the histogram
+pub const HISTOGRAM_BINS: u32 = 1024;
+
+/// Range and histogram of the iteration counts of the points in view which escaped.
+///
+/// The counts are measured by [`Self::measure`], on the logarithmic scale most of the colourers use.
+/// The histogram spans the range, and holds running totals: `bins[i]` counts the points in bins `0..=i`.
+#[derive(Copy, Clone, Debug, NoUninit)]
+#[repr(C)]
+pub struct IterationStats {
+    /// Smallest measure, as the bits of an `f32` (so that the GPU can find it with integer atomics)
+    pub min_bits: u32,
+    /// Largest measure, as the bits of an `f32`
+    pub max_bits: u32,
+    /// Number of points counted
+    pub count: u32,
+    _padding: u32,
+    pub bins: [u32; HISTOGRAM_BINS as usize],
+}
+
+impl IterationStats {
+    /// The statistics of no points at all, from which a reduction starts
+    pub const EMPTY: Self = Self {
+        min_bits: f32::INFINITY.to_bits(),
+        max_bits: 0,
+        count: 0,
+        _padding: 0,
+        bins: [0; HISTOGRAM_BINS as usize],
+    };
+
+    /// The quantity we take statistics of: the log of the iteration count
+    pub fn measure(iters: f32) -> f32 {
+        // Measures are never negative, so their bits sort in the same order as they do
+        iters.max(1.0).ln()
+    }
+
+    pub fn min(&self) -> f32 {
+        f32::from_bits(self.min_bits)
+    }
+    pub fn max(&self) -> f32 {
+        f32::from_bits(self.max_bits)
+    }
+
+    /// Where a measure falls in the range (0..1)
+    fn range_fraction(&self, measure: f32) -> f32 {
+        let (min, max) = (self.min(), self.max());
+        // (This is also the case before the range has been found)
+        if max <= min {
+            return 0.0;
+        }
+        ((measure - min) / (max - min)).clamp(0.0, 1.0)
+    }
+
+    /// The bin a measure falls in, and how far along the bin it is (0..1)
+    pub fn bin(&self, measure: f32) -> (u32, f32) {
+        #![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let position = self.range_fraction(measure) * HISTOGRAM_BINS as f32;
+        let bin = (position as u32).min(HISTOGRAM_BINS - 1);
+        (bin, position - bin as f32)
+    }
+
+    /// An iteration count scaled so that the range in view runs from 0 to 1
+    pub fn auto_range(&self, iters: f32) -> f32 {
+        self.range_fraction(Self::measure(iters))
+    }
+
+    /// The proportion of the points in view with fewer iterations (0..1).
+    ///
+    /// Colouring by this spreads the palette evenly over the points in view.
+    pub fn equalised(&self, iters: f32) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let (bin, fraction) = self.bin(Self::measure(iters));
+        let before = if bin == 0 {
+            0
+        } else {
+            self.bins[bin as usize - 1]
+        };
+        let within = self.bins[bin as usize] - before;
+        (before as f32 + fraction * within as f32) / self.count as f32
+    }
+}
+
+impl Default for IterationStats {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// Tells the reduction which part of the grid to look at, and how
+#[derive(Copy, Clone, Debug)]
+// As with FragmentConstants, UVec2 is not NoUninit on spirv builds.
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct ReductionParams {
+    /// Size of the view, in pixels
+    pub size: Size,
+    /// Size of the grid holding the points (which may be larger than the view)
+    pub grid: Size,
+    /// Where the grid holds pixel (0,0) of the view. The grid wraps around,
+    /// as for [`FragmentConstants::grid_origin`](crate::FragmentConstants::grid_origin).
+    pub origin: UVec2,
+    pub colour_style: ColourStyle,
+}
+
+impl ReductionParams {
+    /// Parameters for a grid which holds exactly the view
+    pub fn new(size: Size, colour_style: ColourStyle) -> Self {
+        Self {
+            size,
+            grid: size,
+            origin: UVec2::ZERO,
+            colour_style,
+        }
+    }
+
+    /// The index in the grid of a pixel of the view
+    pub fn index(&self, pixel: UVec2) -> usize {
+        let cell = (pixel + self.origin) % self.grid.as_uvec2();
+        (cell.y * self.grid.width + cell.x) as usize
+    }
+}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{IterationStats, HISTOGRAM_BINS};
+
+    #[test]
+    fn lookups() {
+        let mut stats = IterationStats {
+            min_bits: IterationStats::measure(10.).to_bits(),
+            max_bits: IterationStats::measure(1000.).to_bits(),
+            count: 4,
+            ..IterationStats::EMPTY
+        };
+        // Three points at the bottom of the range, one at the top
+        for bin in &mut stats.bins[..] {
+            *bin = 3;
+        }
+        stats.bins[HISTOGRAM_BINS as usize - 1] = 4;
+
+        assert!(stats.auto_range(10.).abs() < 1e-6);
+        assert!((stats.auto_range(100.) - 0.5).abs() < 1e-6);
+        assert!((stats.auto_range(5000.) - 1.0).abs() < 1e-6);
+        assert!(stats.equalised(10.) < 0.75);
+        assert!((stats.equalised(100.) - 0.75).abs() < 1e-6);
+        assert!(stats.equalised(1000.) > 0.75);
+        assert_eq!(
+            stats.bin(IterationStats::measure(1000.)).0,
+            HISTOGRAM_BINS - 1
+        );
+
+        // No points: everything is at the bottom of the range
+        let empty = IterationStats::EMPTY;
+        assert!(empty.auto_range(100.).abs() < 1e-6);
+        assert!(empty.equalised(100.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,168 @@
+//! Hybrid fractals, which step through a sequence of the simpler algorithms
+
+use bytemuck::NoUninit;
+
+use crate::{enums::Algorithm, PushExponent};
+
+/// Capacity of the hybrid sequence buffer, in steps
+pub const HYBRID_SIZE: u32 = 16;
+
+/// The algorithms which may appear in a hybrid sequence
+pub const HYBRID_ALGORITHMS: [Algorithm; 5] = [
+    Algorithm::Mandelbrot,
+    Algorithm::Mandelbar,
+    Algorithm::BurningShip,
+    Algorithm::Celtic,
+    Algorithm::BirdOfPrey,
+];
+
+/// One step of a hybrid sequence: `repeat` iterations of `algorithm`, with its own exponent.
+///
+/// The sequence ends at the end of the buffer, or at the first step with a `repeat` of 0,
+/// then starts again from the beginning.
+#[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+#[repr(C)]
+pub struct HybridStep {
+    pub algorithm: Algorithm,
+    pub repeat: u32,
+    pub exponent: PushExponent,
+}
+
+impl HybridStep {
+    /// The longest run of a single step we allow
+    pub const MAX_REPEAT: u32 = 100;
+
+    pub fn new(algorithm: Algorithm, repeat: u32, exponent: PushExponent) -> Self {
+        Self {
+            algorithm,
+            repeat,
+            exponent,
+        }
+    }
+
+    /// The sequence we start with: alternate Burning Ship and Mandelbrot iterations
+    pub fn default_sequence() -> [Self; 2] {
+        [
+            Self::new(Algorithm::BurningShip, 1, PushExponent::default()),
+            Self::new(Algorithm::Mandelbrot, 1, PushExponent::default()),
+        ]
+    }
+}
+
+/// Formats a step in the syntax accepted by its [`FromStr`](core::str::FromStr) implementation
+#[cfg(not(target_arch = "spirv"))]
+impl std::fmt::Display for HybridStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use clap::ValueEnum as _;
+        let name = self
+            .algorithm
+            .to_possible_value()
+            .expect("enum variants are never skipped");
+        write!(f, "{}:{}:{}", name.get_name(), self.repeat, self.exponent)
+    }
+}
+
+/// Parses a step written as `ALGORITHM[:REPEAT[:EXPONENT]]`, for example `burning-ship:2:3`.
+///
+/// The repeat count defaults to 1 and the exponent to 2.
+#[cfg(not(target_arch = "spirv"))]
+impl std::str::FromStr for HybridStep {
+    type Err = ParseHybridStepError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use clap::ValueEnum as _;
+        let mut parts = s.trim().splitn(3, ':');
+        let name = parts.next().unwrap_or_default().trim();
+        let algorithm = Algorithm::from_str(name, true)
+            .ok()
+            .filter(|a| HYBRID_ALGORITHMS.contains(a))
+            .ok_or_else(|| {
+                ParseHybridStepError(format!(
+                    "unknown algorithm '{name}' (expected one of: mandelbrot, mandelbar, burning-ship, celtic, bird-of-prey)"
+                ))
+            })?;
+        let repeat = match parts.next() {
+            Some(r) => r
+                .trim()
+                .parse()
+                .ok()
+                .filter(|r| (1..=Self::MAX_REPEAT).contains(r))
+                .ok_or_else(|| {
+                    ParseHybridStepError(format!(
+                        "repeat count must be between 1 and {}",
+                        Self::MAX_REPEAT
+                    ))
+                })?,
+            None => 1,
+        };
+        let exponent = match parts.next() {
+            Some(e) => e
+                .parse()
+                .map_err(|e| ParseHybridStepError(format!("invalid exponent: {e}")))?,
+            None => PushExponent::default(),
+        };
+        Ok(Self::new(algorithm, repeat, exponent))
+    }
+}
+
+/// Error returned when parsing a [`HybridStep`] fails
+#[cfg(not(target_arch = "spirv"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseHybridStepError(String);
+
+#[cfg(not(target_arch = "spirv"))]
+impl std::fmt::Display for ParseHybridStepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(not(target_arch = "spirv"))]
+impl std::error::Error for ParseHybridStepError {}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::HybridStep;
+    use crate::{enums::Algorithm, PushExponent};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "burning-ship".parse(),
+            Ok(HybridStep::new(
+                Algorithm::BurningShip,
+                1,
+                PushExponent::default()
+            ))
+        );
+        assert_eq!(
+            "celtic:3".parse(),
+            Ok(HybridStep::new(
+                Algorithm::Celtic,
+                3,
+                PushExponent::default()
+            ))
+        );
+        assert_eq!(
+            "mandelbar:2:1.5,-1"
+                .parse::<HybridStep>()
+                .unwrap()
+                .exponent
+                .to_string(),
+            "1.5,-1.0"
+        );
+        assert!("newton".parse::<HybridStep>().is_err());
+        assert!("mandelbrot:0".parse::<HybridStep>().is_err());
+        assert!("mandelbrot:1:x".parse::<HybridStep>().is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for s in ["mandelbrot:1:2", "bird-of-prey:7:-2.5", "celtic:2:1.5,0.5"] {
+            let step: HybridStep = s.parse().unwrap();
+            assert_eq!(step.to_string(), s);
+        }
+    }
+}
@@ -4,13 +4,14 @@
 pub type Complex = abels_complex::Complex<f32>;
 
 #[cfg(not(target_arch = "spirv"))]
-use glam::{uvec2, vec2, UVec2, Vec2};
+use glam::{ivec2, uvec2, vec2, IVec2, UVec2, Vec2};
 
 #[cfg(target_arch = "spirv")]
-use spirv_std::glam::{uvec2, vec2, UVec2, Vec2};
+use spirv_std::glam::{ivec2, uvec2, vec2, IVec2, UVec2, Vec2};
 
-pub const GRID_SIZE: UVec2 = uvec2(3840, 2160);
 pub const INSPECTOR_MARKER_SIZE: f32 = 9.;
+/// Capacity of the reference orbit buffer used for perturbation rendering
+pub const REFERENCE_ORBIT_SIZE: u32 = FragmentConstants::MAX_ITER_LIMIT + 1;
 
 use bytemuck::{NoUninit, Pod, Zeroable};
 use const_default::ConstDefault;
@@ -18,11 +19,23 @@ use const_default::ConstDefault;
 use shader_util::Size;
 
 pub mod enums;
-use enums::{Algorithm, ColourStyle, Colourer};
+use enums::{
+    Algorithm, Antialias, Bailout, ColourStyle, Colourer, Interior, Interpolation, Mapping,
+    OrbitTrap,
+};
 
 use crate::enums::Modifier;
 pub mod data;
+pub mod formula;
+pub mod gradient;
+pub mod histogram;
+pub mod hybrid;
 
+/// Everything the shader needs to know about the view.
+///
+/// This is too large for the push constants (which only guarantee 128 bytes), so it travels
+/// in two parts: the [`PushConstants`], which may change in every frame, and the
+/// [`ViewParameters`], which change less often and go in a storage buffer.
 #[derive(Copy, Clone, Debug)]
 // We only derive NoUninit on non-spirv, because Vec2 is not marked as NoUninint on spirv builds.
 #[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
@@ -38,6 +51,49 @@ pub struct FragmentConstants {
     pub exponent: PushExponent,
     pub palette: Palette,
     pub inspector_point_pixel_address: Vec2,
+    /// The fixed parameter `c` of the Julia set (only used when `JULIA` is set)
+    pub julia_parameter: Vec2,
+    /// Number of valid entries in the reference orbit buffer (only used when `PERTURBED` is set)
+    pub reference_orbit_len: u32,
+    /// Roots of the polynomial (only used by [`Algorithm::NewtonRoots`])
+    pub newton_roots: [Vec2; 3],
+    /// Weight of the previous point of the orbit (only used by [`Algorithm::Phoenix`])
+    pub phoenix_parameter: Vec2,
+    /// Orbits escape when they leave the region of this radius (and of the shape `bailout`).
+    /// The transcendental and convergent algorithms have their own tests, and ignore both.
+    pub escape_radius: f32,
+    pub bailout: Bailout,
+    /// The orbit trap (only used for colouring, by [`Colourer::Trap`] and [`Modifier::Trap`])
+    pub trap: OrbitTrap,
+    /// Position of [`OrbitTrap::UserPoint`]
+    pub trap_point: Vec2,
+    /// Radius of [`OrbitTrap::Circle`]
+    pub trap_radius: f32,
+    /// Size of the cache grids (so `grid_size.x` is their stride), which the host chooses to
+    /// suit the window and the device: see [`data::grid_size_for`].
+    /// If the view is larger, the pixels beyond the grids are iterated every frame.
+    pub grid_size: UVec2,
+    /// Where the cache grids hold pixel (0,0) of the view.
+    /// The grids wrap around, so panning the view only has to move this.
+    pub grid_origin: UVec2,
+    /// How far the view has panned since the last frame, in whole pixels:
+    /// pixel `p` now shows the point which pixel `p + pan` did.
+    /// Only the pixels which were out of view then need iterating.
+    pub pan: IVec2,
+    /// Maps the view to the one the cache grids hold, for [`Flags::ZOOM_PREVIEW`]:
+    /// pixel co-ordinate `p` of the view is at `preview_offset + p * preview_scale` in the cached view.
+    pub preview_offset: Vec2,
+    pub preview_scale: f32,
+    /// The iterations this frame covers, for [`Flags::PROGRESSIVE`]: orbits which the last frame
+    /// left unfinished carry on from `slice_start`, and every orbit stops at `slice_end`.
+    pub slice_start: u32,
+    pub slice_end: u32,
+    /// How many iterations a supersample's orbit may be taken through in a frame, or 0 for no
+    /// limit. Orbits which need more carry on in the next frame; see [`data::Supersamples`].
+    pub sample_slice: u32,
+    /// Supersampling pattern. The samples of each pixel are added up over several frames,
+    /// in a grid of [`data::Supersamples`]; see also [`Flags::ADAPTIVE_ANTIALIAS`].
+    pub antialias: Antialias,
 }
 
 impl FragmentConstants {
@@ -45,7 +101,18 @@ impl FragmentConstants {
     /// Conversion factor applied to viewport_zoom whenever it's presented to a human
     pub const UI_ZOOM_FACTOR: f32 = 4.0;
     pub const DEFAULT_MAX_ITER: u32 = 250;
+    /// Upper limit on `max_iter`. This also sizes the reference orbit buffer.
+    pub const MAX_ITER_LIMIT: u32 = 100_000;
     pub const DEFAULT_SIZE: UVec2 = uvec2(800, 600);
+    pub const DEFAULT_PHOENIX_PARAMETER: Vec2 = vec2(-0.5, 0.0);
+    pub const DEFAULT_NEWTON_ROOTS: [Vec2; 3] =
+        [vec2(1.0, 0.0), vec2(-0.5, 0.5), vec2(-0.25, -1.0)];
+    pub const DEFAULT_ESCAPE_RADIUS: f32 = 10.0;
+    /// Limits on `escape_radius`. Small radii make banding; large radii make smooth gradients.
+    pub const MIN_ESCAPE_RADIUS: f32 = 1.5;
+    pub const MAX_ESCAPE_RADIUS: f32 = 1.0e6;
+    pub const DEFAULT_TRAP_POINT: Vec2 = vec2(-0.5, 0.5);
+    pub const DEFAULT_TRAP_RADIUS: f32 = 1.0;
 }
 
 impl Default for FragmentConstants {
@@ -60,6 +127,24 @@ impl Default for FragmentConstants {
             exponent: PushExponent::default(),
             palette: Palette::default(),
             inspector_point_pixel_address: Default::default(),
+            julia_parameter: vec2(0.0, 0.0),
+            reference_orbit_len: 0,
+            newton_roots: Self::DEFAULT_NEWTON_ROOTS,
+            phoenix_parameter: Self::DEFAULT_PHOENIX_PARAMETER,
+            escape_radius: Self::DEFAULT_ESCAPE_RADIUS,
+            bailout: Bailout::default(),
+            trap: OrbitTrap::default(),
+            trap_point: Self::DEFAULT_TRAP_POINT,
+            trap_radius: Self::DEFAULT_TRAP_RADIUS,
+            grid_size: data::DEFAULT_GRID_SIZE,
+            grid_origin: uvec2(0, 0),
+            pan: ivec2(0, 0),
+            preview_offset: vec2(0.0, 0.0),
+            preview_scale: 1.0,
+            slice_start: 0,
+            slice_end: 0,
+            sample_slice: 0,
+            antialias: Antialias::Off,
         }
     }
 }
@@ -70,6 +155,23 @@ bitflags::bitflags! {
 pub struct Flags : u32 {
     const NEEDS_REITERATE = 1 << 0;
     const INSPECTOR_ACTIVE = 1 << 1;
+    /// Render by perturbation against the reference orbit, instead of directly
+    const PERTURBED = 1 << 2;
+    /// Render the Julia set for `julia_parameter`: the pixel is the starting point of the orbit
+    const JULIA = 1 << 3;
+    /// Draw a marker at the user-placed orbit trap
+    const TRAP_MARKER = 1 << 4;
+    /// The view has zoomed since the cache was drawn: show what the cache has, resampled,
+    /// and only iterate the pixels it doesn't cover (without caching them)
+    const ZOOM_PREVIEW = 1 << 5;
+    /// Spread the iterations over several frames: orbits stop at `slice_end`, and those which
+    /// the last frame left unfinished carry on from where it saved them
+    const PROGRESSIVE = 1 << 6;
+    /// Only supersample the pixels on an edge, as the distance estimate or the iteration counts
+    /// of the neighbouring pixels show it
+    const ADAPTIVE_ANTIALIAS = 1 << 7;
+    /// The colouring has changed since the last frame: discard the supersamples taken so far
+    const RESAMPLE = 1 << 8;
 
     const _ = !0;
 }
@@ -96,6 +198,162 @@ impl FragmentConstants {
     pub fn pixel_spacing(&self) -> f32 {
         Self::pixel_spacing_f32(self.size.height, self.viewport_zoom)
     }
+
+    /// How far the orbits may be iterated in this frame: see [`Flags::PROGRESSIVE`]
+    pub fn iteration_limit(&self) -> u32 {
+        if self.flags.contains(Flags::PROGRESSIVE) {
+            self.slice_end.min(self.max_iter)
+        } else {
+            self.max_iter
+        }
+    }
+
+    /// Where the cache grids hold a pixel of the view
+    pub fn grid_cell(&self, pixel: UVec2) -> UVec2 {
+        (pixel + self.grid_origin) % self.grid_size
+    }
+
+    /// Do the cache grids have room for a pixel of the view?
+    pub fn in_grid(&self, pixel: UVec2) -> bool {
+        pixel.x < self.grid_size.x && pixel.y < self.grid_size.y
+    }
+
+    /// Where a pixel co-ordinate of the view falls in the cached view, for [`Flags::ZOOM_PREVIEW`]
+    pub fn preview_coord(&self, coord: Vec2) -> Vec2 {
+        self.preview_offset + coord * self.preview_scale
+    }
+
+    /// Must this pixel be iterated, or is it in the cache?
+    pub fn needs_iterating(&self, pixel: UVec2) -> bool {
+        let previous = pixel.as_ivec2() + self.pan;
+        // The part of the view which the cache grids hold
+        let cached = uvec2(self.size.width, self.size.height)
+            .min(self.grid_size)
+            .as_ivec2();
+        self.flags.contains(Flags::NEEDS_REITERATE)
+            || !self.in_grid(pixel)
+            || previous.x < 0
+            || previous.y < 0
+            || previous.x >= cached.x
+            || previous.y >= cached.y
+    }
+}
+
+/// The part of [`FragmentConstants`] which travels in the push constants.
+/// The fields are as there.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct PushConstants {
+    pub flags: Flags,
+    pub viewport_translate: Vec2,
+    pub viewport_zoom: f32,
+    pub size: Size,
+    pub algorithm: Algorithm,
+    pub max_iter: u32,
+    pub exponent: PushExponent,
+    pub inspector_point_pixel_address: Vec2,
+    pub reference_orbit_len: u32,
+    pub grid_size: UVec2,
+    pub grid_origin: UVec2,
+    pub pan: IVec2,
+    pub preview_offset: Vec2,
+    pub preview_scale: f32,
+    pub slice_start: u32,
+    pub slice_end: u32,
+    pub sample_slice: u32,
+    pub antialias: Antialias,
+}
+
+/// The part of [`FragmentConstants`] which travels in a storage buffer: the parameters of the
+/// fractal and its colouring. The fields are as there.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct ViewParameters {
+    pub palette: Palette,
+    pub julia_parameter: Vec2,
+    pub phoenix_parameter: Vec2,
+    pub newton_roots: [Vec2; 3],
+    pub trap_point: Vec2,
+    pub escape_radius: f32,
+    pub bailout: Bailout,
+    pub trap: OrbitTrap,
+    pub trap_radius: f32,
+}
+
+impl FragmentConstants {
+    /// The part which travels in the push constants
+    pub fn push(&self) -> PushConstants {
+        PushConstants {
+            flags: self.flags,
+            viewport_translate: self.viewport_translate,
+            viewport_zoom: self.viewport_zoom,
+            size: self.size,
+            algorithm: self.algorithm,
+            max_iter: self.max_iter,
+            exponent: self.exponent,
+            inspector_point_pixel_address: self.inspector_point_pixel_address,
+            reference_orbit_len: self.reference_orbit_len,
+            grid_size: self.grid_size,
+            grid_origin: self.grid_origin,
+            pan: self.pan,
+            preview_offset: self.preview_offset,
+            preview_scale: self.preview_scale,
+            slice_start: self.slice_start,
+            slice_end: self.slice_end,
+            sample_slice: self.sample_slice,
+            antialias: self.antialias,
+        }
+    }
+
+    /// The part which travels in a storage buffer
+    pub fn view(&self) -> ViewParameters {
+        ViewParameters {
+            palette: self.palette,
+            julia_parameter: self.julia_parameter,
+            phoenix_parameter: self.phoenix_parameter,
+            newton_roots: self.newton_roots,
+            trap_point: self.trap_point,
+            escape_radius: self.escape_radius,
+            bailout: self.bailout,
+            trap: self.trap,
+            trap_radius: self.trap_radius,
+        }
+    }
+
+    /// Puts the parts back together
+    pub fn join(push: &PushConstants, view: &ViewParameters) -> Self {
+        Self {
+            flags: push.flags,
+            viewport_translate: push.viewport_translate,
+            viewport_zoom: push.viewport_zoom,
+            size: push.size,
+            algorithm: push.algorithm,
+            max_iter: push.max_iter,
+            exponent: push.exponent,
+            palette: view.palette,
+            inspector_point_pixel_address: push.inspector_point_pixel_address,
+            julia_parameter: view.julia_parameter,
+            reference_orbit_len: push.reference_orbit_len,
+            newton_roots: view.newton_roots,
+            phoenix_parameter: view.phoenix_parameter,
+            escape_radius: view.escape_radius,
+            bailout: view.bailout,
+            trap: view.trap,
+            trap_point: view.trap_point,
+            trap_radius: view.trap_radius,
+            grid_size: push.grid_size,
+            grid_origin: push.grid_origin,
+            pan: push.pan,
+            preview_offset: push.preview_offset,
+            preview_scale: push.preview_scale,
+            slice_start: push.slice_start,
+            slice_end: push.slice_end,
+            sample_slice: push.sample_slice,
+            antialias: push.antialias,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, NoUninit)]
@@ -105,11 +363,27 @@ pub struct Palette {
     pub colour_style: ColourStyle,
     pub brightness_style: Modifier,
     pub saturation_style: Modifier,
+    /// Colouring of points inside the set, independent of the colourer
+    pub interior: Interior,
+    /// How the [`Colourer::Gradient`] blends between its stops
+    pub interpolation: Interpolation,
+    /// How the colourers which run through their palette once (monochrome, the LCH gradient and
+    /// the colour maps) scale the iteration counts to it: relative to `max_iter`, from the lowest
+    /// to the highest count in view, or spread evenly over the points in view
+    pub mapping: Mapping,
     pub gradient: f32,
     pub offset: f32,
     pub saturation: f32,
     pub lightness: f32,
     pub gamma: f32,
+    /// Direction the light comes from, for [`Modifier::Lighting`] (degrees anticlockwise from the real axis)
+    pub light_azimuth: f32,
+    /// Height of the light above the horizon, for [`Modifier::Lighting`] (degrees)
+    pub light_elevation: f32,
+    /// Proportion of the light which reaches every point, for [`Modifier::Lighting`]
+    pub ambient: f32,
+    /// Strength of the highlights, for [`Modifier::Lighting`]
+    pub specular: f32,
 }
 impl ConstDefault for Palette {
     const DEFAULT: Self = Self {
@@ -117,12 +391,19 @@ impl ConstDefault for Palette {
         colour_style: ColourStyle::DEFAULT,
         brightness_style: Modifier::DEFAULT,
         saturation_style: Modifier::DEFAULT,
+        interior: Interior::DEFAULT,
+        interpolation: Interpolation::DEFAULT,
+        mapping: Mapping::DEFAULT,
         // N.B. Each colourer is at liberty to scale gradient & offset as may be reasonable.
         gradient: 1.,
         offset: 0.,
         saturation: 100., // Not available on all palette algorithms
         lightness: 50.,   // Not available on all palette algorithms
         gamma: 1.9,
+        light_azimuth: 45.,
+        light_elevation: 45.,
+        ambient: 0.2,
+        specular: 0.5,
     };
 }
 impl Default for Palette {
@@ -144,28 +425,99 @@ impl Palette {
         self.brightness_style = style;
         self
     }
+    /// Does the palette colour by the orbit trap?
+    pub fn uses_trap(&self) -> bool {
+        self.colourer == Colourer::Trap
+            || self.brightness_style == Modifier::Trap
+            || self.saturation_style == Modifier::Trap
+    }
+    /// Does the palette colour the inside of the set?
+    pub fn uses_interior(&self) -> bool {
+        self.interior != Interior::Black
+    }
+    /// Does the colourer follow the [`mapping`](Self::mapping)?
+    pub fn uses_mapping(&self) -> bool {
+        matches!(
+            self.colourer,
+            Colourer::Monochrome
+                | Colourer::LchGradient
+                | Colourer::Viridis
+                | Colourer::Magma
+                | Colourer::Cividis
+        )
+    }
+    /// Does the palette scale the iteration counts to those in view?
+    /// If so, the colourer needs the [`IterationStats`](histogram::IterationStats) of the view.
+    pub fn adapts_to_view(&self) -> bool {
+        self.mapping != Mapping::Fixed && self.uses_mapping()
+    }
+    /// Does the palette use any of the averaging colouring statistics?
+    pub fn uses_averages(&self) -> bool {
+        self.brightness_style.is_average() || self.saturation_style.is_average()
+    }
     pub const MINIMA: Palette = Palette {
         colourer: Colourer::DEFAULT,
         colour_style: ColourStyle::DEFAULT,
         brightness_style: Modifier::DEFAULT,
         saturation_style: Modifier::DEFAULT,
+        interior: Interior::DEFAULT,
+        interpolation: Interpolation::DEFAULT,
+        mapping: Mapping::DEFAULT,
         gradient: 0.1,
         offset: -10.0,
         saturation: 0.,
         lightness: 0.,
         gamma: 0.,
+        light_azimuth: 0.,
+        light_elevation: 0.,
+        ambient: 0.,
+        specular: 0.,
     };
     pub const MAXIMA: Palette = Palette {
         colourer: Colourer::DEFAULT,
         colour_style: ColourStyle::DEFAULT,
         brightness_style: Modifier::DEFAULT,
         saturation_style: Modifier::DEFAULT,
+        interior: Interior::DEFAULT,
+        interpolation: Interpolation::DEFAULT,
+        mapping: Mapping::DEFAULT,
         gradient: 10.,
         offset: 10.,
         saturation: 100.,
         lightness: 100.,
         gamma: 4.0,
+        light_azimuth: 360.,
+        light_elevation: 90.,
+        ambient: 1.,
+        specular: 1.,
     };
+
+    /// Checks that every numeric field lies between [`Self::MINIMA`] and [`Self::MAXIMA`]
+    #[cfg(not(target_arch = "spirv"))]
+    pub fn check_ranges(&self) -> Result<(), String> {
+        macro_rules! check_range {
+            ($($id:ident), *) => {
+                $(
+                    let (min, max) = (Self::MINIMA.$id, Self::MAXIMA.$id);
+                    if !(min..=max).contains(&self.$id) {
+                        return Err(format!("{} must be between {min} and {max}", stringify!($id)));
+                    }
+                )*
+            };
+        }
+        check_range!(
+            gradient,
+            offset,
+            saturation,
+            lightness,
+            gamma,
+            light_azimuth,
+            light_elevation,
+            ambient,
+            specular
+        );
+        Ok(())
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, NoUninit)]
@@ -250,3 +602,126 @@ impl From<&PushExponent> for Complex {
         }
     }
 }
+
+/// Formats an exponent in the syntax accepted by its [`FromStr`](core::str::FromStr) implementation
+#[cfg(not(target_arch = "spirv"))]
+impl std::fmt::Display for PushExponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Debug formatting of floats always includes a decimal point, and round-trips exactly.
+        match self.typ {
+            NumericType::Integer => write!(f, "{}", self.int),
+            NumericType::Float => write!(f, "{:?}", self.real),
+            NumericType::Complex => write!(f, "{:?},{:?}", self.real, self.imag),
+        }
+    }
+}
+
+/// Parses an exponent: an integer, a real number, or a complex number written as `RE,IM`
+#[cfg(not(target_arch = "spirv"))]
+impl std::str::FromStr for PushExponent {
+    type Err = ParseExponentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(i) = s.parse::<i32>() {
+            return Ok(Self::from(i));
+        }
+        if let Ok(f) = s.parse::<f32>() {
+            return Ok(Self::from(f));
+        }
+        let (re, im) = s.split_once(',').ok_or(ParseExponentError)?;
+        Ok(Self {
+            typ: NumericType::Complex,
+            real: re.trim().parse().map_err(|_| ParseExponentError)?,
+            imag: im.trim().parse().map_err(|_| ParseExponentError)?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Error returned when parsing a [`PushExponent`] fails
+#[cfg(not(target_arch = "spirv"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseExponentError;
+
+#[cfg(not(target_arch = "spirv"))]
+impl std::fmt::Display for ParseExponentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("expected an integer, a real number, or a complex number written as RE,IM")
+    }
+}
+
+#[cfg(not(target_arch = "spirv"))]
+impl std::error::Error for ParseExponentError {}
+
+#[cfg(all(test, not(target_arch = "spirv")))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{enums::Bailout, Flags, FragmentConstants, NumericType, Palette, PushExponent};
+    use glam::{ivec2, vec2};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn constants_parts() {
+        // Every field travels in one part or the other
+        let constants = FragmentConstants {
+            flags: Flags::JULIA | Flags::PROGRESSIVE,
+            viewport_translate: vec2(-0.75, 0.1),
+            max_iter: 1234,
+            palette: Palette {
+                gamma: 2.,
+                ..Palette::default()
+            },
+            julia_parameter: vec2(0.3, -0.2),
+            newton_roots: [vec2(1., 2.), vec2(3., 4.), vec2(5., 6.)],
+            bailout: Bailout::Manhattan,
+            trap_radius: 0.5,
+            pan: ivec2(-3, 2),
+            sample_slice: 1000,
+            ..FragmentConstants::default()
+        };
+        let joined = FragmentConstants::join(&constants.push(), &constants.view());
+        assert_eq!(bytemuck::bytes_of(&joined), bytemuck::bytes_of(&constants));
+    }
+
+    #[test]
+    fn palette_ranges() {
+        assert_eq!(Palette::default().check_ranges(), Ok(()));
+        assert_eq!(Palette::MINIMA.check_ranges(), Ok(()));
+        assert_eq!(Palette::MAXIMA.check_ranges(), Ok(()));
+        let p = Palette {
+            gamma: 5.,
+            ..Palette::default()
+        };
+        assert_eq!(
+            p.check_ranges(),
+            Err("gamma must be between 0 and 4".to_string())
+        );
+        let p = Palette {
+            offset: f32::NAN,
+            ..Palette::default()
+        };
+        assert!(p.check_ranges().is_err());
+    }
+
+    #[test]
+    fn parse_exponent() {
+        assert_eq!("3".parse(), Ok(PushExponent::from(3)));
+        assert_eq!("-2".parse(), Ok(PushExponent::from(-2)));
+        assert_eq!("2.5".parse(), Ok(PushExponent::from(2.5)));
+        let c: PushExponent = "2, -0.5".parse().unwrap();
+        assert_eq!(c.typ, NumericType::Complex);
+        assert_eq!((c.real, c.imag), (2.0, -0.5));
+        assert!("two".parse::<PushExponent>().is_err());
+        assert!("1,i".parse::<PushExponent>().is_err());
+    }
+
+    #[test]
+    fn exponent_round_trip() {
+        for s in ["2", "-3", "2.0", "0.1", "-1.5,0.3333333"] {
+            let e: PushExponent = s.parse().unwrap();
+            assert_eq!(e.to_string(), s);
+            assert_eq!(e.to_string().parse(), Ok(e));
+        }
+    }
+}
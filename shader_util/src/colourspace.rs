@@ -34,6 +34,21 @@ impl Clamp01 for f32 {
     }
 }
 
+/// Converts a colour from sRGB, as found in colour files and on the web, to linear RGB.
+///
+/// The shaders output linear RGB; the display applies the sRGB transfer function.
+#[must_use]
+pub fn srgb_to_linear(value: Vec3Rgb) -> Vec3Rgb {
+    let decode = |c: f32| {
+        if c <= 0.040_45 {
+            c * (1. / 12.92)
+        } else {
+            ((c + 0.055) * (1. / 1.055)).powf(2.4)
+        }
+    };
+    Vec3Rgb::new(decode(value.x), decode(value.y), decode(value.z))
+}
+
 /// RGB colour space representation
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Rgb {
@@ -227,6 +242,14 @@ impl From<Lch> for Lab {
     }
 }
 
+impl From<Lab> for Lch {
+    fn from(value: Lab) -> Self {
+        let h = value.b.atan2(value.a).to_degrees();
+        let c = (value.a * value.a + value.b * value.b).sqrt();
+        Self { l: value.l, c, h }
+    }
+}
+
 // Matrix from <https://docs.rs/color/0.3.2/src/color/colorspace.rs.html>: original source is CSS Color 4.
 const LAB_XYZ_TO_SRGB: [[f32; 3]; 3] = [
     [3.022_233_7, -1.617_386, -0.404_847_65],
@@ -266,6 +289,41 @@ impl From<Lab> for Vec3Rgb {
     }
 }
 
+// The inverse of `LAB_XYZ_TO_SRGB`
+const SRGB_TO_LAB_XYZ: [[f32; 3]; 3] = [
+    [0.452_211_65, 0.399_412_24, 0.148_376_08],
+    [0.222_493_17, 0.716_887, 0.060_619_81],
+    [0.016_875_342, 0.117_659_41, 0.865_465_2],
+];
+
+impl From<Vec3Rgb> for Lab {
+    fn from(value: Vec3Rgb) -> Self {
+        // The inverse of the conversion from Lab
+        let [x, y, z] = matvecmul(&SRGB_TO_LAB_XYZ, value.into());
+        let cbrt = |value: f32| {
+            const EPSILON: f32 = 216. / 24389.;
+            if value > EPSILON {
+                value.powf(1. / 3.)
+            } else {
+                (KAPPA * value + 16.) * (1. / 116.)
+            }
+        };
+        let [f0, f1, f2] = [cbrt(x), cbrt(y), cbrt(z)];
+        Self {
+            l: 116. * f1 - 16.,
+            a: 500. * (f0 - f1),
+            b: 200. * (f1 - f2),
+        }
+    }
+}
+
+impl From<Vec3Rgb> for Lch {
+    fn from(value: Vec3Rgb) -> Self {
+        let lab: Lab = value.into();
+        lab.into()
+    }
+}
+
 impl From<Lch> for Vec3Rgb {
     fn from(value: Lch) -> Self {
         let lab: Lab = value.into();
@@ -282,10 +340,134 @@ impl From<Lch> for Hsl {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+/// Oklab colour space, by Björn Ottosson <https://bottosson.github.io/posts/oklab/>.
+///
+/// Like CIE L*a*b*, but more nearly perceptually uniform; in particular, hue holds
+/// steady as lightness and chroma change.
+pub struct Oklab {
+    /// Lightness (range 0..1)
+    pub l: f32,
+    /// Green-red axis (roughly -0.4..0.4)
+    pub a: f32,
+    /// Blue-yellow axis (roughly -0.4..0.4)
+    pub b: f32,
+}
+
+impl Oklab {
+    #[must_use]
+    #[allow(missing_docs)]
+    pub fn new(l: f32, a: f32, b: f32) -> Self {
+        Self { l, a, b }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Oklch colour space: the polar form of [`Oklab`]
+pub struct Oklch {
+    /// Lightness (range 0..1)
+    pub l: f32,
+    /// Chroma (range 0..0.4 or so)
+    pub c: f32,
+    /// Hue (degrees)
+    pub h: f32,
+}
+
+impl Oklch {
+    #[must_use]
+    #[allow(missing_docs)]
+    pub fn new(l: f32, c: f32, h: f32) -> Self {
+        Self { l, c, h }
+    }
+}
+
+impl From<Oklch> for Oklab {
+    fn from(value: Oklch) -> Self {
+        let (sin, cos) = value.h.to_radians().sin_cos();
+        Self {
+            l: value.l,
+            a: value.c * cos,
+            b: value.c * sin,
+        }
+    }
+}
+
+impl From<Oklab> for Oklch {
+    fn from(value: Oklab) -> Self {
+        let h = value.b.atan2(value.a).to_degrees();
+        let c = (value.a * value.a + value.b * value.b).sqrt();
+        Self { l: value.l, c, h }
+    }
+}
+
+// Matrices from <https://bottosson.github.io/posts/oklab/>
+const OKLAB_SRGB_TO_LMS: [[f32; 3]; 3] = [
+    [0.412_221_47, 0.536_332_54, 0.051_445_993],
+    [0.211_903_5, 0.680_699_5, 0.107_396_96],
+    [0.088_302_46, 0.281_718_84, 0.629_978_7],
+];
+const OKLAB_LMS_TO_LAB: [[f32; 3]; 3] = [
+    [0.210_454_26, 0.793_617_8, -0.004_072_047],
+    [1.977_998_5, -2.428_592_2, 0.450_593_7],
+    [0.025_904_037, 0.782_771_77, -0.808_675_77],
+];
+const OKLAB_LAB_TO_LMS: [[f32; 3]; 3] = [
+    [1., 0.396_337_78, 0.215_803_76],
+    [1., -0.105_561_35, -0.063_854_17],
+    [1., -0.089_484_18, -1.291_485_5],
+];
+const OKLAB_LMS_TO_SRGB: [[f32; 3]; 3] = [
+    [4.076_741_7, -3.307_711_6, 0.230_969_93],
+    [-1.268_438, 2.609_757_4, -0.341_319_4],
+    [-0.004_196_086_3, -0.703_418_6, 1.707_614_7],
+];
+
+impl From<Vec3Rgb> for Oklab {
+    fn from(value: Vec3Rgb) -> Self {
+        let [long, medium, short] = matvecmul(&OKLAB_SRGB_TO_LMS, value.into());
+        // cbrt() isn't available on the GPU; out-of-gamut colours can be negative here
+        let cbrt = |v: f32| v.signum() * v.abs().powf(1. / 3.);
+        let [l, a, b] = matvecmul(&OKLAB_LMS_TO_LAB, [cbrt(long), cbrt(medium), cbrt(short)]);
+        Self { l, a, b }
+    }
+}
+
+impl From<Oklab> for Vec3Rgb {
+    fn from(value: Oklab) -> Self {
+        let cube = |v: f32| v * v * v;
+        let [long, medium, short] = matvecmul(&OKLAB_LAB_TO_LMS, [value.l, value.a, value.b]);
+        matvecmul(&OKLAB_LMS_TO_SRGB, [cube(long), cube(medium), cube(short)]).into()
+    }
+}
+
+impl From<Vec3Rgb> for Oklch {
+    fn from(value: Vec3Rgb) -> Self {
+        let lab: Oklab = value.into();
+        lab.into()
+    }
+}
+
+impl From<Oklch> for Vec3Rgb {
+    fn from(value: Oklch) -> Self {
+        let lab: Oklab = value.into();
+        let unclamped: Vec3Rgb = lab.into();
+        unclamped.clamp01()
+    }
+}
+
+impl From<Oklch> for Hsl {
+    fn from(value: Oklch) -> Self {
+        let vrgb: Vec3Rgb = value.into();
+        let rgb: Rgb = vrgb.into();
+        rgb.into()
+    }
+}
+
 #[cfg(test)]
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
-    use super::{Hsl, Rgb};
+    use super::{srgb_to_linear, Hsl, Lch, Oklab, Oklch, Rgb, Vec3Rgb};
+    use float_eq::assert_float_eq;
 
     fn hsl_rgb_case(hsl: Hsl) {
         let rgb: Rgb = hsl.into();
@@ -307,4 +489,75 @@ mod tests {
         hsl_rgb_case(Hsl::new(0., 0., 50.));
         hsl_rgb_case(Hsl::new(0., 0., 100.));
     }
+
+    #[test]
+    fn rgb_lch_round_trip() {
+        for rgb in [
+            Vec3Rgb::new(0., 0., 0.),
+            Vec3Rgb::new(1., 1., 1.),
+            Vec3Rgb::new(1., 0., 0.),
+            Vec3Rgb::new(0.2, 0.6, 0.4),
+            Vec3Rgb::new(0.01, 0.02, 0.03),
+        ] {
+            let lch: Lch = rgb.into();
+            let rgb2: Vec3Rgb = lch.into();
+            assert_float_eq!(rgb.to_array(), rgb2.to_array(), abs_all <= 0.000_1);
+        }
+        assert_float_eq!(
+            super::srgb_to_linear(Vec3Rgb::new(0., 0.5, 1.)).to_array(),
+            [0., 0.214_041, 1.],
+            abs_all <= 0.000_001
+        );
+        let white: Lch = Vec3Rgb::ONE.into();
+        assert_float_eq!(white.l, 100., abs <= 0.01);
+        assert_float_eq!(white.c, 0., abs <= 0.01);
+    }
+
+    #[test]
+    fn oklab_known_answers() {
+        // Reference values from <https://bottosson.github.io/posts/oklab/> and CSS Color 4
+        for (rgb, expected) in [
+            (Vec3Rgb::new(1., 1., 1.), [1., 0., 0.]),
+            (Vec3Rgb::new(1., 0., 0.), [0.627_955, 0.224_863, 0.125_846]),
+            (Vec3Rgb::new(0., 1., 0.), [0.866_440, -0.233_888, 0.179_498]),
+            (
+                Vec3Rgb::new(0., 0., 1.),
+                [0.452_014, -0.032_457, -0.311_528],
+            ),
+        ] {
+            let lab: Oklab = rgb.into();
+            assert_float_eq!([lab.l, lab.a, lab.b], expected, abs_all <= 0.000_1);
+        }
+        // Colours off the primaries, which only come out right through the sRGB transfer curve:
+        // mid grey (#808080), and rebeccapurple (#663399) from the examples in CSS Color 4
+        for (srgb, expected) in [
+            ([0x80, 0x80, 0x80], [0.599_871, 0., 0.]),
+            ([0x66, 0x33, 0x99], [0.440_27, 0.088_18, -0.133_86]),
+        ] {
+            let rgb = srgb_to_linear(Vec3Rgb::from(srgb.map(|c: u8| f32::from(c) / 255.)));
+            let lab: Oklab = rgb.into();
+            assert_float_eq!([lab.l, lab.a, lab.b], expected, abs_all <= 0.000_1);
+        }
+        let red: Oklch = Vec3Rgb::new(1., 0., 0.).into();
+        assert_float_eq!(red.c, 0.257_683, abs <= 0.000_1);
+        assert_float_eq!(red.h, 29.233_885, abs <= 0.01);
+    }
+
+    #[test]
+    fn rgb_oklch_round_trip() {
+        for rgb in [
+            Vec3Rgb::new(0., 0., 0.),
+            Vec3Rgb::new(1., 1., 1.),
+            Vec3Rgb::new(1., 0., 0.),
+            Vec3Rgb::new(0.2, 0.6, 0.4),
+            Vec3Rgb::new(0.01, 0.02, 0.03),
+        ] {
+            let lch: Oklch = rgb.into();
+            let rgb2: Vec3Rgb = lch.into();
+            assert_float_eq!(rgb.to_array(), rgb2.to_array(), abs_all <= 0.000_1);
+        }
+        // Out of gamut colours are clamped
+        let vivid: Vec3Rgb = Oklch::new(0.7, 0.4, 150.).into();
+        assert!(vivid.cmpge(Vec3Rgb::ZERO).all() && vivid.cmple(Vec3Rgb::ONE).all());
+    }
 }
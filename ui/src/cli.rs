@@ -1,12 +1,21 @@
 //! Command line argument definitions
 // (c) 2025 Ross Younger
 
-#[cfg(we_compile)]
+#[cfg(any(we_compile, not(wasm)))]
 use std::path::PathBuf;
 
-use shader_common::enums::{Algorithm, Colourer};
+use shader_common::{
+    enums::{
+        Algorithm, Bailout, ColourStyle, Colourer, Interior, Interpolation, Mapping, Modifier,
+        OrbitTrap,
+    },
+    FragmentConstants, Palette, PushExponent,
+};
 
-#[derive(Debug, clap::Parser, Clone, Default)]
+use crate::controller::Location;
+
+#[derive(Debug, clap::Parser, Clone)]
+#[command(group(clap::ArgGroup::new("view").multiple(true)))]
 pub(crate) struct Args {
     #[arg(short = 'V', long, help = "Print version")]
     pub version: bool,
@@ -40,23 +49,203 @@ pub(crate) struct Args {
     #[arg(long)]
     pub no_ui: bool,
 
+    /// Starts up at a location previously saved from the main menu.
+    ///
+    /// This cannot be combined with the options which set up the view.
+    #[cfg(not(wasm))]
+    #[arg(long, value_name = "FILE", conflicts_with = "view")]
+    pub load: Option<PathBuf>,
+
+    /// Real part of the initial centre of the view.
+    ///
+    /// This may be given to any number of decimal places.
+    #[arg(long, value_name = "X", default_value = "-1", allow_hyphen_values = true,
+        value_parser = parse_coordinate, group = "view")]
+    pub re: String,
+
+    /// Imaginary part of the initial centre of the view.
+    ///
+    /// This may be given to any number of decimal places.
+    #[arg(long, value_name = "Y", default_value = "0", allow_hyphen_values = true,
+        value_parser = parse_coordinate, group = "view")]
+    pub im: String,
+
+    /// Initial zoom level (as shown in the UI)
+    #[arg(short, long, default_value_t = f64::from(FragmentConstants::DEFAULT_ZOOM * FragmentConstants::UI_ZOOM_FACTOR),
+        group = "view")]
+    pub zoom: f64,
+
+    /// Initial maximum number of iterations per point
+    #[arg(short, long, default_value_t = FragmentConstants::DEFAULT_MAX_ITER,
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(FragmentConstants::MAX_ITER_LIMIT)),
+        group = "view")]
+    pub max_iter: u32,
+
+    /// Initial exponent: an integer, a real number, or a complex number written as `RE,IM`
+    #[arg(
+        short,
+        long,
+        default_value = "2",
+        allow_hyphen_values = true,
+        group = "view"
+    )]
+    pub exponent: PushExponent,
+
     /// Selects the initial fractal algorithm to use
     #[arg(
         short = 'F',
         long,
         alias = "fractal",
         value_name = "NAME",
-        default_value = "mandelbrot"
+        default_value = "mandelbrot",
+        group = "view"
     )]
     pub fractal: Algorithm,
 
+    /// Initial escape radius (larger radii give smoother colouring)
+    #[arg(long, value_name = "R", default_value_t = FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+        group = "view")]
+    pub escape_radius: f32,
+
+    /// Selects the initial shape of the escape region
+    #[arg(long, value_name = "SHAPE", default_value = "circle", group = "view")]
+    pub bailout: Bailout,
+
+    /// Selects the initial orbit trap, for the `trap` colourer and modifier
+    #[arg(long, value_name = "SHAPE", default_value = "point", group = "view")]
+    pub trap: OrbitTrap,
+
     /// Selects the initial colouring algorithm to use
     #[arg(
         short = 'C',
         long,
         alias = "colorer",
         value_name = "NAME",
-        default_value = "log-rainbow"
+        default_value = "log-rainbow",
+        group = "view"
     )]
     pub colourer: Colourer,
+
+    /// Loads the stops of the `gradient` colourer from a palette file:
+    /// a Fractint map (`.map`), a GIMP gradient (`.ggr`), or a list of CSS hex colours
+    #[cfg(not(wasm))]
+    #[arg(long, value_name = "FILE", group = "view")]
+    pub gradient_file: Option<PathBuf>,
+
+    /// Selects the colour space in which the `gradient` colourer blends between its stops
+    #[arg(long, value_name = "SPACE", default_value = "rgb", group = "view")]
+    pub interpolation: Interpolation,
+
+    /// How the colourers which run through their palette once (`monochrome`, `lch-gradient` and the
+    /// colour maps) scale the iteration counts to it: relative to the maximum iteration count,
+    /// from the lowest to the highest count in view, or spread evenly over the view
+    #[arg(long, value_name = "MAPPING", default_value = "fixed", group = "view")]
+    pub mapping: Mapping,
+
+    /// Selects the initial colour style
+    #[arg(
+        long,
+        value_name = "STYLE",
+        default_value = "continuous",
+        group = "view"
+    )]
+    pub colour_style: ColourStyle,
+
+    /// Selects the initial brightness modifier
+    #[arg(long, value_name = "STYLE", default_value = "standard", group = "view")]
+    pub brightness_style: Modifier,
+
+    /// Selects the initial saturation modifier
+    #[arg(long, value_name = "STYLE", default_value = "standard", group = "view")]
+    pub saturation_style: Modifier,
+
+    /// Selects the initial colouring of the inside of the set
+    #[arg(long, value_name = "STYLE", default_value = "black", group = "view")]
+    pub interior: Interior,
+
+    /// Palette gradient
+    #[arg(long, default_value_t = Palette::default().gradient, group = "view")]
+    pub gradient: f32,
+
+    /// Palette offset
+    #[arg(long, default_value_t = Palette::default().offset, allow_negative_numbers = true, group = "view")]
+    pub offset: f32,
+
+    /// Palette saturation (not used by all colourers)
+    #[arg(long, default_value_t = Palette::default().saturation, group = "view")]
+    pub saturation: f32,
+
+    /// Palette lightness (not used by all colourers)
+    #[arg(long, default_value_t = Palette::default().lightness, group = "view")]
+    pub lightness: f32,
+
+    /// Palette gamma (not used by all colourers)
+    #[arg(long, default_value_t = Palette::default().gamma, group = "view")]
+    pub gamma: f32,
+
+    /// Direction the light comes from, in degrees anticlockwise (for the `lighting` modifier)
+    #[arg(long, value_name = "DEGREES", default_value_t = Palette::default().light_azimuth, group = "view")]
+    pub light_azimuth: f32,
+
+    /// Height of the light above the horizon, in degrees (for the `lighting` modifier)
+    #[arg(long, value_name = "DEGREES", default_value_t = Palette::default().light_elevation, group = "view")]
+    pub light_elevation: f32,
+
+    /// Proportion of the light which reaches every point, 0 to 1 (for the `lighting` modifier)
+    #[arg(long, default_value_t = Palette::default().ambient, group = "view")]
+    pub ambient: f32,
+
+    /// Strength of the highlights, 0 to 1 (for the `lighting` modifier)
+    #[arg(long, default_value_t = Palette::default().specular, group = "view")]
+    pub specular: f32,
+}
+
+impl Args {
+    /// The initial view, as set up by the options
+    pub(crate) fn location(&self) -> Location {
+        Location {
+            re: self.re.clone(),
+            im: self.im.clone(),
+            zoom: self.zoom,
+            algorithm: self.fractal,
+            exponent: self.exponent,
+            max_iter: self.max_iter,
+            julia: None,
+            newton_roots: None,
+            phoenix: None,
+            formula: None,
+            hybrid: None,
+            gradient_stops: None,
+            escape_radius: self.escape_radius,
+            bailout: self.bailout,
+            trap: self.trap,
+            trap_point: None,
+            trap_radius: None,
+            palette: Palette {
+                colourer: self.colourer,
+                colour_style: self.colour_style,
+                brightness_style: self.brightness_style,
+                saturation_style: self.saturation_style,
+                interior: self.interior,
+                interpolation: self.interpolation,
+                mapping: self.mapping,
+                gradient: self.gradient,
+                offset: self.offset,
+                saturation: self.saturation,
+                lightness: self.lightness,
+                gamma: self.gamma,
+                light_azimuth: self.light_azimuth,
+                light_elevation: self.light_elevation,
+                ambient: self.ambient,
+                specular: self.specular,
+            },
+        }
+    }
+}
+
+/// Checks that a co-ordinate is a valid decimal number
+fn parse_coordinate(s: &str) -> Result<String, String> {
+    util::parse_decimal(s, 0)
+        .map(|_| s.trim().to_string())
+        .map_err(|e| format!("expected a decimal number ({e})"))
 }
@@ -0,0 +1,32 @@
+//! Anti-aliasing by supersampling, which the shader spreads over several frames
+// (c) 2025 Ross Younger
+
+use shader_common::{enums::Antialias, Palette};
+
+/// Anti-aliasing settings
+#[derive(Default)]
+pub(super) struct Antialiasing {
+    pub(super) pattern: Antialias,
+    /// Only supersample the pixels on an edge: see [`Flags::ADAPTIVE_ANTIALIAS`](shader_common::Flags::ADAPTIVE_ANTIALIAS)
+    pub(super) adaptive: bool,
+    /// What the last frame coloured the supersamples with
+    last: Option<(Palette, Antialias, bool)>,
+}
+
+impl Antialiasing {
+    /// Must the shader take the supersamples again ([`Flags::RESAMPLE`](shader_common::Flags::RESAMPLE))?
+    ///
+    /// The shader keeps the colours of the samples, so they must be taken again if the palette or
+    /// the pattern has changed since the last frame, or if `lookups_changed` (the gradient stops or
+    /// the statistics of the view have).
+    pub(super) fn resample(&mut self, palette: &Palette, lookups_changed: bool) -> bool {
+        let changed = self.last.is_none_or(|(last_palette, pattern, adaptive)| {
+            // Palette isn't PartialEq, but any change to it changes its bytes
+            bytemuck::bytes_of(&last_palette) != bytemuck::bytes_of(palette)
+                || pattern != self.pattern
+                || adaptive != self.adaptive
+        });
+        self.last = Some((*palette, self.pattern, self.adaptive));
+        lookups_changed || changed
+    }
+}
@@ -1,13 +1,202 @@
 //! Controls window
 // (c) 2025 Ross Younger
 
-use shader_common::enums::{Algorithm, ColourStyle, Colourer, Modifier};
+use shader_common::{
+    enums::{
+        Algorithm, Antialias, Bailout, ColourStyle, Colourer, Interior, Interpolation, Mapping,
+        Modifier, OrbitTrap,
+    },
+    gradient::{GradientStop, GRADIENT_SIZE},
+    hybrid::{HybridStep, HYBRID_ALGORITHMS, HYBRID_SIZE},
+    Complex, FragmentConstants, NumericType, PushExponent,
+};
 
 use easy_shader_runner::egui;
 
 impl super::Controller {
     pub(crate) const DEFAULT_WIDTH: f32 = 130.;
 
+    /// Editor for the hybrid sequence. Returns true if it changed.
+    fn hybrid_controls(&mut self, ui: &mut egui::Ui) -> bool {
+        let steps = &mut self.hybrid.steps;
+        let mut changed = false;
+        let mut remove = None;
+        let steps_len = steps.len();
+        egui::Grid::new("hybrid_grid").show(ui, |ui| {
+            ui.label("Step");
+            ui.label("Repeat");
+            ui.label("Exponent");
+            ui.end_row();
+            for (i, step) in steps.iter_mut().enumerate() {
+                egui::ComboBox::from_id_salt(("hybrid_step", i))
+                    .selected_text(format!("{:?}", step.algorithm))
+                    .show_ui(ui, |ui| {
+                        for it in HYBRID_ALGORITHMS {
+                            let label: &'static str = it.into();
+                            changed |= ui
+                                .selectable_value(&mut step.algorithm, it, label)
+                                .changed();
+                        }
+                    });
+                changed |= ui
+                    .add(egui::DragValue::new(&mut step.repeat).range(1..=HybridStep::MAX_REPEAT))
+                    .changed();
+                // Exponents are edited as real numbers; whole numbers are sent as integers
+                let mut exponent = Complex::from(&step.exponent);
+                let range = -Self::EXPONENT_MAX..=Self::EXPONENT_MAX;
+                let mut edited = ui
+                    .add(
+                        egui::DragValue::new(&mut exponent.re)
+                            .speed(0.1)
+                            .range(range.clone()),
+                    )
+                    .changed();
+                if step.exponent.typ == NumericType::Complex {
+                    edited |= ui
+                        .add(
+                            egui::DragValue::new(&mut exponent.im)
+                                .speed(0.1)
+                                .suffix("i")
+                                .range(range),
+                        )
+                        .changed();
+                }
+                if edited {
+                    step.exponent = if step.exponent.typ == NumericType::Complex {
+                        PushExponent::from(exponent)
+                    } else if exponent.re.fract() == 0.0 {
+                        PushExponent::from(exponent.re as i32)
+                    } else {
+                        PushExponent::from(exponent.re)
+                    };
+                    changed = true;
+                }
+                if ui
+                    .add_enabled(steps_len > 1, egui::Button::new("🗑"))
+                    .on_hover_text("Remove this step")
+                    .clicked()
+                {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            steps.remove(i);
+            changed = true;
+        }
+        if ui
+            .add_enabled(
+                steps.len() < HYBRID_SIZE as usize,
+                egui::Button::new("Add step"),
+            )
+            .clicked()
+        {
+            steps.push(*steps.last().expect("the sequence is never empty"));
+            changed = true;
+        }
+        changed
+    }
+
+    /// Editor for the gradient colourer's stops. Returns true if they changed.
+    fn gradient_controls(&mut self, ui: &mut egui::Ui) -> bool {
+        egui::ComboBox::from_label("Interpolation")
+            .selected_text(format!("{:?}", self.palette.interpolation))
+            .show_ui(ui, |ui| {
+                use strum::IntoEnumIterator as _;
+                for it in Interpolation::iter() {
+                    let label: &'static str = it.into();
+                    ui.selectable_value(&mut self.palette.interpolation, it, label);
+                }
+            });
+        let mut changed = self.gradient.preview(ui, self.palette.interpolation);
+        let stops = &mut self.gradient.stops;
+        let mut remove = None;
+        let stops_len = stops.len();
+        egui::Grid::new("gradient_grid").show(ui, |ui| {
+            ui.label("Position");
+            ui.label("Colour");
+            ui.end_row();
+            for (i, stop) in stops.iter_mut().enumerate() {
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut stop.position)
+                            .speed(0.002)
+                            .range(0.0..=1.0),
+                    )
+                    .changed();
+                // Stops are sRGB, as the colour picker expects; they are saved to 8 bits anyway
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let mut srgb = stop
+                    .colour()
+                    .to_array()
+                    .map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+                if egui::color_picker::color_edit_button_srgb(ui, &mut srgb).changed() {
+                    *stop = GradientStop::new(
+                        stop.position,
+                        glam::Vec3::from_array(srgb.map(|c| f32::from(c) / 255.)),
+                    );
+                    changed = true;
+                }
+                if ui
+                    .add_enabled(stops_len > 1, egui::Button::new("🗑"))
+                    .on_hover_text("Remove this stop")
+                    .clicked()
+                {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            stops.remove(i);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            let stops = &mut self.gradient.stops;
+            if ui
+                .add_enabled(
+                    stops.len() < GRADIENT_SIZE as usize,
+                    egui::Button::new("Add stop"),
+                )
+                .clicked()
+            {
+                let last = *stops.last().expect("the gradient is never empty");
+                stops.push(GradientStop {
+                    position: 1.0,
+                    ..last
+                });
+                changed = true;
+            }
+            #[cfg(not(wasm))]
+            if ui
+                .button("Import…")
+                .on_hover_text(
+                    "Load the stops from a Fractint map, GIMP gradient or list of hex colours",
+                )
+                .clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Palette files", &["map", "ggr", "txt"])
+                    .add_filter("All files", &["*"])
+                    .pick_file()
+            {
+                match self.gradient.import(&path) {
+                    Ok(()) => changed = true,
+                    Err(e) => {
+                        self.error_message =
+                            Some(format!("Could not import {}: {e:#}", path.display()));
+                    }
+                }
+            }
+            if ui.button("Reset").clicked() {
+                self.gradient
+                    .set_stops(GradientStop::default_gradient().to_vec());
+                changed = true;
+            }
+        });
+        changed
+    }
+
     pub(super) fn controls_window(&mut self, ctx: &egui::Context) {
         // Don't render this on the first pass before we know the window size. That gives it a bad default position.
         if self.size.y == 0 {
@@ -21,7 +210,6 @@ impl super::Controller {
             .default_pos(pos)
             .resizable(false)
             .show(ctx, |ui| {
-                use shader_common::NumericType;
 
                 let algorithm_before = self.algorithm;
                 egui::ComboBox::from_label(egui::RichText::new("Fractal"))
@@ -37,6 +225,82 @@ impl super::Controller {
                     self.reiterate = true;
                 }
 
+                egui::CollapsingHeader::new("Julia set").show(ui, |ui| {
+                    let mut julia = self.julia.active;
+                    if ui.checkbox(&mut julia, "Enabled").changed() {
+                        self.set_julia(julia);
+                    }
+                    egui::Grid::new("julia_grid").show(ui, |ui| {
+                        ui.label("c (Re)");
+                        let re = ui.add(egui::DragValue::new(&mut self.julia.parameter.x).speed(0.001));
+                        ui.end_row();
+                        ui.label("c (Im)");
+                        let im = ui.add(egui::DragValue::new(&mut self.julia.parameter.y).speed(0.001));
+                        ui.end_row();
+                        if self.julia.active && (re.changed() || im.changed()) {
+                            self.reiterate = true;
+                        }
+                    });
+                });
+
+                if self.algorithm == Algorithm::NewtonRoots {
+                    egui::CollapsingHeader::new("Roots").show(ui, |ui| {
+                        egui::Grid::new("roots_grid").show(ui, |ui| {
+                            for (i, root) in self.newton_roots.iter_mut().enumerate() {
+                                ui.label(format!("Root {}", i + 1));
+                                let re = ui.add(egui::DragValue::new(&mut root.x).speed(0.01));
+                                let im = ui.add(egui::DragValue::new(&mut root.y).speed(0.01).suffix("i"));
+                                ui.end_row();
+                                if re.changed() || im.changed() {
+                                    self.reiterate = true;
+                                }
+                            }
+                        });
+                    });
+                }
+
+                if self.algorithm == Algorithm::Phoenix {
+                    egui::CollapsingHeader::new("Phoenix").show(ui, |ui| {
+                        egui::Grid::new("phoenix_grid").show(ui, |ui| {
+                            ui.label("p (Re)");
+                            let re = ui.add(egui::DragValue::new(&mut self.phoenix_parameter.x).speed(0.001));
+                            ui.end_row();
+                            ui.label("p (Im)");
+                            let im = ui.add(egui::DragValue::new(&mut self.phoenix_parameter.y).speed(0.001));
+                            ui.end_row();
+                            if re.changed() || im.changed() {
+                                self.reiterate = true;
+                            }
+                        });
+                    });
+                }
+
+                if self.algorithm == Algorithm::Formula {
+                    egui::CollapsingHeader::new("Formula").default_open(true).show(ui, |ui| {
+                        ui.label("z :=");
+                        let edit = ui.add(
+                            egui::TextEdit::singleline(&mut self.formula.source)
+                                .code_editor()
+                                .hint_text("z^2 + c"),
+                        );
+                        if edit.changed() && self.formula.compile() {
+                            self.reiterate = true;
+                        }
+                        if let Some(error) = &self.formula.error {
+                            ui.colored_label(egui::Color32::RED, error);
+                        }
+                    });
+                }
+
+                if self.algorithm == Algorithm::Hybrid {
+                    egui::CollapsingHeader::new("Hybrid").default_open(true).show(ui, |ui| {
+                        if self.hybrid_controls(ui) {
+                            self.hybrid.changed();
+                            self.reiterate = true;
+                        }
+                    });
+                }
+
                 egui::CollapsingHeader::new("Exponent").show(ui, |ui| {
                     egui::Grid::new("exponent_grid").show(ui, |ui| {
                         let previous_typ = self.exponent.typ;
@@ -147,11 +411,71 @@ impl super::Controller {
 
                 ui.label(egui::RichText::new("Max Iterations"));
                 if ui
-                    .add(egui::Slider::new(&mut self.max_iter, 1..=100_000).logarithmic(true))
+                    .add(
+                        egui::Slider::new(
+                            &mut self.max_iter,
+                            1..=FragmentConstants::MAX_ITER_LIMIT,
+                        )
+                        .logarithmic(true),
+                    )
                     .changed()
                 {
                     self.reiterate = true;
                 }
+                if ui
+                    .checkbox(&mut self.progressive, "Progressive")
+                    .on_hover_text(
+                        "Spread high iteration counts over several frames, \
+                        showing a coarse preview first",
+                    )
+                    .changed()
+                {
+                    self.reiterate = true;
+                }
+                // The shader notices changes to these for itself
+                egui::ComboBox::from_label("Anti-aliasing")
+                    .selected_text(format!("{:?}", self.antialias.pattern))
+                    .show_ui(ui, |ui| {
+                        use strum::IntoEnumIterator as _;
+                        for it in Antialias::iter() {
+                            let label: &'static str = it.into();
+                            ui.selectable_value(&mut self.antialias.pattern, it, label);
+                        }
+                    });
+                if self.antialias.pattern != Antialias::Off {
+                    ui.checkbox(&mut self.antialias.adaptive, "Edges only")
+                        .on_hover_text(
+                            "Only supersample the pixels on an edge, \
+                            as the distance estimate or the neighbouring pixels show it",
+                        );
+                }
+
+                egui::CollapsingHeader::new("Bailout").show(ui, |ui| {
+                    egui::ComboBox::from_label("Shape")
+                        .selected_text(format!("{:?}", self.bailout))
+                        .show_ui(ui, |ui| {
+                            use strum::IntoEnumIterator as _;
+                            for it in Bailout::iter() {
+                                let label: &'static str = it.into();
+                                if ui.selectable_value(&mut self.bailout, it, label).changed() {
+                                    self.reiterate = true;
+                                }
+                            }
+                        });
+                    ui.label("Escape radius");
+                    if ui
+                        .add(
+                            egui::Slider::new(
+                                &mut self.escape_radius,
+                                FragmentConstants::MIN_ESCAPE_RADIUS..=FragmentConstants::MAX_ESCAPE_RADIUS,
+                            )
+                            .logarithmic(true),
+                        )
+                        .changed()
+                    {
+                        self.reiterate = true;
+                    }
+                });
 
                 ui.separator();
 
@@ -164,6 +488,13 @@ impl super::Controller {
                             ui.selectable_value(&mut self.palette.colourer, it, label);
                         }
                     });
+                if self.palette.colourer == Colourer::Gradient {
+                    egui::CollapsingHeader::new("Gradient").default_open(true).show(ui, |ui| {
+                        if self.gradient_controls(ui) {
+                            self.gradient.changed();
+                        }
+                    });
+                }
                 egui::CollapsingHeader::new("Palette controls")
                     .id_salt("palette-detail")
                     .show(ui, |ui| {
@@ -176,6 +507,17 @@ impl super::Controller {
                                     ui.selectable_value(&mut self.palette.colour_style, it, label);
                                 }
                             });
+                        if self.palette.uses_mapping() {
+                            egui::ComboBox::from_label("Mapping")
+                                .selected_text(format!("{:?}", self.palette.mapping))
+                                .show_ui(ui, |ui| {
+                                    use strum::IntoEnumIterator as _;
+                                    for it in Mapping::iter() {
+                                        let label: &'static str = it.into();
+                                        ui.selectable_value(&mut self.palette.mapping, it, label);
+                                    }
+                                });
+                        }
                         egui::ComboBox::from_label("Brightness Style")
                             .selected_text(format!("{:?}", self.palette.brightness_style))
                             .show_ui(ui, |ui| {
@@ -194,6 +536,15 @@ impl super::Controller {
                                     ui.selectable_value(&mut self.palette.saturation_style, it, label);
                                 }
                             });
+                        egui::ComboBox::from_label("Interior")
+                            .selected_text(format!("{:?}", self.palette.interior))
+                            .show_ui(ui, |ui| {
+                                use strum::IntoEnumIterator as _;
+                                for it in Interior::iter() {
+                                    let label: &'static str = it.into();
+                                    ui.selectable_value(&mut self.palette.interior, it, label);
+                                }
+                            });
 
                         macro_rules! palette_slider {
                             ($($id:ident), * ) => {
@@ -209,7 +560,10 @@ impl super::Controller {
                         palette_slider!(offset);
                         // Hide parameters when they don't apply
                         match self.palette.colourer {
-                            Colourer::LogRainbow | Colourer::SqrtRainbow => {
+                            Colourer::LogRainbow
+                            | Colourer::SqrtRainbow
+                            | Colourer::Basin
+                            | Colourer::OklchRainbow => {
                                 ui.label(egui::RichText::new("Saturation"));
                                 palette_slider!(saturation);
                                 ui.label(egui::RichText::new("Lightness"));
@@ -221,7 +575,55 @@ impl super::Controller {
                             }
                             _ => (),
                         }
+                        if [self.palette.brightness_style, self.palette.saturation_style]
+                            .contains(&Modifier::Lighting)
+                        {
+                            ui.label(egui::RichText::new("Light direction"));
+                            palette_slider!(light_azimuth);
+                            ui.label(egui::RichText::new("Light elevation"));
+                            palette_slider!(light_elevation);
+                            ui.label(egui::RichText::new("Ambient light"));
+                            palette_slider!(ambient);
+                            ui.label(egui::RichText::new("Specular"));
+                            palette_slider!(specular);
+                        }
                     });
+                egui::CollapsingHeader::new("Orbit trap").show(ui, |ui| {
+                    egui::ComboBox::from_label("Trap shape")
+                        .selected_text(format!("{:?}", self.trap.shape))
+                        .show_ui(ui, |ui| {
+                            use strum::IntoEnumIterator as _;
+                            for it in OrbitTrap::iter() {
+                                let label: &'static str = it.into();
+                                if ui.selectable_value(&mut self.trap.shape, it, label).changed() {
+                                    self.reiterate = true;
+                                }
+                            }
+                        });
+                    egui::Grid::new("trap_grid").show(ui, |ui| {
+                        match self.trap.shape {
+                            OrbitTrap::Circle => {
+                                ui.label("Radius");
+                                if ui.add(egui::DragValue::new(&mut self.trap.radius).speed(0.001).range(0.0..=f32::MAX)).changed() {
+                                    self.reiterate = true;
+                                }
+                                ui.end_row();
+                            }
+                            OrbitTrap::UserPoint => {
+                                ui.label("Re");
+                                let re = ui.add(egui::DragValue::new(&mut self.trap.point.x).speed(0.001));
+                                ui.end_row();
+                                ui.label("Im");
+                                let im = ui.add(egui::DragValue::new(&mut self.trap.point.y).speed(0.001));
+                                ui.end_row();
+                                if re.changed() || im.changed() {
+                                    self.reiterate = true;
+                                }
+                            }
+                            _ => (),
+                        }
+                    });
+                });
             })
             .unwrap();
     }
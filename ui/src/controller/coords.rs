@@ -55,6 +55,14 @@ impl super::Controller {
                     } else {
                         ui.monospace(format!("{zoom:+.2e}"));
                     }
+                    if self.julia.active {
+                        ui.end_row();
+                        ui.label("Julia c (Re)");
+                        ui.monospace(dynfmt!(self.julia.parameter.x));
+                        ui.end_row();
+                        ui.label("Julia c (Im)");
+                        ui.monospace(dynfmt!(self.julia.parameter.y));
+                    }
                 });
 
                 if self.inspector.active {
@@ -80,6 +88,12 @@ impl super::Controller {
                             ));
                         }
                         ui.end_row();
+                        let period = self.inspector.data.period();
+                        if period != 0 {
+                            ui.label("Period");
+                            ui.monospace(period.to_string());
+                            ui.end_row();
+                        }
                         ui.label("Distance");
                         ui.monospace(dynfmt!(self.inspector.data.distance()));
                         ui.end_row();
@@ -89,7 +103,37 @@ impl super::Controller {
                         ui.label("Final radius");
                         ui.monospace(dynfmt!(self.inspector.data.radius_sqr().sqrt()));
                         ui.end_row();
+                        if self.palette.uses_trap() {
+                            ui.label("Trap distance");
+                            ui.monospace(dynfmt!(self.inspector.data.trap_distance()));
+                            ui.end_row();
+                        }
+                        if self.palette.uses_interior() && inside && period != 0 {
+                            ui.label("Multiplier");
+                            ui.monospace(dynfmt!(self.inspector.data.multiplier_abs()));
+                            ui.end_row();
+                            ui.label("Multiplier angle");
+                            ui.monospace(dynfmt!(self.inspector.data.multiplier_arg()));
+                            ui.end_row();
+                            ui.label("Interior distance");
+                            ui.monospace(dynfmt!(self.inspector.data.interior_distance()));
+                            ui.end_row();
+                        }
+                        if self.palette.uses_averages() && !inside {
+                            ui.label("Stripe average");
+                            ui.monospace(dynfmt!(self.inspector.data.stripe_average()));
+                            ui.end_row();
+                            ui.label("Triangle average");
+                            ui.monospace(dynfmt!(self.inspector.data.triangle_average()));
+                            ui.end_row();
+                            ui.label("Curvature average");
+                            ui.monospace(dynfmt!(self.inspector.data.curvature_average()));
+                            ui.end_row();
+                        }
                     });
+                    if !self.julia.active && ui.button("Julia set here").clicked() {
+                        self.julia_at(&self.inspector.position.clone());
+                    }
                     if ui.button("Close inspector").clicked() {
                         self.inspector.active = false;
                     }
@@ -107,9 +151,9 @@ impl super::Controller {
     }
 
     pub(crate) fn set_mouse_pointer(&mut self, ctx: &egui::Context) {
-        if self.inspector.dragging {
+        if self.inspector.dragging || self.trap.dragging {
             ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
-        } else if self.mouse_on_marker() {
+        } else if self.mouse_on_marker() || self.mouse_on_trap() {
             ctx.set_cursor_icon(egui::CursorIcon::Grab);
         } else {
             ctx.set_cursor_icon(egui::CursorIcon::Default);
@@ -117,7 +161,19 @@ impl super::Controller {
     }
     pub(crate) fn update_inspector(&mut self) {
         self.inspector.stale = false;
-        let consts = self.fragment_constants(false);
-        self.inspector.data = shader::fractal::render(&consts, self.inspector.position.as_vec2());
+        if self.perturbed() {
+            self.update_reference_orbit();
+            let consts = self.fragment_constants(false);
+            let delta = self.inspector.position.clone() - self.drawn_centre();
+            self.inspector.data =
+                shader::fractal::render_perturbed(&consts, delta.as_vec2(), &self.reference.orbit);
+        } else {
+            let consts = self.fragment_constants(false);
+            self.inspector.data = shader::fractal::render_custom(
+                &consts,
+                self.inspector.position.as_vec2(),
+                self.custom(),
+            );
+        }
     }
 }
@@ -0,0 +1,83 @@
+//! User-defined iteration formulas
+// (c) 2025 Ross Younger
+
+use easy_shader_runner::wgpu;
+use shader_common::formula::FORMULA_SIZE;
+
+/// The formula we start with
+const DEFAULT_FORMULA: &str = "z^2 + c";
+
+/// The user's formula, its bytecode, and the GPU buffer
+pub(super) struct Formula {
+    /// Source text, as edited by the user
+    pub(super) source: String,
+    /// Bytecode of the most recent source which compiled
+    code: Vec<u32>,
+    /// If the source doesn't compile, the reason why
+    pub(super) error: Option<String>,
+    /// GPU copy of the bytecode (created by `describe_bind_groups`)
+    buffer: Option<wgpu::Buffer>,
+    /// Does the GPU copy need updating?
+    upload: bool,
+}
+
+impl Default for Formula {
+    fn default() -> Self {
+        let mut formula = Self {
+            source: DEFAULT_FORMULA.to_owned(),
+            code: Vec::new(),
+            error: None,
+            buffer: None,
+            upload: false,
+        };
+        formula.compile();
+        formula
+    }
+}
+
+impl Formula {
+    /// Size in bytes of the GPU buffer
+    pub(super) const BUFFER_SIZE: u64 = FORMULA_SIZE as u64 * std::mem::size_of::<u32>() as u64;
+
+    pub(super) fn set_buffer(&mut self, buffer: wgpu::Buffer) {
+        self.buffer = Some(buffer);
+        self.upload = true;
+    }
+
+    /// Compiles the source text.
+    ///
+    /// If it doesn't compile, we keep the previous bytecode and record the error.
+    /// Returns true if the bytecode has changed.
+    pub(super) fn compile(&mut self) -> bool {
+        match util::compile_formula(&self.source) {
+            Ok(code) => {
+                self.error = None;
+                if code == self.code {
+                    return false;
+                }
+                self.code = code;
+                self.upload = true;
+                true
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// The bytecode, as sent to the GPU
+    pub(super) fn code(&self) -> &[u32] {
+        &self.code
+    }
+
+    /// Sends the bytecode to the GPU, if it has changed
+    pub(super) fn upload(&mut self, queue: &wgpu::Queue) {
+        if let Some(buffer) = &self.buffer
+            && self.upload
+        {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&self.code));
+            self.upload = false;
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Gradient palettes
+// (c) 2025 Ross Younger
+
+use easy_shader_runner::{egui, wgpu};
+use glam::{vec3, Vec3};
+use shader_common::{
+    enums::Interpolation,
+    gradient::{sort_stops, GradientStop, GRADIENT_SIZE},
+};
+
+/// Height of the preview bar, in points
+const PREVIEW_HEIGHT: f32 = 24.;
+/// Size of the stop handles under the preview bar, in points
+const HANDLE_SIZE: f32 = 6.;
+/// Number of bands the preview bar is drawn in
+const PREVIEW_BANDS: u16 = 128;
+
+/// The stops of the gradient colourer, and its GPU buffer
+pub(super) struct Gradient {
+    /// The stops, as edited by the user (not necessarily in order)
+    pub(super) stops: Vec<GradientStop>,
+    /// The stop whose handle is being dragged, if any
+    dragging: Option<usize>,
+    /// GPU copy of the stops (created by `describe_bind_groups`)
+    buffer: Option<wgpu::Buffer>,
+    /// Does the GPU copy need updating?
+    upload: bool,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            stops: GradientStop::default_gradient().to_vec(),
+            dragging: None,
+            buffer: None,
+            upload: true,
+        }
+    }
+}
+
+impl Gradient {
+    /// Size in bytes of the GPU buffer
+    pub(super) const BUFFER_SIZE: u64 =
+        GRADIENT_SIZE as u64 * std::mem::size_of::<GradientStop>() as u64;
+
+    pub(super) fn set_buffer(&mut self, buffer: wgpu::Buffer) {
+        self.buffer = Some(buffer);
+        self.upload = true;
+    }
+
+    /// Replaces the stops
+    pub(super) fn set_stops(&mut self, stops: Vec<GradientStop>) {
+        debug_assert!(!stops.is_empty() && stops.len() <= GRADIENT_SIZE as usize);
+        self.stops = stops;
+        self.dragging = None;
+        self.upload = true;
+    }
+
+    /// Notes that the stops have been edited in place
+    pub(super) fn changed(&mut self) {
+        self.upload = true;
+    }
+
+    /// The stops in order, as the shader needs them
+    pub(super) fn sorted(&self) -> Vec<GradientStop> {
+        let mut stops = self.stops.clone();
+        sort_stops(&mut stops);
+        stops
+    }
+
+    /// Sends the stops to the GPU, if they have changed.
+    /// Returns whether it did.
+    pub(super) fn upload(&mut self, queue: &wgpu::Queue) -> bool {
+        if let Some(buffer) = &self.buffer
+            && self.upload
+        {
+            let mut data = self.sorted();
+            // The shader searches the whole buffer, so the end must be filled in
+            data.resize(GRADIENT_SIZE as usize, GradientStop::END);
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&data));
+            self.upload = false;
+            return true;
+        }
+        false
+    }
+
+    /// Loads the stops from a palette file
+    #[cfg(not(wasm))]
+    pub(super) fn import(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let text = std::fs::read(path)?;
+        // Palette files are often older than UTF-8; any text in them is only comments
+        let stops = util::import_palette(
+            &String::from_utf8_lossy(&text),
+            util::PaletteFormat::from_path(path),
+        )?;
+        self.set_stops(stops);
+        Ok(())
+    }
+
+    /// Draws the gradient as the shader would, with a handle under it for each stop.
+    ///
+    /// The handles may be dragged along the gradient; clicking elsewhere adds a stop.
+    /// Returns true if the stops changed.
+    pub(super) fn preview(&mut self, ui: &mut egui::Ui, interpolation: Interpolation) -> bool {
+        let width = ui.available_width();
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(width, PREVIEW_HEIGHT + HANDLE_SIZE * 2.),
+            egui::Sense::click_and_drag(),
+        );
+        let bar = egui::Rect::from_min_size(rect.min, egui::vec2(width, PREVIEW_HEIGHT));
+        let x_to_position = |x: f32| ((x - bar.left()) / bar.width()).clamp(0., 1.);
+        let position_to_x = |position: f32| bar.left() + position * bar.width();
+
+        let mut changed = false;
+        if let Some(pointer) = response.interact_pointer_pos() {
+            if response.drag_started() {
+                // Pick up the nearest handle, if the pointer is on one
+                self.dragging = self
+                    .stops
+                    .iter()
+                    .enumerate()
+                    .map(|(i, stop)| (i, (position_to_x(stop.position) - pointer.x).abs()))
+                    .filter(|(_, distance)| *distance <= HANDLE_SIZE)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(i, _)| i);
+            }
+            if let Some(i) = self.dragging
+                && response.dragged()
+            {
+                self.stops[i].position = x_to_position(pointer.x);
+                changed = true;
+            }
+            if response.clicked() && self.stops.len() < GRADIENT_SIZE as usize {
+                // The new stop takes the colour the gradient already has there
+                let position = x_to_position(pointer.x);
+                let colour = self.colour_at(position, interpolation);
+                self.stops.push(GradientStop::new(position, colour));
+                changed = true;
+            }
+        }
+        if response.drag_stopped() {
+            self.dragging = None;
+        }
+
+        let painter = ui.painter_at(rect);
+        let band_width = bar.width() / f32::from(PREVIEW_BANDS);
+        let sorted = self.sorted();
+        for band in 0..PREVIEW_BANDS {
+            let position = (f32::from(band) + 0.5) / f32::from(PREVIEW_BANDS);
+            let colour: Vec3 =
+                shader::colour::gradient_colour(&sorted, position, interpolation).into();
+            let left = bar.left() + f32::from(band) * band_width;
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(left, bar.top()),
+                    egui::pos2(left + band_width + 0.5, bar.bottom()),
+                ),
+                0.,
+                egui::Rgba::from_rgb(colour.x, colour.y, colour.z),
+            );
+        }
+        let stroke = ui.visuals().widgets.noninteractive.fg_stroke;
+        for (i, stop) in self.stops.iter().enumerate() {
+            let x = position_to_x(stop.position);
+            let tip = egui::pos2(x, bar.bottom());
+            let fill = if self.dragging == Some(i) {
+                ui.visuals().selection.bg_fill
+            } else {
+                ui.visuals().widgets.inactive.bg_fill
+            };
+            painter.add(egui::Shape::convex_polygon(
+                vec![
+                    tip,
+                    tip + egui::vec2(HANDLE_SIZE, HANDLE_SIZE * 2.),
+                    tip + egui::vec2(-HANDLE_SIZE, HANDLE_SIZE * 2.),
+                ],
+                fill,
+                stroke,
+            ));
+        }
+        response.on_hover_text("Drag the handles to move the stops; click to add a stop");
+        changed
+    }
+
+    /// The colour of the gradient at a position, in sRGB
+    fn colour_at(&self, position: f32, interpolation: Interpolation) -> Vec3 {
+        linear_to_srgb(
+            shader::colour::gradient_colour(&self.sorted(), position, interpolation).into(),
+        )
+    }
+}
+
+/// Converts a colour from linear RGB (as the shader outputs) to sRGB (as the stops hold)
+fn linear_to_srgb(colour: Vec3) -> Vec3 {
+    let encode = |c: f32| {
+        let c = c.clamp(0., 1.);
+        if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1. / 2.4) - 0.055
+        }
+    };
+    vec3(encode(colour.x), encode(colour.y), encode(colour.z))
+}
@@ -0,0 +1,180 @@
+//! Statistics of the iteration counts in view, for the palette mappings which adapt to them
+// (c) 2025 Ross Younger
+
+use easy_shader_runner::{wgpu, GraphicsContext};
+use glam::UVec2;
+use shader::histogram::WORKGROUP_SIZE;
+use shader_common::{
+    enums::ColourStyle,
+    histogram::{IterationStats, ReductionParams},
+};
+
+/// Reduces the cached iteration counts on the GPU, by running the shader's compute passes
+/// over them between frames.
+///
+/// The statistics lag the grid by a frame: each reduction works on the points drawn by the last frame.
+#[derive(Default)]
+pub(super) struct Histogram {
+    /// GPU resources (created by `describe_bind_groups`)
+    gpu: Option<Gpu>,
+    /// Has the grid changed since the statistics were last gathered?
+    stale: bool,
+    /// The colour style the statistics were last gathered for
+    colour_style: ColourStyle,
+}
+
+struct Gpu {
+    /// The statistics, which the fragment shader reads
+    stats: wgpu::Buffer,
+    /// The [`ReductionParams`]
+    params: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    find_range: wgpu::ComputePipeline,
+    count: wgpu::ComputePipeline,
+    accumulate: wgpu::ComputePipeline,
+}
+
+impl Histogram {
+    /// Size in bytes of the statistics buffer
+    pub(super) const BUFFER_SIZE: u64 = std::mem::size_of::<IterationStats>() as u64;
+
+    /// Sets up the compute passes, which reduce `grid_a` into `stats`
+    pub(super) fn set_buffers(
+        &mut self,
+        device: &wgpu::Device,
+        grid_a: &wgpu::Buffer,
+        stats: wgpu::Buffer,
+    ) {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[storage(0, true), storage(1, false), storage(2, true)],
+            label: Some("histogram_bind_group_layout"),
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram_params_buffer"),
+            size: std::mem::size_of::<ReductionParams>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: grid_a.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: stats.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("histogram_bind_group"),
+        });
+
+        // The compute passes are in the same shader as everything else; the runner only
+        // knows about the render pipeline, so we build our own.
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("histogram_shader"),
+            source: wgpu::util::make_spirv(include_bytes!(env!("shader.spv"))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("histogram_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+
+        self.gpu = Some(Gpu {
+            stats,
+            params,
+            bind_group,
+            find_range: pipeline("find_range_cs"),
+            count: pipeline("count_cs"),
+            accumulate: pipeline("accumulate_cs"),
+        });
+        self.stale = true;
+    }
+
+    /// Notes that the grid has been redrawn
+    pub(super) fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Gathers the statistics of the view, if they are out of date.
+    /// Returns whether it did.
+    ///
+    /// `size`, `grid_size` and `grid_origin` must be those of the frame which last drew the grid.
+    pub(super) fn update(
+        &mut self,
+        gfx_ctx: &GraphicsContext,
+        size: UVec2,
+        grid_size: UVec2,
+        grid_origin: UVec2,
+        colour_style: ColourStyle,
+    ) -> bool {
+        let Some(gpu) = &self.gpu else {
+            return false;
+        };
+        if !self.stale && colour_style == self.colour_style {
+            return false;
+        }
+        let size = size.min(grid_size);
+        let params = ReductionParams {
+            size: size.into(),
+            grid: grid_size.into(),
+            origin: grid_origin,
+            colour_style,
+        };
+        let queue = &gfx_ctx.queue;
+        queue.write_buffer(&gpu.stats, 0, bytemuck::bytes_of(&IterationStats::EMPTY));
+        queue.write_buffer(&gpu.params, 0, bytemuck::bytes_of(&params));
+
+        let workgroups = (size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        let mut encoder = gfx_ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("histogram_encoder"),
+            });
+        // Each pass depends on the results of the one before
+        for (pipeline, workgroups) in [
+            (&gpu.find_range, workgroups),
+            (&gpu.count, workgroups),
+            (&gpu.accumulate, UVec2::ONE),
+        ] {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("histogram_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &gpu.bind_group, &[]);
+            pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+        }
+        queue.submit([encoder.finish()]);
+
+        self.stale = false;
+        self.colour_style = colour_style;
+        true
+    }
+}
@@ -0,0 +1,66 @@
+//! Hybrid fractals
+// (c) 2025 Ross Younger
+
+use easy_shader_runner::wgpu;
+use shader_common::hybrid::{HybridStep, HYBRID_SIZE};
+
+/// The hybrid sequence, and its GPU buffer
+pub(super) struct Hybrid {
+    /// The sequence, as edited by the user
+    pub(super) steps: Vec<HybridStep>,
+    /// GPU copy of the sequence (created by `describe_bind_groups`)
+    buffer: Option<wgpu::Buffer>,
+    /// Does the GPU copy need updating?
+    upload: bool,
+}
+
+impl Default for Hybrid {
+    fn default() -> Self {
+        Self {
+            steps: HybridStep::default_sequence().to_vec(),
+            buffer: None,
+            upload: true,
+        }
+    }
+}
+
+impl Hybrid {
+    /// Size in bytes of the GPU buffer
+    pub(super) const BUFFER_SIZE: u64 =
+        HYBRID_SIZE as u64 * std::mem::size_of::<HybridStep>() as u64;
+
+    pub(super) fn set_buffer(&mut self, buffer: wgpu::Buffer) {
+        self.buffer = Some(buffer);
+        self.upload = true;
+    }
+
+    /// Replaces the sequence
+    pub(super) fn set_steps(&mut self, steps: Vec<HybridStep>) {
+        debug_assert!(!steps.is_empty() && steps.len() <= HYBRID_SIZE as usize);
+        self.steps = steps;
+        self.upload = true;
+    }
+
+    /// Notes that the sequence has been edited in place
+    pub(super) fn changed(&mut self) {
+        self.upload = true;
+    }
+
+    /// Sends the sequence to the GPU, if it has changed
+    pub(super) fn upload(&mut self, queue: &wgpu::Queue) {
+        if let Some(buffer) = &self.buffer
+            && self.upload
+        {
+            let mut data = self.steps.clone();
+            // The shader stops at the first step which is never repeated
+            if data.len() < HYBRID_SIZE as usize {
+                data.push(HybridStep {
+                    repeat: 0,
+                    ..data[0]
+                });
+            }
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&data));
+            self.upload = false;
+        }
+    }
+}
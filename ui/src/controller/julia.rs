@@ -0,0 +1,51 @@
+//! Julia set mode
+// (c) 2025 Ross Younger
+
+use glam::DVec2;
+use shader_common::FragmentConstants;
+use util::BigVec2;
+
+/// Julia set state
+#[derive(Default)]
+pub(super) struct Julia {
+    /// Are we rendering the Julia set instead of the parameter plane?
+    pub(super) active: bool,
+    /// The fixed parameter `c`
+    pub(super) parameter: DVec2,
+    /// Position and zoom of the parameter plane view, restored on leaving Julia mode
+    saved_view: Option<(BigVec2, f64)>,
+}
+
+impl super::Controller {
+    /// Switches into or out of Julia mode.
+    ///
+    /// The Julia set view always starts out centred on the origin; the parameter plane
+    /// view is restored on the way back out.
+    pub(super) fn set_julia(&mut self, active: bool) {
+        if active == self.julia.active {
+            return;
+        }
+        self.julia.active = active;
+        if active {
+            let saved = (
+                std::mem::take(&mut self.viewport_translate),
+                self.viewport_zoom,
+            );
+            self.julia.saved_view = Some(saved);
+            self.viewport_translate = BigVec2::ZERO.with_precision(super::PRECISION);
+            self.viewport_zoom = FragmentConstants::DEFAULT_ZOOM.into();
+        } else if let Some((translate, zoom)) = self.julia.saved_view.take() {
+            self.viewport_translate = translate;
+            self.viewport_zoom = zoom;
+        }
+        // The marked position is meaningless in the other plane
+        self.inspector.active = false;
+        self.reiterate = true;
+    }
+
+    /// Renders the Julia set for the given point of the parameter plane
+    pub(super) fn julia_at(&mut self, point: &BigVec2) {
+        self.julia.parameter = point.as_dvec2();
+        self.set_julia(true);
+    }
+}
@@ -0,0 +1,472 @@
+//! Saving and loading view locations
+// (c) 2025 Ross Younger
+
+use std::path::Path;
+
+use anyhow::{anyhow, ensure};
+use glam::DVec2;
+use serde::{Deserialize, Serialize};
+use shader_common::{
+    enums::{
+        Algorithm, Bailout, ColourStyle, Colourer, Interior, Interpolation, Mapping, Modifier,
+        OrbitTrap,
+    },
+    gradient::{GradientStop, GRADIENT_SIZE},
+    hybrid::{HybridStep, HYBRID_SIZE},
+    FragmentConstants, Palette, PushExponent,
+};
+use util::{parse_decimal, to_decimal_string, BigVec2};
+
+use super::{Exponent, MIN_ZOOM, PRECISION};
+
+/// A saved view: everything needed to reproduce what is on screen.
+///
+/// The co-ordinates of the centre are held as exact decimal strings, so no precision is lost
+/// in saving and loading them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Location {
+    /// Real part of the centre of the view
+    pub re: String,
+    /// Imaginary part of the centre of the view
+    pub im: String,
+    /// Zoom level, as shown in the UI
+    pub zoom: f64,
+    #[serde(with = "value_enum")]
+    pub algorithm: Algorithm,
+    #[serde(with = "display_fromstr")]
+    pub exponent: PushExponent,
+    pub max_iter: u32,
+    /// If present, the view is of the Julia set for this parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub julia: Option<[f64; 2]>,
+    /// Roots of the polynomial, if the algorithm is `newton-roots`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newton_roots: Option<[[f64; 2]; 3]>,
+    /// Weight of the previous point, if the algorithm is `phoenix`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phoenix: Option<[f64; 2]>,
+    /// The iteration formula, if the algorithm is `formula`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formula: Option<String>,
+    /// The sequence, if the algorithm is `hybrid`, with each step written as
+    /// `ALGORITHM:REPEAT:EXPONENT`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hybrid: Option<Vec<String>>,
+    /// The stops, if the colourer is `gradient`, with each written as `POSITION:#RRGGBB`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gradient_stops: Option<Vec<String>>,
+    /// Radius of the bailout test (older files don't have this, or `bailout`)
+    #[serde(default = "default_escape_radius", serialize_with = "short_f32")]
+    pub escape_radius: f32,
+    #[serde(default, with = "value_enum")]
+    pub bailout: Bailout,
+    #[serde(default, with = "value_enum")]
+    pub trap: OrbitTrap,
+    /// Position of the trap, if it is `user-point`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trap_point: Option<[f64; 2]>,
+    /// Radius of the trap, if it is `circle`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trap_radius: Option<f64>,
+    #[serde(with = "PaletteDef")]
+    pub palette: Palette,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Palette", deny_unknown_fields)]
+struct PaletteDef {
+    #[serde(with = "value_enum")]
+    colourer: Colourer,
+    #[serde(with = "value_enum")]
+    colour_style: ColourStyle,
+    #[serde(with = "value_enum")]
+    brightness_style: Modifier,
+    #[serde(with = "value_enum")]
+    saturation_style: Modifier,
+    #[serde(default, with = "value_enum")]
+    interior: Interior,
+    #[serde(default, with = "value_enum")]
+    interpolation: Interpolation,
+    #[serde(default, with = "value_enum")]
+    mapping: Mapping,
+    #[serde(serialize_with = "short_f32")]
+    gradient: f32,
+    #[serde(serialize_with = "short_f32")]
+    offset: f32,
+    #[serde(serialize_with = "short_f32")]
+    saturation: f32,
+    #[serde(serialize_with = "short_f32")]
+    lightness: f32,
+    #[serde(serialize_with = "short_f32")]
+    gamma: f32,
+    // Older files don't have the lighting settings
+    #[serde(default = "default_light_azimuth", serialize_with = "short_f32")]
+    light_azimuth: f32,
+    #[serde(default = "default_light_elevation", serialize_with = "short_f32")]
+    light_elevation: f32,
+    #[serde(default = "default_ambient", serialize_with = "short_f32")]
+    ambient: f32,
+    #[serde(default = "default_specular", serialize_with = "short_f32")]
+    specular: f32,
+}
+
+fn default_escape_radius() -> f32 {
+    FragmentConstants::DEFAULT_ESCAPE_RADIUS
+}
+
+macro_rules! palette_defaults {
+    ($($name:ident: $id:ident),*) => {
+        $(
+            fn $name() -> f32 {
+                Palette::default().$id
+            }
+        )*
+    };
+}
+palette_defaults!(
+    default_light_azimuth: light_azimuth,
+    default_light_elevation: light_elevation,
+    default_ambient: ambient,
+    default_specular: specular
+);
+
+/// Serialises an `f32` as the shortest decimal which reads back as the same value.
+///
+/// (TOML floats are `f64`, so 1.9 would otherwise come out as 1.899999976158142.)
+fn short_f32<S: serde::Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(shortest(*value))
+}
+
+/// The shortest decimal `f64` which converts back to the given `f32`
+fn shortest(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(f64::from(value))
+}
+
+/// Serialises enums by the names they have on the command line
+mod value_enum {
+    use clap::ValueEnum;
+    use serde::{de::Error as _, Deserialize as _, Deserializer, Serializer};
+
+    pub(super) fn serialize<T: ValueEnum, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let name = value
+            .to_possible_value()
+            .expect("enum variants are never skipped");
+        serializer.serialize_str(name.get_name())
+    }
+
+    pub(super) fn deserialize<'de, T: ValueEnum, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        T::from_str(&name, true).map_err(D::Error::custom)
+    }
+}
+
+/// Serialises a value as a string, by way of its `Display` and `FromStr` implementations
+mod display_fromstr {
+    use serde::{de::Error as _, Deserialize as _, Deserializer, Serializer};
+    use std::{fmt::Display, str::FromStr};
+
+    pub(super) fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub(super) fn deserialize<'de, T, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+impl super::Controller {
+    /// Captures the current view
+    pub(crate) fn location(&self) -> Location {
+        Location {
+            re: to_decimal_string(&self.viewport_translate.x),
+            im: to_decimal_string(&self.viewport_translate.y),
+            zoom: self.viewport_zoom * f64::from(FragmentConstants::UI_ZOOM_FACTOR),
+            algorithm: self.algorithm,
+            exponent: self.exponent.into(),
+            max_iter: self.max_iter,
+            julia: self.julia.active.then(|| self.julia.parameter.to_array()),
+            newton_roots: (self.algorithm == Algorithm::NewtonRoots)
+                .then(|| self.newton_roots.map(|r| r.to_array().map(shortest))),
+            phoenix: (self.algorithm == Algorithm::Phoenix)
+                .then(|| self.phoenix_parameter.to_array().map(shortest)),
+            formula: (self.algorithm == Algorithm::Formula).then(|| self.formula.source.clone()),
+            hybrid: (self.algorithm == Algorithm::Hybrid)
+                .then(|| self.hybrid.steps.iter().map(ToString::to_string).collect()),
+            gradient_stops: (self.palette.colourer == Colourer::Gradient).then(|| {
+                self.gradient
+                    .sorted()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            }),
+            escape_radius: self.escape_radius,
+            bailout: self.bailout,
+            trap: self.trap.shape,
+            trap_point: (self.trap.shape == OrbitTrap::UserPoint)
+                .then(|| self.trap.point.to_array().map(shortest)),
+            trap_radius: (self.trap.shape == OrbitTrap::Circle).then(|| shortest(self.trap.radius)),
+            palette: self.palette,
+        }
+    }
+
+    /// Restores a saved view.
+    ///
+    /// The location is checked in full before anything is changed.
+    pub(crate) fn apply_location(&mut self, location: &Location) -> anyhow::Result<()> {
+        let x = parse_decimal(&location.re, PRECISION)
+            .map_err(|e| anyhow!("invalid real co-ordinate: {e}"))?;
+        let y = parse_decimal(&location.im, PRECISION)
+            .map_err(|e| anyhow!("invalid imaginary co-ordinate: {e}"))?;
+        let zoom = location.zoom / f64::from(FragmentConstants::UI_ZOOM_FACTOR);
+        ensure!(
+            zoom.is_finite() && zoom >= MIN_ZOOM,
+            "zoom is out of range: {}",
+            location.zoom
+        );
+        ensure!(
+            (1..=FragmentConstants::MAX_ITER_LIMIT).contains(&location.max_iter),
+            "max_iter must be between 1 and {}",
+            FragmentConstants::MAX_ITER_LIMIT
+        );
+        let exponent = Exponent::from(location.exponent);
+        ensure!(
+            exponent.real <= Self::EXPONENT_MAX && exponent.imag <= Self::EXPONENT_MAX,
+            "exponent is out of range: {}",
+            location.exponent
+        );
+        ensure!(
+            (FragmentConstants::MIN_ESCAPE_RADIUS..=FragmentConstants::MAX_ESCAPE_RADIUS)
+                .contains(&location.escape_radius),
+            "escape radius must be between {} and {}",
+            FragmentConstants::MIN_ESCAPE_RADIUS,
+            FragmentConstants::MAX_ESCAPE_RADIUS
+        );
+        if let Some(c) = location.julia {
+            ensure!(
+                c.iter().all(|v| v.is_finite()),
+                "Julia parameter must be finite"
+            );
+        }
+        if let Some(roots) = location.newton_roots {
+            ensure!(
+                roots.as_flattened().iter().all(|v| v.is_finite()),
+                "roots must be finite"
+            );
+        }
+        if let Some(p) = location.phoenix {
+            ensure!(
+                p.iter().all(|v| v.is_finite()),
+                "Phoenix parameter must be finite"
+            );
+        }
+        if let Some(p) = location.trap_point {
+            ensure!(p.iter().all(|v| v.is_finite()), "trap point must be finite");
+        }
+        if let Some(r) = location.trap_radius {
+            ensure!(r.is_finite() && r >= 0., "trap radius must not be negative");
+        }
+        if let Some(f) = &location.formula {
+            util::compile_formula(f).map_err(|e| anyhow!("invalid formula: {e}"))?;
+        }
+        let hybrid = location
+            .hybrid
+            .as_ref()
+            .map(|steps| {
+                ensure!(
+                    (1..=HYBRID_SIZE as usize).contains(&steps.len()),
+                    "hybrid sequence must have between 1 and {HYBRID_SIZE} steps"
+                );
+                steps
+                    .iter()
+                    .map(|s| {
+                        s.parse::<HybridStep>()
+                            .map_err(|e| anyhow!("invalid hybrid step {s:?}: {e}"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .transpose()?;
+        let gradient = location
+            .gradient_stops
+            .as_ref()
+            .map(|stops| {
+                ensure!(
+                    (1..=GRADIENT_SIZE as usize).contains(&stops.len()),
+                    "gradient must have between 1 and {GRADIENT_SIZE} stops"
+                );
+                stops
+                    .iter()
+                    .map(|s| {
+                        s.parse::<GradientStop>()
+                            .map_err(|e| anyhow!("invalid gradient stop {s:?}: {e}"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .transpose()?;
+        location
+            .palette
+            .check_ranges()
+            .map_err(anyhow::Error::msg)?;
+
+        // Keep every bit we were given, but no less than the zoom level needs
+        let precision = x.precision().max(y.precision());
+        self.viewport_translate = BigVec2::new(x, y).with_precision(precision);
+        self.viewport_zoom = zoom;
+        self.algorithm = location.algorithm;
+        self.exponent = exponent;
+        self.max_iter = location.max_iter;
+        self.escape_radius = location.escape_radius;
+        self.bailout = location.bailout;
+        self.trap.shape = location.trap;
+        self.trap.point = location
+            .trap_point
+            .map_or(FragmentConstants::DEFAULT_TRAP_POINT, |p| {
+                DVec2::from(p).as_vec2()
+            });
+        self.trap.radius = location
+            .trap_radius
+            .map_or(FragmentConstants::DEFAULT_TRAP_RADIUS, |r| r as f32);
+        self.palette = location.palette;
+        self.julia = super::julia::Julia::default();
+        if let Some(c) = location.julia {
+            self.julia.active = true;
+            self.julia.parameter = c.into();
+        }
+        self.newton_roots = location
+            .newton_roots
+            .map_or(FragmentConstants::DEFAULT_NEWTON_ROOTS, |roots| {
+                roots.map(|r| DVec2::from(r).as_vec2())
+            });
+        self.phoenix_parameter = location
+            .phoenix
+            .map_or(FragmentConstants::DEFAULT_PHOENIX_PARAMETER, |p| {
+                DVec2::from(p).as_vec2()
+            });
+        if let Some(f) = &location.formula {
+            self.formula.source.clone_from(f);
+            self.formula.compile();
+        }
+        if let Some(steps) = hybrid {
+            self.hybrid.set_steps(steps);
+        }
+        if let Some(stops) = gradient {
+            self.gradient.set_stops(stops);
+        }
+        self.viewport_zoom = self.viewport_zoom.min(self.max_zoom());
+        self.update_precision();
+        self.inspector.active = false;
+        self.reiterate = true;
+        Ok(())
+    }
+
+    /// Saves the current view to a file
+    pub(crate) fn save_location(&self, path: &Path) -> anyhow::Result<()> {
+        let document = toml::to_string(&self.location())?;
+        std::fs::write(path, document)?;
+        Ok(())
+    }
+
+    /// Loads a view from a file
+    pub(crate) fn load_location(&mut self, path: &Path) -> anyhow::Result<()> {
+        let document = std::fs::read_to_string(path)?;
+        let location: Location = toml::from_str(&document)?;
+        self.apply_location(&location)
+    }
+
+    /// Prompts for a file, then loads a view from it
+    #[cfg(not(wasm))]
+    pub(super) fn open_location_dialog(&mut self) {
+        let Some(path) = location_file_dialog().pick_file() else {
+            return;
+        };
+        if let Err(e) = self.load_location(&path) {
+            self.error_message = Some(format!("Could not open {}: {e:#}", path.display()));
+        }
+    }
+
+    /// Prompts for a file, then saves the current view to it
+    #[cfg(not(wasm))]
+    pub(super) fn save_location_dialog(&mut self) {
+        let Some(path) = location_file_dialog()
+            .set_file_name("location.toml")
+            .save_file()
+        else {
+            return;
+        };
+        if let Err(e) = self.save_location(&path) {
+            self.error_message = Some(format!("Could not save {}: {e:#}", path.display()));
+        }
+    }
+}
+
+#[cfg(not(wasm))]
+fn location_file_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new().add_filter("brot3 location", &["toml"])
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{super::Controller, Location};
+    use crate::cli::Args;
+    use clap::Parser as _;
+    use shader_common::{
+        enums::{Colourer, Interior, Mapping, Modifier},
+        Palette,
+    };
+    use util::{parse_decimal, BigVec2};
+
+    #[test]
+    fn round_trip() {
+        let args = Args::parse_from(["brot3"]);
+        let mut controller = Controller::new(&args).unwrap();
+        // Far more bits than an f64 holds
+        let x = parse_decimal(
+            "-0.74364388703715870475219150611477417410487291093102569212398104629071",
+            300,
+        )
+        .unwrap();
+        let y = parse_decimal(
+            "0.13182590420531197049313205638513916016060142108376538472891006219377",
+            300,
+        )
+        .unwrap();
+        assert!(x.precision() >= 256);
+        controller.viewport_translate = BigVec2::new(x, y);
+        let mut palette = Palette::default().with_colourer(Colourer::Gradient);
+        palette.interior = Interior::Multiplier;
+        palette.mapping = Mapping::Equalised;
+        palette.brightness_style = Modifier::Lighting;
+        palette.gradient = 1.9;
+        palette.offset = -3.25;
+        palette.gamma = 0.7;
+        palette.light_azimuth = 135.;
+        controller.palette = palette;
+
+        let document = toml::to_string(&controller.location()).unwrap();
+        let location: Location = toml::from_str(&document).unwrap();
+        let mut loaded = Controller::new(&args).unwrap();
+        loaded.apply_location(&location).unwrap();
+        assert_eq!(loaded.viewport_translate, controller.viewport_translate);
+        // Palette isn't PartialEq, but any difference would show in its bytes
+        assert_eq!(
+            bytemuck::bytes_of(&loaded.palette),
+            bytemuck::bytes_of(&palette),
+            "{document}"
+        );
+    }
+}
@@ -36,6 +36,17 @@ impl super::Controller {
                                 .min_size(vec2(ITEM_WIDTH, 0.0))
                         };
                     }
+                    #[cfg(not(wasm))]
+                    {
+                        if ui.add(item!("Open location…", "")).clicked() {
+                            self.open_location_dialog();
+                        }
+                        if ui.add(item!("Save location…", "")).clicked() {
+                            self.save_location_dialog();
+                        }
+                        ui.separator();
+                    }
+
                     checkbox!(self.show_controls, "Controls", "F2");
                     checkbox!(self.show_coords_window, "Data read-out", "F3");
                     checkbox!(self.show_scale_bar, "Scale bar", "F4");
@@ -1,27 +1,45 @@
 use crate::cli::Args;
 
 use easy_shader_runner::{egui, wgpu, winit, ControllerTrait, GraphicsContext, UiState};
-use glam::{dvec2, DVec2, UVec2, Vec2};
+use glam::{dvec2, uvec2, DVec2, IVec2, UVec2, Vec2};
 use shader_common::{
-    data::{PointResult, PointResultA, PointResultB},
-    enums::Algorithm,
-    flag_if, Flags, FragmentConstants, NumericType, Palette, PushExponent, GRID_SIZE,
+    data::{
+        grid_size_for, OrbitState, PointResult, PointResultA, PointResultB, PointResultC,
+        Supersamples, DEFAULT_GRID_SIZE,
+    },
+    enums::{Algorithm, Bailout},
+    flag_if, Flags, FragmentConstants, NumericType, Palette, PushExponent, ViewParameters,
 };
 use util::BigVec2;
 use web_time::Instant;
 use winit::event::{ElementState, MouseButton};
 
 mod about;
+mod antialias;
 mod controls;
 mod coords;
+mod formula;
+mod gradient;
+mod histogram;
+mod hybrid;
+mod julia;
 mod keyboard;
+mod location;
 mod menu;
+mod perturbation;
+mod preview;
+mod progressive;
 mod small_windows;
+mod trap;
 mod ui;
 
+pub(crate) use location::Location;
+
+/// Minimum precision of co-ordinates, in bits
 const PRECISION: usize = 128;
 const MIN_ZOOM: f64 = 0.05;
-const MAX_ZOOM: f64 = 13000.; // TODO: implement perturbed mbrot
+/// The deepest zoom we allow when not rendering by perturbation
+const MAX_ZOOM: f64 = 13000.;
 
 pub(crate) struct Controller {
     /// viewport pixel size
@@ -35,15 +53,37 @@ pub(crate) struct Controller {
     max_iter: u32,
     palette: Palette,
     exponent: Exponent,
+    julia: julia::Julia,
+    /// Roots of the polynomial, for [`Algorithm::NewtonRoots`]
+    newton_roots: [Vec2; 3],
+    /// Weight of the previous point, for [`Algorithm::Phoenix`]
+    phoenix_parameter: Vec2,
+    /// For [`Algorithm::Formula`]
+    formula: formula::Formula,
+    /// For [`Algorithm::Hybrid`]
+    hybrid: hybrid::Hybrid,
+    /// For [`Colourer::Gradient`](shader_common::enums::Colourer::Gradient)
+    gradient: gradient::Gradient,
+    /// For the palette mappings which adapt to the view
+    histogram: histogram::Histogram,
+    /// The bailout test: orbits escape when they leave the region of this radius and shape
+    escape_radius: f32,
+    bailout: Bailout,
+    trap: trap::Trap,
     // User-facing options
     show_coords_window: bool,
     show_scale_bar: bool,
     show_fps: bool,
     vsync: bool,
+    /// Spread the iterations over several frames, when the view allows it: see [`Flags::PROGRESSIVE`]
+    progressive: bool,
+    antialias: antialias::Antialiasing,
     show_controls: bool,
     keyboard_help: bool,
     show_about: bool,
     show_license: bool,
+    /// If present, an error to report to the user
+    error_message: Option<String>,
 
     // UI operational data
     last_instant: Instant,
@@ -57,6 +97,29 @@ pub(crate) struct Controller {
     fullscreen_requested: bool,
     context_menu: Option<DVec2>,
     inspector: Inspector,
+    /// How far the view is from the pixels which the cache holds, in pixels: see [`Self::pan`]
+    pan_remainder: DVec2,
+    /// Size of the cache grids, chosen when they are created: see [`FragmentConstants::grid_size`]
+    grid_size: UVec2,
+    /// Where the cache grids hold pixel (0,0) of the view: see [`FragmentConstants::grid_origin`]
+    grid_origin: UVec2,
+    /// While previewing a zoom, the view the cache grids hold
+    zoom_preview: Option<preview::CachedView>,
+    /// Where the next slice of a progressive render starts, while one is under way
+    next_slice: Option<u32>,
+    reference: perturbation::ReferenceOrbit,
+    /// What the palette needed from the orbits when they were last iterated: see [`Self::check_orbit_data`]
+    orbit_data: OrbitData,
+    /// GPU copy of the [`ViewParameters`] (created by `describe_bind_groups`)
+    view_buffer: Option<wgpu::Buffer>,
+}
+
+/// Data which the shader only collects from the orbits when the palette uses it
+#[derive(Clone, Copy, Default, PartialEq)]
+struct OrbitData {
+    trap: bool,
+    averages: bool,
+    interior: bool,
 }
 
 #[derive(Default)]
@@ -69,27 +132,40 @@ struct Inspector {
 }
 
 impl Controller {
-    pub fn new(options: &Args) -> Self {
-        Self {
+    /// Creates a controller, starting at the view set up by the command line options
+    pub fn new(options: &Args) -> anyhow::Result<Self> {
+        let mut controller = Self {
             size: UVec2::ZERO,
-            // TODO figure out what precision is best
-            viewport_translate: BigVec2::try_new(-1., 0.).unwrap().with_precision(PRECISION),
+            viewport_translate: BigVec2::ZERO.with_precision(PRECISION),
             viewport_zoom: FragmentConstants::DEFAULT_ZOOM.into(),
             movement: Movement::default(),
 
-            algorithm: options.fractal,
+            algorithm: Algorithm::default(),
             max_iter: FragmentConstants::DEFAULT_MAX_ITER,
-            palette: Palette::default().with_colourer(options.colourer), // TODO with render style too
+            palette: Palette::default(),
             exponent: Exponent::default(),
+            julia: julia::Julia::default(),
+            newton_roots: FragmentConstants::DEFAULT_NEWTON_ROOTS,
+            phoenix_parameter: FragmentConstants::DEFAULT_PHOENIX_PARAMETER,
+            formula: formula::Formula::default(),
+            hybrid: hybrid::Hybrid::default(),
+            gradient: gradient::Gradient::default(),
+            histogram: histogram::Histogram::default(),
+            escape_radius: FragmentConstants::DEFAULT_ESCAPE_RADIUS,
+            bailout: Bailout::default(),
+            trap: trap::Trap::default(),
 
             show_coords_window: true,
             show_scale_bar: true,
             show_fps: false,
             vsync: true,
+            progressive: true,
+            antialias: antialias::Antialiasing::default(),
             show_controls: !options.no_ui,
             keyboard_help: false,
             show_about: false,
             show_license: false,
+            error_message: None,
 
             last_instant: Instant::now(),
             mouse_position: DVec2::default(),
@@ -102,15 +178,38 @@ impl Controller {
             fullscreen_requested: options.fullscreen,
             context_menu: None,
             inspector: Inspector::default(),
+            pan_remainder: DVec2::ZERO,
+            grid_size: DEFAULT_GRID_SIZE,
+            grid_origin: UVec2::ZERO,
+            zoom_preview: None,
+            next_slice: None,
+            reference: perturbation::ReferenceOrbit::default(),
+            orbit_data: OrbitData::default(),
+            view_buffer: None,
+        };
+        controller.apply_location(&options.location())?;
+        #[cfg(not(wasm))]
+        if let Some(path) = &options.gradient_file {
+            use anyhow::Context as _;
+            controller
+                .gradient
+                .import(path)
+                .with_context(|| format!("importing {}", path.display()))?;
         }
+        Ok(controller)
     }
 
     fn fragment_constants(&self, reiterate: bool) -> FragmentConstants {
+        let perturbed = self.perturbed();
         let flags = flag_if(reiterate, Flags::NEEDS_REITERATE)
-            | flag_if(self.inspector.active, Flags::INSPECTOR_ACTIVE);
+            | flag_if(self.inspector.active, Flags::INSPECTOR_ACTIVE)
+            | flag_if(perturbed, Flags::PERTURBED)
+            | flag_if(self.julia.active, Flags::JULIA)
+            | flag_if(self.trap_marker_visible(), Flags::TRAP_MARKER)
+            | flag_if(self.antialias.adaptive, Flags::ADAPTIVE_ANTIALIAS);
         FragmentConstants {
             flags,
-            viewport_translate: self.viewport_translate.as_vec2(),
+            viewport_translate: self.drawn_centre().as_vec2(),
             viewport_zoom: self.viewport_zoom as f32,
             size: self.size.into(),
             algorithm: self.algorithm,
@@ -120,6 +219,85 @@ impl Controller {
             inspector_point_pixel_address: self
                 .complex_point_to_pixel(&self.inspector.position)
                 .as_vec2(),
+            julia_parameter: self.julia.parameter.as_vec2(),
+            reference_orbit_len: if perturbed {
+                self.reference.orbit.len() as u32
+            } else {
+                0
+            },
+            newton_roots: self.newton_roots,
+            phoenix_parameter: self.phoenix_parameter,
+            escape_radius: self.escape_radius,
+            bailout: self.bailout,
+            trap: self.trap.shape,
+            trap_point: self.trap.point,
+            trap_radius: self.trap.radius,
+            grid_size: self.grid_size,
+            grid_origin: self.grid_origin,
+            pan: IVec2::ZERO,
+            preview_offset: Vec2::ZERO,
+            preview_scale: 1.0,
+            slice_start: 0,
+            slice_end: 0,
+            sample_slice: self.sample_slice(),
+            antialias: self.antialias.pattern,
+        }
+    }
+
+    /// Pans the view by a distance in pixels.
+    ///
+    /// The view moves exactly that far. The cache only moves by whole pixels, so that the shader
+    /// only has to iterate the strips it exposes: the remainder is carried over, and the cached
+    /// view is drawn until it adds up to a whole pixel.
+    fn pan(&mut self, pixels: DVec2) {
+        self.viewport_translate += self.pixels_to_complex(pixels);
+        self.pan_remainder += pixels;
+    }
+
+    /// Moves the cache by the whole pixels of the pan remainder.
+    /// Returns how far it moved, for [`FragmentConstants::pan`].
+    fn apply_pan(&mut self) -> IVec2 {
+        if self.reiterate || self.zoom_preview.is_some() {
+            // The view is drawn afresh, or mapped onto the cache, where it now is
+            self.pan_remainder = DVec2::ZERO;
+            return IVec2::ZERO;
+        }
+        let whole = self.pan_remainder.trunc();
+        self.pan_remainder -= whole;
+        whole.as_ivec2()
+    }
+
+    /// The centre of the view which the cache holds, and the shader draws.
+    /// This is where the view is, less the pan remainder.
+    fn drawn_centre(&self) -> BigVec2 {
+        self.viewport_translate.clone() - self.pixels_to_complex(self.pan_remainder)
+    }
+
+    /// A distance in pixels, in the complex plane, to the full precision of the view
+    fn pixels_to_complex(&self, pixels: DVec2) -> BigVec2 {
+        let precision = self.viewport_translate.precision().x as usize;
+        BigVec2::try_from(pixels).unwrap().with_precision(precision) * self.pixel_complex_size()
+    }
+
+    /// Requests a new iteration pass when the palette starts using data which the shader
+    /// only collects on demand (so the cached results don't have it).
+    fn check_orbit_data(&mut self) {
+        let wanted = OrbitData {
+            trap: self.palette.uses_trap(),
+            averages: self.palette.uses_averages(),
+            interior: self.palette.uses_interior(),
+        };
+        if wanted != self.orbit_data {
+            self.orbit_data = wanted;
+            self.reiterate = true;
+        }
+    }
+
+    /// The user-defined algorithms, as sent to the GPU
+    fn custom(&self) -> shader::fractal::Custom<'_> {
+        shader::fractal::Custom {
+            formula: self.formula.code(),
+            hybrid: &self.hybrid.steps,
         }
     }
 }
@@ -185,6 +363,36 @@ impl From<Exponent> for PushExponent {
     }
 }
 
+impl From<PushExponent> for Exponent {
+    fn from(exp: PushExponent) -> Self {
+        match exp.typ {
+            NumericType::Integer => Exponent {
+                int: exp.int.unsigned_abs(),
+                real: exp.int.unsigned_abs() as f32,
+                real_is_negative: exp.int < 0,
+                ..Default::default()
+            },
+            NumericType::Float => Exponent {
+                int: exp.real.abs().round() as u32,
+                real: exp.real.abs(),
+                imag: 0.0,
+                typ: NumericType::Float,
+                real_is_negative: exp.real < 0.,
+                imag_is_negative: false,
+            },
+            NumericType::Complex => Exponent {
+                int: exp.real.abs().round() as u32,
+                real: exp.real.abs(),
+                imag: exp.imag.abs(),
+                typ: NumericType::Complex,
+                real_is_negative: exp.real < 0.,
+                imag_is_negative: exp.imag < 0.,
+            },
+            _ => todo!(),
+        }
+    }
+}
+
 #[derive(Default)]
 struct Movement {
     translate: DVec2,
@@ -207,13 +415,72 @@ impl ControllerTrait for Controller {
 
     fn prepare_render(
         &mut self,
-        _gfx_ctx: &GraphicsContext,
+        gfx_ctx: &GraphicsContext,
         _offset: Vec2,
     ) -> impl bytemuck::NoUninit {
+        self.check_orbit_data();
+        // The histogram works on the grid as the last frame left it
+        let new_stats = self.palette.adapts_to_view()
+            && self.histogram.update(
+                gfx_ctx,
+                self.size,
+                self.grid_size,
+                self.grid_origin,
+                self.palette.colour_style,
+            );
+        let mut pan = self.apply_pan();
+        let preview = self.zoom_preview();
+        if preview.is_some() {
+            // The cache stays where it is, for the preview to read
+            pan = IVec2::ZERO;
+        } else {
+            // Move the cache with the view
+            self.grid_origin = (self.grid_origin.as_ivec2() + pan)
+                .rem_euclid(self.grid_size.as_ivec2())
+                .as_uvec2();
+        }
         let reiterate = self.reiterate;
-        self.inspector.stale = reiterate;
+        let slice = self.next_slice(reiterate, preview.is_some());
+        let grid_changed =
+            reiterate || pan != IVec2::ZERO || (slice.is_some() && preview.is_none());
+        let moved = grid_changed || preview.is_some();
+        if moved && self.perturbed() {
+            self.update_reference_orbit();
+            self.upload_reference_orbit(&gfx_ctx.queue);
+        }
+        self.formula.upload(&gfx_ctx.queue);
+        self.hybrid.upload(&gfx_ctx.queue);
+        let new_gradient = self.gradient.upload(&gfx_ctx.queue);
+        let resample = self
+            .antialias
+            .resample(&self.palette, new_stats || new_gradient);
+        if grid_changed {
+            self.histogram.invalidate();
+        }
+        self.inspector.stale = moved;
         self.reiterate = false;
-        self.fragment_constants(reiterate)
+        let mut constants = FragmentConstants {
+            pan,
+            ..self.fragment_constants(reiterate)
+        };
+        constants.flags |= flag_if(resample, Flags::RESAMPLE);
+        if let Some((offset, scale)) = preview {
+            constants.flags |= Flags::ZOOM_PREVIEW;
+            constants.preview_offset = offset;
+            constants.preview_scale = scale;
+        }
+        if let Some((start, end)) = slice {
+            constants.flags |= Flags::PROGRESSIVE;
+            constants.slice_start = start;
+            constants.slice_end = end;
+        }
+        // Only part of the constants fits the push constants: the rest goes in a buffer
+        if let Some(buffer) = &self.view_buffer {
+            gfx_ctx
+                .queue
+                .write_buffer(buffer, 0, bytemuck::bytes_of(&constants.view()));
+        }
+        constants.push()
     }
 
     fn describe_bind_groups(
@@ -243,24 +510,174 @@ impl ControllerTrait for Controller {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("bind_group_layout"),
         });
 
-        use wgpu::util::DeviceExt;
-        let render_data_buffer_a = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("render_data_buffer_a"),
-            usage: wgpu::BufferUsages::STORAGE,
-            contents: &[0; std::mem::size_of::<PointResultA>()
-                * GRID_SIZE.x as usize
-                * GRID_SIZE.y as usize],
+        // The grids cover the window with some headroom for resizing, as far as the device allows.
+        // (Its limits are the ones the runner asked the adapter for when it created it.)
+        let limits = device.limits();
+        let window = uvec2(gfx_ctx.config.width, gfx_ctx.config.height);
+        let max_binding_size =
+            u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+        self.grid_size = grid_size_for(window, max_binding_size);
+        if self.grid_size.cmplt(window).any() {
+            log::warn!(
+                "The device allows storage buffers of only {} MB, so the cache covers {}x{} pixels of the {}x{} window; the rest are iterated in every frame",
+                max_binding_size >> 20,
+                self.grid_size.x,
+                self.grid_size.y,
+                window.x,
+                window.y,
+            );
+        }
+        self.grid_origin = UVec2::ZERO;
+        self.reiterate = true;
+        let grid_points = u64::from(self.grid_size.x) * u64::from(self.grid_size.y);
+        // wgpu zeroes new buffers, which the shader reads as empty cells
+        let grid_buffer = |label, element_size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: element_size as u64 * grid_points,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            })
+        };
+        let render_data_buffer_a =
+            grid_buffer("render_data_buffer_a", std::mem::size_of::<PointResultA>());
+        let render_data_buffer_b =
+            grid_buffer("render_data_buffer_b", std::mem::size_of::<PointResultB>());
+        let render_data_buffer_c =
+            grid_buffer("render_data_buffer_c", std::mem::size_of::<PointResultC>());
+        let orbit_state_buffer =
+            grid_buffer("orbit_state_buffer", std::mem::size_of::<OrbitState>());
+        let supersamples_buffer =
+            grid_buffer("supersamples_buffer", std::mem::size_of::<Supersamples>());
+        let reference_orbit_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("reference_orbit_buffer"),
+            size: perturbation::ReferenceOrbit::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let formula_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("formula_buffer"),
+            size: formula::Formula::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
-        let render_data_buffer_b = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("render_data_buffer_b"),
-            usage: wgpu::BufferUsages::STORAGE,
-            contents: &[0; std::mem::size_of::<PointResultB>()
-                * GRID_SIZE.x as usize
-                * GRID_SIZE.y as usize],
+        let hybrid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hybrid_buffer"),
+            size: hybrid::Hybrid::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let gradient_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gradient_buffer"),
+            size: gradient::Gradient::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let stats_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("stats_buffer"),
+            size: histogram::Histogram::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("view_buffer"),
+            size: std::mem::size_of::<ViewParameters>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -274,9 +691,52 @@ impl ControllerTrait for Controller {
                     binding: 1,
                     resource: render_data_buffer_b.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: reference_orbit_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: formula_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: hybrid_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: render_data_buffer_c.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: gradient_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: stats_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: orbit_state_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: supersamples_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: view_buffer.as_entire_binding(),
+                },
             ],
             label: Some("fractal_bind_group"),
         });
+        self.reference.set_buffer(reference_orbit_buffer);
+        self.formula.set_buffer(formula_buffer);
+        self.hybrid.set_buffer(hybrid_buffer);
+        self.gradient.set_buffer(gradient_buffer);
+        self.view_buffer = Some(view_buffer);
+        self.histogram
+            .set_buffers(device, &render_data_buffer_a, stats_buffer);
         (vec![layout], vec![bind_group])
     }
 
@@ -299,6 +759,7 @@ impl ControllerTrait for Controller {
             MouseButton::Left => {
                 self.dragging = pressed;
                 self.inspector.dragging = pressed && self.mouse_on_marker();
+                self.trap.dragging = pressed && !self.inspector.dragging && self.mouse_on_trap();
             }
             MouseButton::Right => {
                 if state == ElementState::Pressed {
@@ -316,13 +777,14 @@ impl ControllerTrait for Controller {
             self.inspector.position += self.pixel_address_to_complex(self.mouse_position)
                 - self.pixel_address_to_complex(prev_position);
             self.inspector.stale = true;
-        } else if self.dragging {
-            let delta =
-                BigVec2::try_from((prev_position - self.mouse_position) / self.size.y as f64)
-                    .unwrap()
-                    .with_precision(PRECISION);
-            self.viewport_translate += delta * self.modifier_key_factor() / self.viewport_zoom;
+        } else if self.trap.dragging {
+            let delta = (self.pixel_address_to_complex(self.mouse_position)
+                - self.pixel_address_to_complex(prev_position))
+            .as_vec2();
+            self.trap.point += delta;
             self.reiterate = true;
+        } else if self.dragging {
+            self.pan((prev_position - self.mouse_position) * self.modifier_key_factor());
         }
     }
     fn mouse_scroll(&mut self, delta: DVec2) {
@@ -331,15 +793,17 @@ impl ControllerTrait for Controller {
         }
 
         let motion = delta.y * 0.1 * self.modifier_key_factor();
+        self.zooming();
         let position = self.mouse_position;
         let size = self.size.as_dvec2();
         let prev_zoom = self.viewport_zoom;
+        let max_zoom = self.max_zoom();
         let zoom = &mut self.viewport_zoom;
         let mouse_pos0 = BigVec2::try_from(position - size / 2.).unwrap() / *zoom / size.y;
-        *zoom = (prev_zoom * (1.0 + motion)).clamp(MIN_ZOOM, MAX_ZOOM);
+        *zoom = (prev_zoom * (1.0 + motion)).clamp(MIN_ZOOM, max_zoom);
         let mouse_pos1 = BigVec2::try_from(position - size / 2.).unwrap() / *zoom / size.y;
         self.viewport_translate += mouse_pos0 - mouse_pos1;
-        self.reiterate = true;
+        self.update_precision();
     }
 }
 
@@ -366,3 +830,54 @@ impl Controller {
             + 0.5 * size
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::Controller;
+    use crate::cli::Args;
+    use clap::Parser as _;
+    use glam::{dvec2, ivec2, uvec2, DVec2, IVec2};
+    use util::{parse_decimal, BigVec2};
+
+    /// How far `to` is from `from`, in pixels
+    fn pixels(controller: &Controller, from: &BigVec2, to: &BigVec2) -> DVec2 {
+        (to.clone() - from.clone()).as_dvec2() / controller.pixel_complex_size()
+    }
+
+    #[test]
+    fn fractional_drags() {
+        // The view follows every fraction of a drag, while the cache moves by whole pixels
+        // and the pixels it draws stay where the cache has them
+        let mut controller = Controller::new(&Args::parse_from(["brot3"])).unwrap();
+        controller.viewport_translate = BigVec2::new(
+            parse_decimal("-1.7490975056", 300).unwrap(),
+            parse_decimal("0.0000000315", 300).unwrap(),
+        );
+        controller.viewport_zoom = 1e30;
+        controller.size = uvec2(800, 600);
+        controller.reiterate = false;
+        let start = controller.viewport_translate.clone();
+        let mut moved = IVec2::ZERO;
+        for _ in 0..3 {
+            controller.pan(dvec2(0.4, -0.3));
+            moved += controller.apply_pan();
+        }
+        assert_eq!(moved, ivec2(1, 0));
+        let view = pixels(&controller, &start, &controller.viewport_translate);
+        let drawn = pixels(&controller, &start, &controller.drawn_centre());
+        assert!(
+            (view - dvec2(1.2, -0.9)).abs().max_element() < 1e-9,
+            "{view}"
+        );
+        assert!(
+            (drawn - dvec2(1., 0.)).abs().max_element() < 1e-9,
+            "{drawn}"
+        );
+
+        // Iterating the view afresh puts the pixels where the view is
+        controller.reiterate = true;
+        assert_eq!(controller.apply_pan(), IVec2::ZERO);
+        assert_eq!(controller.drawn_centre(), controller.viewport_translate);
+    }
+}
@@ -0,0 +1,116 @@
+//! Deep zoom by perturbation
+// (c) 2025 Ross Younger
+
+use easy_shader_runner::wgpu;
+use glam::Vec2;
+use shader_common::REFERENCE_ORBIT_SIZE;
+use util::{BigComplex, BigVec2};
+
+/// Above this zoom level, we render by perturbation if the fractal supports it
+const PERTURBATION_MIN_ZOOM: f64 = 1000.;
+/// The deepest zoom we allow when rendering by perturbation.
+/// This is limited by the range of `f32`, in which the shader computes the deltas.
+const MAX_PERTURBED_ZOOM: f64 = 1e30;
+/// Extra bits of precision we carry beyond the size of a pixel
+const GUARD_BITS: usize = 32;
+
+/// High precision reference orbit, and its GPU buffer
+#[derive(Default)]
+pub(super) struct ReferenceOrbit {
+    /// The point the orbit was computed for
+    centre: BigVec2,
+    /// The iteration limit the orbit was computed for
+    max_iter: u32,
+    /// Orbit data, as sent to the GPU
+    pub(super) orbit: Vec<Vec2>,
+    /// GPU copy of the orbit (created by `describe_bind_groups`)
+    pub(super) buffer: Option<wgpu::Buffer>,
+    /// Does the GPU copy need updating?
+    upload: bool,
+}
+
+impl ReferenceOrbit {
+    /// Size in bytes of the GPU buffer
+    pub(super) const BUFFER_SIZE: u64 =
+        REFERENCE_ORBIT_SIZE as u64 * std::mem::size_of::<Vec2>() as u64;
+
+    pub(super) fn set_buffer(&mut self, buffer: wgpu::Buffer) {
+        self.buffer = Some(buffer);
+        self.upload = true;
+    }
+}
+
+impl super::Controller {
+    /// Can the current fractal be rendered by perturbation?
+    fn supports_perturbation(&self) -> bool {
+        // The reference orbit is always computed for the parameter plane
+        !self.julia.active
+            && shader::fractal::supports_perturbation(self.algorithm, &self.exponent.into())
+    }
+
+    /// Are we rendering by perturbation?
+    pub(super) fn perturbed(&self) -> bool {
+        self.viewport_zoom > PERTURBATION_MIN_ZOOM && self.supports_perturbation()
+    }
+
+    /// The deepest zoom level the current fractal supports
+    pub(super) fn max_zoom(&self) -> f64 {
+        if self.supports_perturbation() {
+            MAX_PERTURBED_ZOOM
+        } else {
+            super::MAX_ZOOM
+        }
+    }
+
+    /// Number of bits of precision we need to address individual pixels at the current zoom level
+    pub(super) fn precision(&self) -> usize {
+        #![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let pixel_bits = -self.pixel_complex_size().log2();
+        // N.B. the cast saturates, so infinities and NaNs come out as 0 or usize::MAX.
+        (pixel_bits.ceil() as usize)
+            .saturating_add(GUARD_BITS)
+            .max(super::PRECISION)
+    }
+
+    /// Ensures the viewport centre is held to sufficient precision for the zoom level
+    pub(super) fn update_precision(&mut self) {
+        let precision = self.precision();
+        if (self.viewport_translate.precision().x as usize) < precision {
+            self.viewport_translate =
+                std::mem::take(&mut self.viewport_translate).with_precision(precision);
+        }
+    }
+
+    /// Recomputes the reference orbit, if the view has changed
+    pub(super) fn update_reference_orbit(&mut self) {
+        // The shader draws the pixels around the centre of the cached view
+        let centre = self.drawn_centre();
+        let reference = &mut self.reference;
+        if reference.max_iter == self.max_iter
+            && reference.centre == centre
+            && !reference.orbit.is_empty()
+        {
+            return;
+        }
+        reference.centre = centre;
+        reference.max_iter = self.max_iter;
+        reference.orbit = util::reference_orbit(
+            &BigComplex::from(reference.centre.clone()),
+            self.max_iter,
+            shader::fractal::REFERENCE_ESCAPE_SQ,
+        );
+        reference.upload = true;
+    }
+
+    /// Sends the reference orbit to the GPU, if it has changed
+    pub(super) fn upload_reference_orbit(&mut self, queue: &wgpu::Queue) {
+        let reference = &mut self.reference;
+        if let Some(buffer) = &reference.buffer
+            && reference.upload
+        {
+            debug_assert!(reference.orbit.len() <= REFERENCE_ORBIT_SIZE as usize);
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&reference.orbit));
+            reference.upload = false;
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Instant previews while zooming, resampled from the cache
+// (c) 2025 Ross Younger
+
+use glam::Vec2;
+use shader_common::FragmentConstants;
+use util::BigVec2;
+use web_time::Instant;
+
+/// How long the zoom must be still before we iterate the view properly, in seconds
+const SETTLE_TIME: f64 = 0.25;
+/// How far the view may zoom from the cached one (either way) before the preview is too coarse to use
+const MAX_PREVIEW_SCALE: f64 = 2.0;
+
+/// The view the cache grids hold, while a zoom preview is showing
+pub(super) struct CachedView {
+    translate: BigVec2,
+    zoom: f64,
+    /// When the zoom last changed
+    last_change: Instant,
+}
+
+impl super::Controller {
+    /// Notes that the zoom is about to change, so that the next frames can preview it from the cache.
+    ///
+    /// Call this before changing the view.
+    pub(super) fn zooming(&mut self) {
+        match &mut self.zoom_preview {
+            Some(cached) => cached.last_change = Instant::now(),
+            None => {
+                self.zoom_preview = Some(CachedView {
+                    translate: self.drawn_centre(),
+                    zoom: self.viewport_zoom,
+                    last_change: Instant::now(),
+                });
+            }
+        }
+    }
+
+    /// While the view is zooming, maps it onto the view the cache holds,
+    /// returning the offset and scale for [`Flags::ZOOM_PREVIEW`](shader_common::Flags::ZOOM_PREVIEW).
+    ///
+    /// Once the zoom settles, or the preview gets too coarse, requests that the view is iterated properly.
+    pub(super) fn zoom_preview(&mut self) -> Option<(Vec2, f32)> {
+        let cached = self.zoom_preview.as_ref()?;
+        // The ratio of the pixel spacings, new to old
+        let scale = cached.zoom / self.viewport_zoom;
+        if self.reiterate
+            || cached.last_change.elapsed().as_secs_f64() > SETTLE_TIME
+            || !(1. / MAX_PREVIEW_SCALE..=MAX_PREVIEW_SCALE).contains(&scale)
+        {
+            self.zoom_preview = None;
+            self.reiterate = true;
+            return None;
+        }
+        let cached_spacing = FragmentConstants::pixel_spacing_f64(self.size.y, cached.zoom);
+        let shift = (self.viewport_translate.clone() - cached.translate.clone()).as_dvec2()
+            / cached_spacing;
+        let centre = 0.5 * self.size.as_dvec2();
+        Some(((centre * (1. - scale) + shift).as_vec2(), scale as f32))
+    }
+}
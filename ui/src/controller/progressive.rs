@@ -0,0 +1,76 @@
+//! Progressive rendering, which spreads the iterations of a view over several frames
+// (c) 2025 Ross Younger
+
+use easy_shader_runner::egui;
+
+/// How many iterations each frame takes the orbits through.
+/// This bounds the work in a frame, so that high iteration limits don't stall the GUI
+/// (or trip the GPU watchdog).
+const SLICE_ITERATIONS: u32 = 1000;
+
+impl super::Controller {
+    /// Can the view be rendered progressively?
+    fn progressive_supported(&self) -> bool {
+        self.progressive
+            && self.max_iter > SLICE_ITERATIONS
+            && !self.perturbed()
+            && shader::fractal::supports_progressive(
+                self.algorithm,
+                &self.palette,
+                self.formula.code(),
+            )
+    }
+
+    /// How many iterations a supersample may take in a frame: see
+    /// [`FragmentConstants::sample_slice`](shader_common::FragmentConstants::sample_slice).
+    /// Supersamples are bounded like the slices, if the view can be rendered progressively.
+    pub(super) fn sample_slice(&self) -> u32 {
+        if self.progressive_supported() {
+            SLICE_ITERATIONS
+        } else {
+            0
+        }
+    }
+
+    /// Chooses the iterations this frame covers, for [`Flags::PROGRESSIVE`](shader_common::Flags::PROGRESSIVE).
+    /// Returns `None` if the frame isn't part of a progressive render.
+    ///
+    /// The first slice, which starts when the whole view is to be iterated afresh, serves as a coarse preview:
+    /// the points which haven't escaped yet are drawn as inside the set.
+    /// While previewing a zoom, the pixels which the cache doesn't cover get only that far.
+    pub(super) fn next_slice(&mut self, reiterate: bool, previewing: bool) -> Option<(u32, u32)> {
+        let supported = self.progressive_supported();
+        if previewing {
+            // The preview leaves the cache alone, and the view is iterated afresh once the zoom settles
+            self.next_slice = None;
+            return supported.then_some((0, SLICE_ITERATIONS));
+        }
+        if reiterate {
+            self.next_slice = supported.then_some(0);
+        }
+        let start = self.next_slice?;
+        let end = start.saturating_add(SLICE_ITERATIONS).min(self.max_iter);
+        self.next_slice = (end < self.max_iter).then_some(end);
+        Some((start, end))
+    }
+
+    /// Shows how far a progressive render has got, while it is under way
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn progress_window(&self, ctx: &egui::Context) {
+        let Some(done) = self.next_slice else {
+            return;
+        };
+        egui::Window::new("progress")
+            .title_bar(false)
+            .resizable(false)
+            .interactable(false)
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10., -10.))
+            .show(ctx, |ui| {
+                ui.add(
+                    egui::ProgressBar::new(done as f32 / self.max_iter as f32)
+                        .desired_width(200.)
+                        .text(format!("Iterations: {done} / {}", self.max_iter)),
+                );
+            });
+    }
+}
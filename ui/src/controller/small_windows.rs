@@ -90,6 +90,15 @@ impl super::Controller {
                     self.inspector.stale = true;
                     self.show_coords_window = true;
                 }
+                if self.julia.active {
+                    if ui.button("Back to parameter plane").clicked() {
+                        self.set_julia(false);
+                        self.context_menu = None;
+                    }
+                } else if ui.button("Julia set here").clicked() {
+                    self.julia_at(&self.pixel_address_to_complex(pos));
+                    self.context_menu = None;
+                }
             });
         if let Some(r) = r
             && r.response.clicked_elsewhere()
@@ -98,4 +107,21 @@ impl super::Controller {
             self.context_menu = None;
         }
     }
+
+    pub(crate) fn error_modal(&mut self, ctx: &egui::Context) {
+        let message = self.error_message.as_deref().unwrap_or_default();
+        let mut dismissed = false;
+        if egui::Modal::new("error".into())
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new("Error").size(18.));
+                ui.label(message);
+                ui.add_space(6.);
+                dismissed = ui.button("OK").clicked();
+            })
+            .should_close()
+            || dismissed
+        {
+            self.error_message = None;
+        }
+    }
 }
@@ -0,0 +1,50 @@
+//! Orbit trap settings
+// (c) 2025 Ross Younger
+
+use glam::Vec2;
+use shader_common::{enums::OrbitTrap, FragmentConstants, INSPECTOR_MARKER_SIZE};
+use util::BigVec2;
+
+/// Orbit trap state
+pub(super) struct Trap {
+    pub(super) shape: OrbitTrap,
+    /// Position of [`OrbitTrap::UserPoint`]
+    pub(super) point: Vec2,
+    /// Radius of [`OrbitTrap::Circle`]
+    pub(super) radius: f32,
+    /// Is the user dragging the trap marker?
+    pub(super) dragging: bool,
+}
+
+impl Default for Trap {
+    fn default() -> Self {
+        Self {
+            shape: OrbitTrap::default(),
+            point: FragmentConstants::DEFAULT_TRAP_POINT,
+            radius: FragmentConstants::DEFAULT_TRAP_RADIUS,
+            dragging: false,
+        }
+    }
+}
+
+impl super::Controller {
+    /// Is the orbit trap marker on screen?
+    ///
+    /// Only a user-placed trap has a marker, and only when it affects the colouring.
+    pub(super) fn trap_marker_visible(&self) -> bool {
+        self.show_controls && self.trap.shape == OrbitTrap::UserPoint && self.palette.uses_trap()
+    }
+
+    /// Is the mouse over the orbit trap marker?
+    pub(super) fn mouse_on_trap(&self) -> bool {
+        if !self.trap_marker_visible() {
+            return false;
+        }
+        let Ok(point) = BigVec2::try_from(self.trap.point.as_dvec2()) else {
+            return false;
+        };
+        self.mouse_position
+            .distance_squared(self.complex_point_to_pixel(&point.with_precision(super::PRECISION)))
+            < f64::from(INSPECTOR_MARKER_SIZE) * f64::from(INSPECTOR_MARKER_SIZE)
+    }
+}
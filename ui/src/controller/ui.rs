@@ -39,6 +39,7 @@ impl super::Controller {
         if self.show_fps {
             self.fps_window(ctx, ui_state);
         }
+        self.progress_window(ctx);
         if self.keyboard_help {
             self.keyboard_help_window(ctx);
         }
@@ -48,6 +49,9 @@ impl super::Controller {
         if self.show_license {
             self.license_modal(ctx);
         }
+        if self.error_message.is_some() {
+            self.error_modal(ctx);
+        }
 
         self.resized = false;
         self.set_mouse_pointer(ctx);
@@ -58,15 +62,17 @@ impl super::Controller {
         self.last_instant = Instant::now();
         let factor = self.modifier_key_factor();
         let factor32 = factor as f32;
-        let movement = &mut self.movement;
-        if movement.zoom != 0.0 {
-            self.viewport_zoom *= (movement.zoom - 1.0) * factor * dt + 1.0;
-            self.reiterate = true;
+        if self.movement.zoom != 0.0 {
+            self.zooming();
+            self.viewport_zoom *= (self.movement.zoom - 1.0) * factor * dt + 1.0;
+            self.viewport_zoom = self.viewport_zoom.min(self.max_zoom());
+            self.update_precision();
         }
-        if movement.translate != DVec2::ZERO {
-            self.viewport_translate += movement.translate * factor / self.viewport_zoom * dt;
-            self.reiterate = true;
+        if self.movement.translate != DVec2::ZERO {
+            let distance = self.movement.translate * factor / self.viewport_zoom * dt;
+            self.pan(distance / self.pixel_complex_size());
         }
+        let movement = &mut self.movement;
         if movement.exponent != 0. {
             let new_exp = (self.exponent.real + factor32 * movement.exponent)
                 .clamp(Self::EXPONENT_MIN, Self::EXPONENT_MAX);
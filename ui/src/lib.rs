@@ -36,7 +36,15 @@ pub fn main() -> anyhow::Result<()> {
         println!("{}", version_string("brot3 "));
         return Ok(());
     }
-    let controller = controller::Controller::new(&args);
+    #[cfg_attr(wasm, allow(unused_mut))]
+    let mut controller = controller::Controller::new(&args)?;
+    #[cfg(not(wasm))]
+    if let Some(path) = &args.load {
+        use anyhow::Context as _;
+        controller
+            .load_location(path)
+            .with_context(|| format!("loading {}", path.display()))?;
+    }
     let params = easy_shader_runner::Parameters::new(controller, version_string("brot3 "))
         .esc_key_exits(false);
     cfg_if::cfg_if! {
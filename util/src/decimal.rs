@@ -0,0 +1,142 @@
+//! Exact conversion of arbitrary precision numbers to and from decimal strings
+
+use dashu::base::{DivRem as _, ParseError};
+use dashu::float::{DBig, FBig};
+use dashu::integer::IBig;
+use std::str::FromStr as _;
+
+/// Formats a number in decimal, exactly.
+///
+/// Every binary fraction has a finite decimal expansion, so no digits are lost:
+/// [`parse_decimal`] recovers the identical value from the output.
+///
+/// ```
+/// # use util::{parse_decimal, to_decimal_string};
+/// let x = dashu::float::FBig::try_from(-0.375f64).unwrap();
+/// assert_eq!(to_decimal_string(&x), "-0.375");
+/// assert_eq!(parse_decimal("-0.375", 0).unwrap(), x);
+/// ```
+#[must_use]
+pub fn to_decimal_string(x: &FBig) -> String {
+    let repr = x.repr();
+    if repr.is_infinite() {
+        return x.to_string();
+    }
+    let significand = repr.significand().clone();
+    let exponent = repr.exponent();
+    let decimal = if exponent >= 0 {
+        DBig::from_parts(significand << exponent.unsigned_abs(), 0)
+    } else {
+        // m * 2^-k == m * 5^k * 10^-k
+        let five_k = IBig::from(5).pow(exponent.unsigned_abs());
+        DBig::from_parts(significand * five_k, exponent)
+    };
+    decimal.to_string()
+}
+
+/// Parses a number from a decimal string.
+///
+/// The result is exact if the value is representable in binary, which is always the case
+/// for the output of [`to_decimal_string`]. Otherwise it is rounded, keeping at least as many
+/// bits as there were decimal digits in the input.
+///
+/// The result has at least `min_precision` bits of precision.
+pub fn parse_decimal(s: &str, min_precision: usize) -> Result<FBig, ParseError> {
+    let decimal = DBig::from_str(s.trim())?;
+    let repr = decimal.repr();
+    let exponent = repr.exponent();
+    let result = if exponent >= 0 {
+        FBig::from_parts(
+            repr.significand() * IBig::from(10).pow(exponent.unsigned_abs()),
+            0,
+        )
+    } else {
+        // m * 10^-k == (m / 5^k) * 2^-k, which is exact if 5^k divides m
+        let five_k = IBig::from(5).pow(exponent.unsigned_abs());
+        let (quotient, remainder) = repr.significand().div_rem(&five_k);
+        if remainder == IBig::ZERO {
+            FBig::from_parts(quotient, exponent)
+        } else {
+            // log2(10) < 4
+            let precision = (decimal.digits() * 4).max(min_precision);
+            decimal
+                .with_base_and_precision::<2>(precision)
+                .value()
+                .with_rounding()
+        }
+    };
+    if result.precision() < min_precision {
+        Ok(result.with_precision(min_precision).value())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{parse_decimal, to_decimal_string};
+    use dashu::float::FBig;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn simple() {
+        for (f, s) in [
+            (0.0, "0"),
+            (1.0, "1"),
+            (-2.0, "-2"),
+            (1024.0, "1024"),
+            (0.5, "0.5"),
+            (-0.75, "-0.75"),
+            (2.0f64.powi(-10), "0.0009765625"),
+        ] {
+            let x = FBig::try_from(f).unwrap();
+            assert_eq!(to_decimal_string(&x), s);
+            assert_eq!(parse_decimal(s, 0).unwrap(), x);
+        }
+    }
+
+    #[test]
+    fn round_trip_every_digit() {
+        // A number with many more significant bits than an f64
+        let tiny = FBig::try_from(2.0f64.powi(-300)).unwrap();
+        let x = (FBig::try_from(-0.743_643_887_037_158_7)
+            .unwrap()
+            .with_precision(400)
+            .value()
+            + tiny)
+            / FBig::from(3);
+        assert_eq!(x.precision(), 400);
+        let s = to_decimal_string(&x);
+        assert!(s.len() > 300);
+        let y = parse_decimal(&s, 128).unwrap();
+        assert_eq!(x, y);
+        assert_eq!(to_decimal_string(&y), s);
+        assert_eq!(parse_decimal(&s, 400).unwrap().precision(), 400);
+    }
+
+    #[test]
+    fn inexact() {
+        // 0.1 is not representable in binary
+        let x = parse_decimal("0.1", 128).unwrap();
+        assert_eq!(x.precision(), 128);
+        assert!((x.to_f64().value() - 0.1).abs() < 1e-17);
+        // Precision is raised to fit the digits given
+        let long = "0.1234567890123456789012345678901234567890123456789";
+        assert!(parse_decimal(long, 64).unwrap().precision() >= 160);
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!(parse_decimal("3", 200).unwrap().precision(), 200);
+        let expected: FBig = FBig::try_from(-1.5).unwrap();
+        assert_eq!(parse_decimal("-1.5", 0).unwrap(), expected);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_decimal("", 64).is_err());
+        assert!(parse_decimal("one", 64).is_err());
+        assert!(parse_decimal("1.2.3", 64).is_err());
+    }
+}
@@ -0,0 +1,553 @@
+//! Compiler for user-defined iteration formulas
+//!
+//! The output is bytecode for the shader's interpreter; see [`shader_common::formula`].
+
+use shader_common::formula::{op, FORMULA_HEADER_SIZE, FORMULA_SIZE, FORMULA_STACK_SIZE};
+
+/// An error found while compiling a formula
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormulaError {
+    /// Position of the problem in the source, in characters counting from 0
+    pub position: usize,
+    /// What went wrong
+    pub message: String,
+}
+
+impl FormulaError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+/// Compiles a formula to shader bytecode.
+///
+/// The formula gives the next point of the orbit in terms of:
+/// * `z`, the current point;
+/// * `c`, the parameter (the pixel, or the Julia parameter in Julia mode);
+/// * `n`, the number of iterations so far;
+/// * numbers, which may be imaginary (`2.5i`), and `i`.
+///
+/// The operators are `+ - * / ^` with the usual precedence (`^` is right-associative),
+/// and the functions are `pow(a, b)`, `abs` (of the real and imaginary parts separately),
+/// `re`, `im`, `conj`, `sin`, `cos`, `tan`, `sinh`, `cosh`, `exp`, `log` (or `ln`) and `sqrt`.
+///
+/// ```
+/// let program = util::compile_formula("z^2 + c").unwrap();
+/// assert!(util::compile_formula("z^2 +").is_err());
+/// ```
+///
+/// # Errors
+/// If the formula cannot be parsed, or is too large for the shader.
+pub fn compile(source: &str) -> Result<Vec<u32>, FormulaError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        index: 0,
+        end: source.chars().count(),
+    };
+    let expr = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(FormulaError::new(
+            token.position,
+            "expected an operator or the end of the formula",
+        ));
+    }
+    if expr.stack_depth() > FORMULA_STACK_SIZE {
+        return Err(FormulaError::new(0, "formula is too deeply nested"));
+    }
+    let mut program = vec![0, 0];
+    expr.emit(&mut program);
+    if program.len() > FORMULA_SIZE as usize {
+        return Err(FormulaError::new(0, "formula is too long"));
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        program[0] = (program.len() - FORMULA_HEADER_SIZE) as u32;
+    }
+    // Anything which isn't a polynomial in z is smoothed as if it were quadratic
+    let degree = expr.degree().filter(|d| *d >= 1.0).unwrap_or(2.0);
+    program[1] = degree.to_bits();
+    Ok(program)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Number(f32),
+    Imaginary(f32),
+    Name(String),
+    Symbol(char),
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, FormulaError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        let start = i;
+        if ch.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let kind = if ch.is_ascii_digit() || ch == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // exponent
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value: f32 = text
+                .parse()
+                .map_err(|_| FormulaError::new(start, format!("invalid number '{text}'")))?;
+            // An `i` suffix makes it imaginary, provided it isn't the start of a longer name
+            if i < chars.len()
+                && chars[i] == 'i'
+                && !chars.get(i + 1).is_some_and(|c| c.is_alphanumeric())
+            {
+                i += 1;
+                TokenKind::Imaginary(value)
+            } else {
+                TokenKind::Number(value)
+            }
+        } else if ch.is_alphabetic() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Name(chars[start..i].iter().collect())
+        } else {
+            i += 1;
+            match ch {
+                '+' | '-' | '*' | '/' | '^' | '(' | ')' | ',' => TokenKind::Symbol(ch),
+                '−' => TokenKind::Symbol('-'),
+                '×' => TokenKind::Symbol('*'),
+                '÷' => TokenKind::Symbol('/'),
+                _ => return Err(FormulaError::new(start, format!("unexpected '{ch}'"))),
+            }
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Syntax tree
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Z,
+    C,
+    N,
+    Const(f32, f32),
+    PowI(Box<Expr>, i32),
+    Unary(u32, Box<Expr>),
+    Binary(u32, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Number of stack entries needed to evaluate this expression
+    fn stack_depth(&self) -> usize {
+        match self {
+            Expr::Z | Expr::C | Expr::N | Expr::Const(..) => 1,
+            Expr::PowI(a, _) | Expr::Unary(_, a) => a.stack_depth(),
+            Expr::Binary(_, a, b) => a.stack_depth().max(b.stack_depth() + 1),
+        }
+    }
+
+    fn emit(&self, program: &mut Vec<u32>) {
+        match self {
+            Expr::Z => program.push(op::Z),
+            Expr::C => program.push(op::C),
+            Expr::N => program.push(op::N),
+            Expr::Const(re, im) => program.extend([op::CONST, re.to_bits(), im.to_bits()]),
+            Expr::PowI(a, k) => {
+                a.emit(program);
+                #[allow(clippy::cast_sign_loss)]
+                program.extend([op::POWI, *k as u32]);
+            }
+            Expr::Unary(opcode, a) => {
+                a.emit(program);
+                program.push(*opcode);
+            }
+            Expr::Binary(opcode, a, b) => {
+                a.emit(program);
+                b.emit(program);
+                program.push(*opcode);
+            }
+        }
+    }
+
+    /// The degree of this expression as a polynomial in z, if it is one
+    #[allow(clippy::cast_precision_loss, clippy::float_cmp)]
+    fn degree(&self) -> Option<f32> {
+        match self {
+            Expr::Z => Some(1.0),
+            Expr::C | Expr::N | Expr::Const(..) => Some(0.0),
+            Expr::PowI(a, k) => match a.degree()? {
+                0.0 => Some(0.0),
+                d if *k >= 0 => Some(d * *k as f32),
+                _ => None,
+            },
+            Expr::Unary(opcode, a) => {
+                let d = a.degree()?;
+                match *opcode {
+                    op::NEG | op::ABS | op::RE | op::IM | op::CONJ => Some(d),
+                    _ if d == 0.0 => Some(0.0),
+                    _ => None,
+                }
+            }
+            Expr::Binary(opcode, a, b) => {
+                let (da, db) = (a.degree()?, b.degree()?);
+                match *opcode {
+                    op::ADD | op::SUB => Some(da.max(db)),
+                    op::MUL => Some(da + db),
+                    op::DIV if db == 0.0 => Some(da),
+                    op::POW => match **b {
+                        Expr::Const(re, 0.0) if re >= 0.0 => Some(da * re),
+                        _ if da == 0.0 && db == 0.0 => Some(0.0),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Recursive descent parser
+struct Parser<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    /// Position of the end of the source, for errors
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next_is(&self, symbol: char) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == TokenKind::Symbol(symbol))
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |t| t.position)
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), FormulaError> {
+        if self.next_is(symbol) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(FormulaError::new(
+                self.position(),
+                format!("expected '{symbol}'"),
+            ))
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, FormulaError> {
+        let mut lhs = self.term()?;
+        loop {
+            let opcode = if self.next_is('+') {
+                op::ADD
+            } else if self.next_is('-') {
+                op::SUB
+            } else {
+                return Ok(lhs);
+            };
+            self.index += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(opcode, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    /// term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let opcode = if self.next_is('*') {
+                op::MUL
+            } else if self.next_is('/') {
+                op::DIV
+            } else {
+                return Ok(lhs);
+            };
+            self.index += 1;
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(opcode, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    /// unary := ('-' | '+') unary | power
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        if self.next_is('-') {
+            self.index += 1;
+            return Ok(match self.unary()? {
+                Expr::Const(re, im) => Expr::Const(-re, -im),
+                e => Expr::Unary(op::NEG, Box::new(e)),
+            });
+        }
+        if self.next_is('+') {
+            self.index += 1;
+            return self.unary();
+        }
+        self.power()
+    }
+
+    /// power := atom ('^' unary)?
+    fn power(&mut self) -> Result<Expr, FormulaError> {
+        let base = self.atom()?;
+        if !self.next_is('^') {
+            return Ok(base);
+        }
+        self.index += 1;
+        let exponent = self.unary()?;
+        Ok(make_power(base, exponent))
+    }
+
+    fn atom(&mut self) -> Result<Expr, FormulaError> {
+        let position = self.position();
+        let Some(token) = self.peek().cloned() else {
+            return Err(FormulaError::new(position, "unexpected end of formula"));
+        };
+        self.index += 1;
+        match token.kind {
+            TokenKind::Number(v) => Ok(Expr::Const(v, 0.0)),
+            TokenKind::Imaginary(v) => Ok(Expr::Const(0.0, v)),
+            TokenKind::Symbol('(') => {
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            TokenKind::Symbol(ch) => Err(FormulaError::new(position, format!("unexpected '{ch}'"))),
+            TokenKind::Name(name) => match name.as_str() {
+                "z" => Ok(Expr::Z),
+                "c" => Ok(Expr::C),
+                "n" => Ok(Expr::N),
+                "i" => Ok(Expr::Const(0.0, 1.0)),
+                "pow" => {
+                    self.expect('(')?;
+                    let base = self.expr()?;
+                    self.expect(',')?;
+                    let exponent = self.expr()?;
+                    self.expect(')')?;
+                    Ok(make_power(base, exponent))
+                }
+                _ => {
+                    let opcode = match name.as_str() {
+                        "abs" => op::ABS,
+                        "re" => op::RE,
+                        "im" => op::IM,
+                        "conj" => op::CONJ,
+                        "sin" => op::SIN,
+                        "cos" => op::COS,
+                        "tan" => op::TAN,
+                        "sinh" => op::SINH,
+                        "cosh" => op::COSH,
+                        "exp" => op::EXP,
+                        "log" | "ln" => op::LOG,
+                        "sqrt" => op::SQRT,
+                        _ => {
+                            return Err(FormulaError::new(
+                                position,
+                                format!("unknown name '{name}'"),
+                            ));
+                        }
+                    };
+                    self.expect('(')?;
+                    let arg = self.expr()?;
+                    self.expect(')')?;
+                    Ok(Expr::Unary(opcode, Box::new(arg)))
+                }
+            },
+        }
+    }
+}
+
+/// Integer powers get an opcode of their own. The shader raises to them in polar form, as the
+/// built-in algorithms do (squares excepted, which are a multiplication): unlike going by way
+/// of the logarithm, this is defined at zero and needs no branch cut.
+fn make_power(base: Expr, exponent: Expr) -> Expr {
+    /// Larger exponents than this are no use in practice
+    const MAX_INTEGER_EXPONENT: f32 = 1024.0;
+    match exponent {
+        #[allow(clippy::cast_possible_truncation, clippy::float_cmp)]
+        Expr::Const(re, 0.0) if re.fract() == 0.0 && re.abs() <= MAX_INTEGER_EXPONENT => {
+            Expr::PowI(Box::new(base), re as i32)
+        }
+        _ => Expr::Binary(op::POW, Box::new(base), Box::new(exponent)),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::{compile, FormulaError};
+    use float_eq::assert_float_eq;
+    use pretty_assertions::assert_eq;
+    use shader_common::formula::{op, FORMULA_STACK_SIZE};
+
+    fn code(source: &str) -> Vec<u32> {
+        compile(source).unwrap()[2..].to_vec()
+    }
+
+    fn degree(source: &str) -> f32 {
+        f32::from_bits(compile(source).unwrap()[1])
+    }
+
+    fn error_at(source: &str) -> usize {
+        compile(source).unwrap_err().position
+    }
+
+    #[test]
+    fn mandelbrot() {
+        let program = compile("z^2 + c").unwrap();
+        assert_eq!(
+            program,
+            vec![5, 2.0f32.to_bits(), op::Z, op::POWI, 2, op::C, op::ADD]
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            code("z + c * n"),
+            vec![op::Z, op::C, op::N, op::MUL, op::ADD]
+        );
+        assert_eq!(
+            code("(z + c) * n"),
+            vec![op::Z, op::C, op::ADD, op::N, op::MUL]
+        );
+        assert_eq!(
+            code("z - c - n"),
+            vec![op::Z, op::C, op::SUB, op::N, op::SUB]
+        );
+        // ^ is right-associative, and binds more tightly than unary minus
+        assert_eq!(
+            code("-z^c^n"),
+            vec![op::Z, op::C, op::N, op::POW, op::POW, op::NEG]
+        );
+        // alternative symbols
+        assert_eq!(code("z × c ÷ n − z"), code("z * c / n - z"));
+    }
+
+    #[test]
+    fn constants() {
+        let two = 2.0f32.to_bits();
+        let zero = 0.0f32.to_bits();
+        let minus_half = (-0.5f32).to_bits();
+        assert_eq!(code("2"), vec![op::CONST, two, zero]);
+        assert_eq!(code("2i"), vec![op::CONST, zero, two]);
+        assert_eq!(
+            code("-.5"),
+            vec![op::CONST, minus_half, (-0.0f32).to_bits()]
+        );
+        assert_eq!(code("i"), vec![op::CONST, zero, 1.0f32.to_bits()]);
+        assert_eq!(code("2e1")[1], 20.0f32.to_bits());
+        // A non-integer exponent goes by way of the logarithm
+        assert_eq!(
+            code("z^2.5"),
+            vec![op::Z, op::CONST, 2.5f32.to_bits(), zero, op::POW]
+        );
+        #[allow(clippy::cast_sign_loss)]
+        let minus_two = -2i32 as u32;
+        assert_eq!(code("pow(z, -2)"), vec![op::Z, op::POWI, minus_two]);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            code("abs(z)^2 + c"),
+            vec![op::Z, op::ABS, op::POWI, 2, op::C, op::ADD]
+        );
+        for (name, opcode) in [
+            ("re", op::RE),
+            ("im", op::IM),
+            ("conj", op::CONJ),
+            ("sin", op::SIN),
+            ("cos", op::COS),
+            ("tan", op::TAN),
+            ("sinh", op::SINH),
+            ("cosh", op::COSH),
+            ("exp", op::EXP),
+            ("log", op::LOG),
+            ("ln", op::LOG),
+            ("sqrt", op::SQRT),
+        ] {
+            assert_eq!(code(&format!("{name}(z)")), vec![op::Z, opcode], "{name}");
+        }
+    }
+
+    #[test]
+    fn degrees() {
+        assert_float_eq!(degree("z^2 + c"), 2.0, ulps <= 0);
+        assert_float_eq!(degree("z^3 + z*c"), 3.0, ulps <= 0);
+        assert_float_eq!(degree("c*z*z*z*z"), 4.0, ulps <= 0);
+        assert_float_eq!(degree("z^2.5 + c"), 2.5, ulps <= 0);
+        assert_float_eq!(degree("abs(z)^5 + c"), 5.0, ulps <= 0);
+        assert_float_eq!(degree("(z^3 + c) / 2"), 3.0, ulps <= 0);
+        // not polynomials
+        assert_float_eq!(degree("c * sin(z)"), 2.0, ulps <= 0);
+        assert_float_eq!(degree("1 / z"), 2.0, ulps <= 0);
+        assert_float_eq!(degree("c"), 2.0, ulps <= 0);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error_at("z^2 +"), 5);
+        assert_eq!(error_at("z^2 + q"), 6);
+        assert_eq!(error_at("(z"), 2);
+        assert_eq!(error_at("z c"), 2);
+        assert_eq!(error_at("z $ c"), 2);
+        assert_eq!(error_at("sin z"), 4);
+        assert_eq!(error_at("pow(z)"), 5);
+        assert_eq!(error_at(""), 0);
+        let e = FormulaError::new(3, "oops");
+        assert_eq!(e.to_string(), "oops (at character 4)");
+    }
+
+    #[test]
+    fn limits() {
+        // Right-nested additions need one more stack entry each
+        let nest = |depth: usize| "(z+".repeat(depth - 1) + "z" + &")".repeat(depth - 1);
+        assert!(compile(&nest(FORMULA_STACK_SIZE)).is_ok());
+        assert!(compile(&nest(FORMULA_STACK_SIZE + 1)).is_err());
+        // Left-nested additions don't
+        let long = vec!["z"; 100].join("+");
+        assert!(compile(&long).is_ok());
+        let too_long = vec!["z"; 200].join("+");
+        assert!(compile(&too_long).is_err());
+    }
+}
@@ -6,8 +6,16 @@
 
 mod big_complex;
 mod big_vec2;
+mod decimal;
 mod dynfmt;
+mod formula;
+mod palette;
+mod reference_orbit;
 
 pub use big_complex::BigComplex;
 pub use big_vec2::BigVec2;
+pub use decimal::{parse_decimal, to_decimal_string};
 pub use dynfmt::dynamic_format;
+pub use formula::{compile as compile_formula, FormulaError};
+pub use palette::{import_palette, PaletteError, PaletteFormat};
+pub use reference_orbit::reference_orbit;
@@ -0,0 +1,503 @@
+//! Importers for palette files, which become the stops of a gradient
+//!
+//! See [`shader_common::gradient`].
+
+use std::path::Path;
+
+use glam::{vec3, Vec3};
+use shader_common::gradient::{parse_hex_colour, GradientStop, GRADIENT_SIZE};
+
+/// The palette file formats we understand
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// Fractint colour map (`.map`): one colour per line, as three numbers from 0 to 255
+    FractintMap,
+    /// GIMP gradient (`.ggr`)
+    GimpGradient,
+    /// A list of colours in CSS hex notation (`#rrggbb` or `#rgb`), evenly spaced
+    HexList,
+}
+
+impl PaletteFormat {
+    /// Works out the format of a file from its name.
+    ///
+    /// Anything we don't recognise is taken to be a list of hex colours.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("map") => Self::FractintMap,
+            Some("ggr") => Self::GimpGradient,
+            _ => Self::HexList,
+        }
+    }
+}
+
+/// An error found while importing a palette
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaletteError {
+    /// Line of the file where the problem was found, counting from 1 (0 if it applies to the whole file)
+    pub line: usize,
+    /// What went wrong
+    pub message: String,
+}
+
+impl PaletteError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{} (at line {})", self.message, self.line)
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+/// Converts a palette file to gradient stops.
+///
+/// ```
+/// use util::{import_palette, PaletteFormat};
+/// let stops = import_palette("#000 #ff0000, #ffffff", PaletteFormat::HexList).unwrap();
+/// assert_eq!(stops.len(), 3);
+/// assert_eq!(stops[1].position, 0.5);
+/// ```
+///
+/// # Errors
+/// If the file cannot be parsed, or has too many stops for the shader.
+pub fn import_palette(
+    text: &str,
+    format: PaletteFormat,
+) -> Result<Vec<GradientStop>, PaletteError> {
+    let stops = match format {
+        PaletteFormat::FractintMap => fractint_map(text)?,
+        PaletteFormat::GimpGradient => gimp_gradient(text)?,
+        PaletteFormat::HexList => hex_list(text)?,
+    };
+    if stops.is_empty() {
+        return Err(PaletteError::new(0, "the palette has no colours"));
+    }
+    if stops.len() > GRADIENT_SIZE as usize {
+        return Err(PaletteError::new(
+            0,
+            format!(
+                "the palette needs {} stops, but at most {GRADIENT_SIZE} are allowed",
+                stops.len()
+            ),
+        ));
+    }
+    Ok(stops)
+}
+
+/// Spreads colours evenly along the gradient
+#[allow(clippy::cast_precision_loss)]
+fn evenly_spaced(colours: Vec<Vec3>) -> Vec<GradientStop> {
+    let last = colours.len().saturating_sub(1).max(1) as f32;
+    colours
+        .into_iter()
+        .enumerate()
+        .map(|(i, colour)| GradientStop::new(i as f32 / last, colour))
+        .collect()
+}
+
+fn fractint_map(text: &str) -> Result<Vec<GradientStop>, PaletteError> {
+    let mut colours = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        // Anything after the three components is a comment
+        let component = |word: Option<&str>| {
+            word.and_then(|w| w.parse::<u8>().ok())
+                .map(|c| f32::from(c) / 255.)
+                .ok_or_else(|| PaletteError::new(i + 1, "expected three numbers between 0 and 255"))
+        };
+        colours.push(vec3(
+            component(Some(first))?,
+            component(words.next())?,
+            component(words.next())?,
+        ));
+    }
+    Ok(evenly_spaced(colours))
+}
+
+fn hex_list(text: &str) -> Result<Vec<GradientStop>, PaletteError> {
+    let mut colours = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        for word in line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|w| !w.is_empty())
+        {
+            colours.push(
+                parse_hex_colour(word)
+                    .ok_or_else(|| PaletteError::new(i + 1, format!("invalid colour '{word}'")))?,
+            );
+        }
+    }
+    Ok(evenly_spaced(colours))
+}
+
+/// How many stops we use to approximate a GIMP gradient segment which doesn't blend linearly in RGB
+const GIMP_SEGMENT_SAMPLES: u16 = 8;
+
+/// Blending functions of GIMP gradient segments
+#[derive(Clone, Copy, PartialEq)]
+enum GimpBlend {
+    Linear,
+    Curved,
+    Sine,
+    SphereIncreasing,
+    SphereDecreasing,
+    Step,
+}
+
+/// Colour spaces of GIMP gradient segments
+#[derive(Clone, Copy, PartialEq)]
+enum GimpColouring {
+    Rgb,
+    HsvAnticlockwise,
+    HsvClockwise,
+}
+
+struct GimpSegment {
+    left: f32,
+    middle: f32,
+    right: f32,
+    left_colour: Vec3,
+    right_colour: Vec3,
+    blend: GimpBlend,
+    colouring: GimpColouring,
+}
+
+impl GimpSegment {
+    fn parse(line: &str, number: usize) -> Result<Self, PaletteError> {
+        let error = |message: &str| PaletteError::new(number, message);
+        let values = line
+            .split_whitespace()
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| error("expected a segment, written as numbers"))?;
+        // Newer files add the types of the endpoint colours, which we ignore
+        if values.len() < 13 {
+            return Err(error("expected a segment of at least 13 numbers"));
+        }
+        let blend = match values[11] {
+            0. => GimpBlend::Linear,
+            1. => GimpBlend::Curved,
+            2. => GimpBlend::Sine,
+            3. => GimpBlend::SphereIncreasing,
+            4. => GimpBlend::SphereDecreasing,
+            5. => GimpBlend::Step,
+            _ => return Err(error("unknown blending function")),
+        };
+        let colouring = match values[12] {
+            0. => GimpColouring::Rgb,
+            1. => GimpColouring::HsvAnticlockwise,
+            2. => GimpColouring::HsvClockwise,
+            _ => return Err(error("unknown colour type")),
+        };
+        let segment = Self {
+            left: values[0],
+            middle: values[1],
+            right: values[2],
+            // The alpha channels (values 6 and 10) are ignored
+            left_colour: vec3(values[3], values[4], values[5]),
+            right_colour: vec3(values[7], values[8], values[9]),
+            blend,
+            colouring,
+        };
+        if !(0.0..=1.0).contains(&segment.left)
+            || !(segment.left..=segment.right).contains(&segment.middle)
+            || segment.right > 1.0
+        {
+            return Err(error("segment positions must be in order, between 0 and 1"));
+        }
+        Ok(segment)
+    }
+
+    /// How far from the left colour to the right colour the gradient is, at a position within the segment.
+    ///
+    /// This follows GIMP's `gimp_gradient_get_color_at`.
+    fn factor(&self, position: f32) -> f32 {
+        const EPSILON: f32 = 1e-10;
+        let length = self.right - self.left;
+        let (middle, position) = if length < EPSILON {
+            (0.5, 0.5)
+        } else {
+            (
+                (self.middle - self.left) / length,
+                (position - self.left) / length,
+            )
+        };
+        let linear = || {
+            if position <= middle {
+                if middle < EPSILON {
+                    0.
+                } else {
+                    0.5 * position / middle
+                }
+            } else if 1. - middle < EPSILON {
+                1.
+            } else {
+                0.5 + 0.5 * (position - middle) / (1. - middle)
+            }
+        };
+        match self.blend {
+            GimpBlend::Linear => linear(),
+            GimpBlend::Curved => position.powf(0.5_f32.ln() / middle.max(EPSILON).ln()),
+            GimpBlend::Sine => {
+                ((-std::f32::consts::FRAC_PI_2 + std::f32::consts::PI * linear()).sin() + 1.) * 0.5
+            }
+            GimpBlend::SphereIncreasing => {
+                let p = linear() - 1.;
+                (1. - p * p).sqrt()
+            }
+            GimpBlend::SphereDecreasing => {
+                let p = linear();
+                1. - (1. - p * p).sqrt()
+            }
+            GimpBlend::Step => {
+                if position >= middle {
+                    1.
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+
+    fn colour_at(&self, position: f32) -> Vec3 {
+        let factor = self.factor(position);
+        match self.colouring {
+            GimpColouring::Rgb => self.left_colour.lerp(self.right_colour, factor),
+            GimpColouring::HsvAnticlockwise | GimpColouring::HsvClockwise => {
+                let (left, right) = (rgb_to_hsv(self.left_colour), rgb_to_hsv(self.right_colour));
+                // Hue is in turns; go round the way the segment asks
+                let mut difference = right.x - left.x;
+                if self.colouring == GimpColouring::HsvAnticlockwise && difference < 0. {
+                    difference += 1.;
+                } else if self.colouring == GimpColouring::HsvClockwise && difference > 0. {
+                    difference -= 1.;
+                }
+                let mut hsv = left.lerp(right, factor);
+                hsv.x = (left.x + difference * factor).rem_euclid(1.);
+                hsv_to_rgb(hsv)
+            }
+        }
+    }
+
+    /// Stops which reproduce the segment
+    fn stops(&self) -> Vec<GradientStop> {
+        if self.blend == GimpBlend::Linear && self.colouring == GimpColouring::Rgb {
+            // Linear blending is linear on either side of the midpoint, so three stops are exact
+            return vec![
+                GradientStop::new(self.left, self.left_colour),
+                GradientStop::new(self.middle, self.colour_at(self.middle)),
+                GradientStop::new(self.right, self.right_colour),
+            ];
+        }
+        (0..=GIMP_SEGMENT_SAMPLES)
+            .map(|i| {
+                let position = self.left
+                    + (self.right - self.left) * f32::from(i) / f32::from(GIMP_SEGMENT_SAMPLES);
+                GradientStop::new(position, self.colour_at(position))
+            })
+            .collect()
+    }
+}
+
+fn gimp_gradient(text: &str) -> Result<Vec<GradientStop>, PaletteError> {
+    let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+    if lines.next().map(|(_, l)| l) != Some("GIMP Gradient") {
+        return Err(PaletteError::new(1, "this is not a GIMP gradient file"));
+    }
+    let mut line = lines.next();
+    if let Some((_, l)) = line
+        && l.starts_with("Name:")
+    {
+        line = lines.next();
+    }
+    let (number, count) = line.unwrap_or((0, ""));
+    let count: usize = count
+        .parse()
+        .map_err(|_| PaletteError::new(number, "expected the number of segments"))?;
+
+    let mut stops: Vec<GradientStop> = Vec::new();
+    for _ in 0..count {
+        let (number, line) = lines
+            .next()
+            .ok_or_else(|| PaletteError::new(0, "the file ended before the last segment"))?;
+        for stop in GimpSegment::parse(line, number)?.stops() {
+            // Adjacent segments usually share the colour where they meet
+            if stops.last() != Some(&stop) {
+                stops.push(stop);
+            }
+        }
+    }
+    Ok(stops)
+}
+
+/// Converts RGB (0..1) to HSV, with the hue in turns
+#[allow(clippy::float_cmp)] // the maximum is one of the components
+fn rgb_to_hsv(rgb: Vec3) -> Vec3 {
+    let max = rgb.max_element();
+    let chroma = max - rgb.min_element();
+    let hue = if chroma == 0. {
+        0.
+    } else if max == rgb.x {
+        ((rgb.y - rgb.z) / chroma).rem_euclid(6.)
+    } else if max == rgb.y {
+        (rgb.z - rgb.x) / chroma + 2.
+    } else {
+        (rgb.x - rgb.y) / chroma + 4.
+    } / 6.;
+    let saturation = if max == 0. { 0. } else { chroma / max };
+    vec3(hue, saturation, max)
+}
+
+/// Converts HSV, with the hue in turns, to RGB (0..1)
+fn hsv_to_rgb(hsv: Vec3) -> Vec3 {
+    let component = |n: f32| {
+        let k = (n + hsv.x * 6.) % 6.;
+        hsv.z - hsv.z * hsv.y * k.min(4. - k).clamp(0., 1.)
+    };
+    vec3(component(5.), component(3.), component(1.))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+#[allow(clippy::float_cmp)] // the positions of the stops are exact
+mod tests {
+    use super::{hsv_to_rgb, import_palette, rgb_to_hsv, PaletteFormat};
+    use float_eq::assert_float_eq;
+    use glam::vec3;
+    use pretty_assertions::assert_eq;
+    use std::path::Path;
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            PaletteFormat::from_path(Path::new("x/blues.MAP")),
+            PaletteFormat::FractintMap
+        );
+        assert_eq!(
+            PaletteFormat::from_path(Path::new("Sunrise.ggr")),
+            PaletteFormat::GimpGradient
+        );
+        assert_eq!(
+            PaletteFormat::from_path(Path::new("colours.txt")),
+            PaletteFormat::HexList
+        );
+        assert_eq!(
+            PaletteFormat::from_path(Path::new("colours")),
+            PaletteFormat::HexList
+        );
+    }
+
+    #[test]
+    fn fractint() {
+        let stops = import_palette(
+            "0 0 0\n\n255 128 0 orange, more or less\n  0 0 255\n",
+            PaletteFormat::FractintMap,
+        )
+        .unwrap();
+        assert_eq!(stops.len(), 3);
+        assert_eq!(stops[0].position, 0.);
+        assert_eq!(stops[1].position, 0.5);
+        assert_eq!(stops[2].position, 1.);
+        assert_eq!(stops[1].colour(), vec3(1., 128. / 255., 0.));
+        assert_eq!(stops[2].colour(), vec3(0., 0., 1.));
+
+        let error = import_palette("0 0 0\n1 2\n", PaletteFormat::FractintMap).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(import_palette("0 0 256", PaletteFormat::FractintMap).is_err());
+        let too_long = "1 2 3\n".repeat(257);
+        assert!(import_palette(&too_long, PaletteFormat::FractintMap).is_err());
+    }
+
+    #[test]
+    fn hex() {
+        let stops = import_palette("#000000, #fff;\n#ff0000", PaletteFormat::HexList).unwrap();
+        assert_eq!(stops.len(), 3);
+        assert_eq!(stops[1].colour(), vec3(1., 1., 1.));
+        assert_eq!(stops[2].position, 1.);
+        // A single colour is a plain gradient
+        let stops = import_palette("#123456", PaletteFormat::HexList).unwrap();
+        assert_eq!(stops[0].position, 0.);
+
+        let error = import_palette("#000\n#ff00zz", PaletteFormat::HexList).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(import_palette("  \n", PaletteFormat::HexList).is_err());
+    }
+
+    #[test]
+    fn gimp() {
+        let ggr = "GIMP Gradient
+Name: Test
+2
+0.000000 0.250000 0.500000 0.000000 0.000000 0.000000 1.000000 1.000000 0.000000 0.000000 1.000000 0 0
+0.500000 0.750000 1.000000 1.000000 0.000000 0.000000 1.000000 0.000000 0.000000 1.000000 1.000000 2 0 0 0
+";
+        let stops = import_palette(ggr, PaletteFormat::GimpGradient).unwrap();
+        // The first segment is exact; the second is sampled, sharing its first stop with the first
+        assert_eq!(stops.len(), 3 + 8);
+        assert_eq!(stops[1].position, 0.25);
+        assert_float_eq!(stops[1].colour().to_array(), [0.5, 0., 0.], abs_all <= 1e-6);
+        assert_eq!(stops[2].colour(), vec3(1., 0., 0.));
+        assert_eq!(stops.last().unwrap().colour(), vec3(0., 0., 1.));
+        // The sine blend is halfway at the midpoint
+        let middle = stops.iter().find(|s| s.position == 0.75).unwrap();
+        assert_float_eq!(middle.colour().to_array(), [0.5, 0., 0.5], abs_all <= 1e-6);
+
+        assert!(import_palette("GIMP Palette\n", PaletteFormat::GimpGradient).is_err());
+        assert!(import_palette("GIMP Gradient\n2\n", PaletteFormat::GimpGradient).is_err());
+        let error = import_palette(
+            "GIMP Gradient\n1\n0 0.5 1 0 0 0 1\n",
+            PaletteFormat::GimpGradient,
+        )
+        .unwrap_err();
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn gimp_hsv() {
+        // Red to blue, anticlockwise (through green) and clockwise (through magenta)
+        let ggr = |colouring| format!("GIMP Gradient\n1\n0 0.5 1 1 0 0 1 0 0 1 1 0 {colouring}\n");
+        let stops = import_palette(&ggr(1), PaletteFormat::GimpGradient).unwrap();
+        let middle = stops.iter().find(|s| s.position == 0.5).unwrap();
+        assert_float_eq!(middle.colour().to_array(), [0., 1., 0.], abs_all <= 1e-5);
+        let stops = import_palette(&ggr(2), PaletteFormat::GimpGradient).unwrap();
+        let middle = stops.iter().find(|s| s.position == 0.5).unwrap();
+        assert_float_eq!(middle.colour().to_array(), [1., 0., 1.], abs_all <= 1e-5);
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for rgb in [
+            vec3(0., 0., 0.),
+            vec3(1., 1., 1.),
+            vec3(1., 0.5, 0.),
+            vec3(0.2, 0.4, 0.8),
+            vec3(0.9, 0.1, 0.5),
+        ] {
+            assert_float_eq!(
+                hsv_to_rgb(rgb_to_hsv(rgb)).to_array(),
+                rgb.to_array(),
+                abs_all <= 1e-6
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! High precision reference orbits, for rendering by perturbation
+// (c) 2025 Ross Younger
+
+use crate::BigComplex;
+use glam::Vec2;
+
+/// Computes the Mandelbrot reference orbit `Z_0 = 0, Z_{n+1} = Z_n^2 + C` at the
+/// precision of `centre`.
+///
+/// The points are returned at `f32` precision, which is all the shader needs: only the
+/// per-pixel deltas from the orbit have to be precise.
+/// Iteration stops after `max_iter` iterations, or once `|Z|^2` exceeds `escape_sq`,
+/// so the result contains between 2 and `max_iter + 1` points.
+///
+/// ```
+/// # use util::{make_complex, reference_orbit};
+/// let orbit = reference_orbit(&make_complex!(1.0, 0.0), 10, 100.0);
+/// assert_eq!(orbit, [[0., 0.], [1., 0.], [2., 0.], [5., 0.], [26., 0.]].map(glam::Vec2::from));
+/// ```
+#[must_use]
+pub fn reference_orbit(centre: &BigComplex, max_iter: u32, escape_sq: f32) -> Vec<Vec2> {
+    let mut orbit = Vec::with_capacity(max_iter as usize + 1);
+    let mut z = BigComplex::ZERO.with_precision(centre.x.precision());
+    orbit.push(z.as_vec2());
+    for _ in 0..max_iter.max(1) {
+        z = z.square() + centre.clone();
+        let point = z.as_vec2();
+        orbit.push(point);
+        if point.length_squared() > escape_sq {
+            break;
+        }
+    }
+    orbit
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::reference_orbit;
+    use crate::{make_complex, BigComplex};
+    use glam::vec2;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn inside() {
+        // The origin is a fixed point
+        let orbit = reference_orbit(&BigComplex::ZERO, 50, 100.);
+        assert_eq!(orbit.len(), 51);
+        assert!(orbit.iter().all(|z| *z == vec2(0., 0.)));
+
+        // -1 is in the period-2 bulb
+        let orbit = reference_orbit(&make_complex!(-1.0, 0.0), 4, 100.);
+        assert_eq!(
+            orbit,
+            [
+                vec2(0., 0.),
+                vec2(-1., 0.),
+                vec2(0., 0.),
+                vec2(-1., 0.),
+                vec2(0., 0.)
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let orbit = reference_orbit(&make_complex!(0.0, 1.0), 10, 4.);
+        // 0, i, -1+i, -i, -1+i, ... never escapes 4
+        assert_eq!(orbit.len(), 11);
+        assert_eq!(orbit[3], vec2(0., -1.));
+
+        let orbit = reference_orbit(&make_complex!(0.5, 0.5), 100, 100.);
+        assert!(orbit.len() < 100);
+        assert!(orbit.last().unwrap().length_squared() > 100.);
+    }
+
+    #[test]
+    fn high_precision() {
+        // -2 is a fixed point after two iterations.
+        let orbit = reference_orbit(&make_complex!(-2.0, 0.0), 20, 100.);
+        assert_eq!(orbit.len(), 21);
+        assert_eq!(orbit[20], vec2(2., 0.));
+
+        // A point which differs from -2 in the 100th binary place must stay just inside 2,
+        // which is only possible if the tiny difference was carried through.
+        // (The conversion to f32 rounds towards zero.)
+        let tiny = dashu::float::FBig::try_from(2.0f64.powi(-100)).unwrap();
+        let mut c = make_complex!(-2.0, 0.0).with_precision(256);
+        c.x += tiny;
+        let orbit = reference_orbit(&c, 20, 100.);
+        assert_eq!(orbit.len(), 21);
+        assert!(orbit[20].x < 2.);
+        assert!(orbit[20].abs_diff_eq(vec2(2., 0.), 1e-6));
+    }
+}